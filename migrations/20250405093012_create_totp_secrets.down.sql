@@ -0,0 +1,9 @@
+-- Add down migration script here
+ALTER TABLE authentications DROP COLUMN IF EXISTS second_factor_pending;
+
+DROP INDEX IF EXISTS idx_totp_secrets_id;
+DROP INDEX IF EXISTS idx_totp_secrets_user_id;
+DROP INDEX IF EXISTS idx_totp_secrets_created_at;
+DROP INDEX IF EXISTS idx_totp_secrets_updated_at;
+DROP INDEX IF EXISTS idx_totp_secrets_archived_at;
+DROP TABLE IF EXISTS totp_secrets;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS totp_secrets (
+    id VARCHAR(255) PRIMARY KEY,
+    user_id VARCHAR(255) NOT NULL REFERENCES users(id),
+    secret VARCHAR(255) NOT NULL,
+    last_used_step BIGINT,
+    confirmed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    archived_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_totp_secrets_id ON totp_secrets (id);
+CREATE INDEX IF NOT EXISTS idx_totp_secrets_user_id ON totp_secrets (user_id);
+CREATE INDEX IF NOT EXISTS idx_totp_secrets_created_at ON totp_secrets (created_at);
+CREATE INDEX IF NOT EXISTS idx_totp_secrets_updated_at ON totp_secrets (updated_at);
+CREATE INDEX IF NOT EXISTS idx_totp_secrets_archived_at ON totp_secrets (archived_at);
+
+ALTER TABLE authentications ADD COLUMN IF NOT EXISTS second_factor_pending BOOLEAN NOT NULL DEFAULT FALSE;
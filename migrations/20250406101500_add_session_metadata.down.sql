@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_authentications_last_seen_at;
+
+ALTER TABLE authentications DROP COLUMN IF EXISTS last_seen_at;
+ALTER TABLE authentications DROP COLUMN IF EXISTS ip_address;
+ALTER TABLE authentications DROP COLUMN IF EXISTS user_agent;
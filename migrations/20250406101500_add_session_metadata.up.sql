@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE authentications ADD COLUMN IF NOT EXISTS user_agent VARCHAR(255);
+ALTER TABLE authentications ADD COLUMN IF NOT EXISTS ip_address VARCHAR(45);
+ALTER TABLE authentications ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS idx_authentications_last_seen_at ON authentications (last_seen_at);
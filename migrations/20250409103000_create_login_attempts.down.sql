@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_login_attempts_blocked_until;
+DROP INDEX IF EXISTS idx_login_attempts_last_failed_at;
+DROP TABLE IF EXISTS login_attempts;
@@ -0,0 +1,4 @@
+-- Add down migration script here
+-- Hashed tokens can't be turned back into plaintext, so sessions are ended.
+DELETE FROM refresh_tokens;
+DELETE FROM authentications;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Session and refresh tokens were stored in plaintext and can't be converted
+-- without the server secret, so existing sessions are ended. Backup codes
+-- are hashed in place by the server on startup.
+DELETE FROM refresh_tokens;
+DELETE FROM authentications;
@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_password_resets_id;
+DROP INDEX IF EXISTS idx_password_resets_token;
+DROP INDEX IF EXISTS idx_password_resets_user_id;
+DROP INDEX IF EXISTS idx_password_resets_expires_at;
+DROP INDEX IF EXISTS idx_password_resets_archived_at;
+DROP TABLE IF EXISTS password_resets;
+
+DROP INDEX IF EXISTS idx_users_email;
+
+ALTER TABLE users DROP COLUMN IF EXISTS email;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_api_keys_id;
+DROP INDEX IF EXISTS idx_api_keys_token;
+DROP INDEX IF EXISTS idx_api_keys_user_id;
+DROP INDEX IF EXISTS idx_api_keys_archived_at;
+DROP TABLE IF EXISTS api_keys;
@@ -0,0 +1,10 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_oidc_logins_id;
+DROP INDEX IF EXISTS idx_oidc_logins_state;
+DROP INDEX IF EXISTS idx_oidc_logins_expires_at;
+DROP TABLE IF EXISTS oidc_logins;
+
+DROP INDEX IF EXISTS idx_user_identities_id;
+DROP INDEX IF EXISTS idx_user_identities_issuer_subject;
+DROP INDEX IF EXISTS idx_user_identities_user_id;
+DROP TABLE IF EXISTS user_identities;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events;
+DROP FUNCTION IF EXISTS audit_events_append_only();
+
+DROP INDEX IF EXISTS idx_audit_events_user_id_created_at;
+DROP INDEX IF EXISTS idx_audit_events_team_id_created_at;
+DROP INDEX IF EXISTS idx_audit_events_actor_id;
+DROP TABLE IF EXISTS audit_events;
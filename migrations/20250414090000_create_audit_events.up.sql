@@ -0,0 +1,28 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS audit_events (
+    id VARCHAR(255) PRIMARY KEY,
+    actor_id VARCHAR(255),
+    user_id VARCHAR(255),
+    team_id VARCHAR(255),
+    action VARCHAR(255) NOT NULL,
+    target_type VARCHAR(255),
+    target_id VARCHAR(255),
+    ip_address VARCHAR(255),
+    user_agent VARCHAR(255),
+    details JSONB NOT NULL DEFAULT '{}'::JSONB,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_user_id_created_at ON audit_events (user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_events_team_id_created_at ON audit_events (team_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_audit_events_actor_id ON audit_events (actor_id);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_users_is_admin;
+
+ALTER TABLE users DROP COLUMN IF EXISTS is_admin;
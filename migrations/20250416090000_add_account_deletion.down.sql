@@ -0,0 +1,6 @@
+-- Add down migration script here
+DELETE FROM activities WHERE assigned_to IS NULL;
+ALTER TABLE activities ALTER COLUMN assigned_to SET NOT NULL;
+
+DROP INDEX IF EXISTS idx_users_purge_after;
+ALTER TABLE users DROP COLUMN IF EXISTS purge_after;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS purge_after TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_users_purge_after ON users (purge_after) WHERE purge_after IS NOT NULL;
+
+ALTER TABLE activities ALTER COLUMN assigned_to DROP NOT NULL;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_data_exports_id;
+DROP INDEX IF EXISTS idx_data_exports_user_id;
+DROP INDEX IF EXISTS idx_data_exports_status;
+DROP INDEX IF EXISTS idx_data_exports_expires_at;
+DROP TABLE IF EXISTS data_exports;
@@ -0,0 +1,7 @@
+-- Add down migration script here
+CREATE TYPE team_role_enum AS ENUM ('admin', 'manager', 'member');
+
+ALTER TABLE invitations
+ALTER COLUMN team_role DROP DEFAULT,
+ALTER COLUMN team_role TYPE team_role_enum USING team_role::team_role_enum,
+ALTER COLUMN team_role SET DEFAULT 'member';
@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- Store invitation roles as text, like teams_users, so they bind and decode
+-- the same way as every other role column.
+ALTER TABLE invitations
+ALTER COLUMN team_role DROP DEFAULT,
+ALTER COLUMN team_role TYPE VARCHAR(255) USING team_role::TEXT,
+ALTER COLUMN team_role SET DEFAULT 'member';
+
+DROP TYPE IF EXISTS team_role_enum;
+
+-- Invitations accepted before acceptance created a membership.
+INSERT INTO
+    teams_users (id, team_id, user_id, team_role, created_at, updated_at)
+SELECT
+    invitations.id,
+    invitations.team_id,
+    invitations.user_id,
+    invitations.team_role,
+    invitations.accepted_at,
+    invitations.accepted_at
+FROM
+    invitations
+    JOIN teams ON teams.id = invitations.team_id
+WHERE
+    invitations.accepted_at IS NOT NULL
+    AND invitations.archived_at IS NULL
+    AND teams.owner_id <> invitations.user_id
+ON CONFLICT (team_id, user_id) DO NOTHING;
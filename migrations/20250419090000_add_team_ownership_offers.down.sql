@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_teams_pending_owner_id;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS ownership_offered_at,
+DROP COLUMN IF EXISTS pending_owner_id;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS pending_owner_id VARCHAR(255) REFERENCES users (id) ON DELETE SET NULL,
+ADD COLUMN IF NOT EXISTS ownership_offered_at TIMESTAMP
+WITH
+    TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_teams_pending_owner_id ON teams (pending_owner_id)
+WHERE
+    pending_owner_id IS NOT NULL;
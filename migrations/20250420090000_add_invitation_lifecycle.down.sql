@@ -0,0 +1,11 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_invitations_pending_team_id_user_id;
+
+DROP INDEX IF EXISTS idx_invitations_expires_at;
+
+DROP INDEX IF EXISTS idx_invitations_invited_by;
+
+ALTER TABLE invitations
+DROP COLUMN IF EXISTS revoked_at,
+DROP COLUMN IF EXISTS expires_at,
+DROP COLUMN IF EXISTS invited_by;
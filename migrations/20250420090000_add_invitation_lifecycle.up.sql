@@ -0,0 +1,39 @@
+-- Add up migration script here
+ALTER TABLE invitations
+ADD COLUMN IF NOT EXISTS invited_by VARCHAR(255) REFERENCES users (id) ON DELETE SET NULL,
+ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP
+WITH
+    TIME ZONE,
+ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMP
+WITH
+    TIME ZONE;
+
+UPDATE invitations
+SET
+    expires_at = created_at + INTERVAL '7 days'
+WHERE
+    expires_at IS NULL;
+
+ALTER TABLE invitations
+ALTER COLUMN expires_at
+SET NOT NULL;
+
+-- A user and a team each used to be limited to a single invitation.
+DROP INDEX IF EXISTS idx_invitations_user_id;
+
+DROP INDEX IF EXISTS idx_invitations_team_id;
+
+CREATE INDEX IF NOT EXISTS idx_invitations_user_id ON invitations (user_id);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_team_id ON invitations (team_id);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_invited_by ON invitations (invited_by);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_expires_at ON invitations (expires_at);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_invitations_pending_team_id_user_id ON invitations (team_id, user_id)
+WHERE
+    accepted_at IS NULL
+    AND rejected_at IS NULL
+    AND revoked_at IS NULL
+    AND archived_at IS NULL;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_teams_users_join_link_id;
+
+ALTER TABLE teams_users
+DROP COLUMN IF EXISTS join_link_id;
+
+DROP TABLE IF EXISTS join_requests;
+
+DROP TABLE IF EXISTS join_links;
@@ -0,0 +1,76 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS join_links (
+        id VARCHAR(255) PRIMARY KEY,
+        team_id VARCHAR(255) NOT NULL REFERENCES teams (id),
+        created_by VARCHAR(255) REFERENCES users (id) ON DELETE SET NULL,
+        code VARCHAR(255) NOT NULL,
+        team_role VARCHAR(255) NOT NULL,
+        requires_approval BOOLEAN NOT NULL DEFAULT FALSE,
+        max_uses INTEGER,
+        use_count INTEGER NOT NULL DEFAULT 0,
+        expires_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL,
+            revoked_at TIMESTAMP
+        WITH
+            TIME ZONE,
+            created_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            archived_at TIMESTAMP
+        WITH
+            TIME ZONE
+    );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_join_links_code ON join_links (code);
+
+CREATE INDEX IF NOT EXISTS idx_join_links_team_id ON join_links (team_id);
+
+CREATE INDEX IF NOT EXISTS idx_join_links_created_at ON join_links (created_at);
+
+CREATE TABLE
+    IF NOT EXISTS join_requests (
+        id VARCHAR(255) PRIMARY KEY,
+        team_id VARCHAR(255) NOT NULL REFERENCES teams (id),
+        user_id VARCHAR(255) NOT NULL REFERENCES users (id),
+        join_link_id VARCHAR(255) REFERENCES join_links (id),
+        team_role VARCHAR(255) NOT NULL,
+        decided_by VARCHAR(255) REFERENCES users (id) ON DELETE SET NULL,
+        approved_at TIMESTAMP
+        WITH
+            TIME ZONE,
+            denied_at TIMESTAMP
+        WITH
+            TIME ZONE,
+            created_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            archived_at TIMESTAMP
+        WITH
+            TIME ZONE
+    );
+
+CREATE INDEX IF NOT EXISTS idx_join_requests_team_id ON join_requests (team_id);
+
+CREATE INDEX IF NOT EXISTS idx_join_requests_user_id ON join_requests (user_id);
+
+CREATE INDEX IF NOT EXISTS idx_join_requests_join_link_id ON join_requests (join_link_id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_join_requests_pending_team_id_user_id ON join_requests (team_id, user_id)
+WHERE
+    approved_at IS NULL
+    AND denied_at IS NULL
+    AND archived_at IS NULL;
+
+-- Remembers which link a member came in through.
+ALTER TABLE teams_users
+ADD COLUMN IF NOT EXISTS join_link_id VARCHAR(255) REFERENCES join_links (id);
+
+CREATE INDEX IF NOT EXISTS idx_teams_users_join_link_id ON teams_users (join_link_id);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE join_requests
+DROP COLUMN IF EXISTS message;
+
+DROP INDEX IF EXISTS idx_teams_is_discoverable;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS is_discoverable;
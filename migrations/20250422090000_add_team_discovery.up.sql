@@ -0,0 +1,11 @@
+-- Add up migration script here
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS is_discoverable BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_teams_is_discoverable ON teams (is_discoverable)
+WHERE
+    is_discoverable
+    AND archived_at IS NULL;
+
+ALTER TABLE join_requests
+ADD COLUMN IF NOT EXISTS message TEXT;
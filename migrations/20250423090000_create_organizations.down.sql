@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_teams_organization_id;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS organization_id;
+
+DROP TABLE IF EXISTS organizations_users;
+
+DROP TABLE IF EXISTS organizations;
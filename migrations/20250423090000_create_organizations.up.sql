@@ -0,0 +1,119 @@
+-- Add up migration script here
+CREATE TABLE
+    IF NOT EXISTS organizations (
+        id VARCHAR(255) PRIMARY KEY,
+        organization_name VARCHAR(255) NOT NULL,
+        organization_description TEXT,
+        created_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            archived_at TIMESTAMP
+        WITH
+            TIME ZONE
+    );
+
+CREATE INDEX IF NOT EXISTS idx_organizations_created_at ON organizations (created_at);
+
+CREATE INDEX IF NOT EXISTS idx_organizations_archived_at ON organizations (archived_at);
+
+CREATE TABLE
+    IF NOT EXISTS organizations_users (
+        id VARCHAR(255) PRIMARY KEY,
+        organization_id VARCHAR(255) NOT NULL REFERENCES organizations (id),
+        user_id VARCHAR(255) NOT NULL REFERENCES users (id),
+        organization_role VARCHAR(255) NOT NULL,
+        created_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TIMESTAMP
+        WITH
+            TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            archived_at TIMESTAMP
+        WITH
+            TIME ZONE
+    );
+
+CREATE INDEX IF NOT EXISTS idx_organizations_users_organization_id ON organizations_users (organization_id);
+
+CREATE INDEX IF NOT EXISTS idx_organizations_users_user_id ON organizations_users (user_id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_organizations_users_organization_id_user_id ON organizations_users (organization_id, user_id);
+
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS organization_id VARCHAR(255) REFERENCES organizations (id);
+
+CREATE INDEX IF NOT EXISTS idx_teams_organization_id ON teams (organization_id);
+
+-- Everything that already exists goes into one organization, so nobody
+-- loses sight of the users and teams they could see before. Site admins
+-- own it; without any, the earliest user does.
+INSERT INTO
+    organizations (id, organization_name)
+SELECT
+    gen_random_uuid ()::VARCHAR,
+    'Default organization'
+WHERE
+    EXISTS (
+        SELECT
+            1
+        FROM
+            users
+    );
+
+INSERT INTO
+    organizations_users (id, organization_id, user_id, organization_role)
+SELECT
+    gen_random_uuid ()::VARCHAR,
+    organizations.id,
+    users.id,
+    CASE
+        WHEN users.is_admin THEN 'owner'
+        ELSE 'member'
+    END
+FROM
+    users
+    CROSS JOIN organizations
+ON CONFLICT (organization_id, user_id) DO NOTHING;
+
+UPDATE organizations_users
+SET
+    organization_role = 'owner'
+WHERE
+    user_id = (
+        SELECT
+            id
+        FROM
+            users
+        ORDER BY
+            created_at ASC
+        LIMIT
+            1
+    )
+    AND NOT EXISTS (
+        SELECT
+            1
+        FROM
+            organizations_users
+        WHERE
+            organization_role = 'owner'
+    );
+
+UPDATE teams
+SET
+    organization_id = (
+        SELECT
+            id
+        FROM
+            organizations
+        LIMIT
+            1
+    )
+WHERE
+    organization_id IS NULL;
+
+ALTER TABLE teams
+ALTER COLUMN organization_id
+SET NOT NULL;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_teams_parent_team_id;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS inherits_permissions;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS parent_team_id;
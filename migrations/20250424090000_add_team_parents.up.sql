@@ -0,0 +1,8 @@
+-- Add up migration script here
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS parent_team_id VARCHAR(255) REFERENCES teams (id) ON DELETE SET NULL;
+
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS inherits_permissions BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_teams_parent_team_id ON teams (parent_team_id);
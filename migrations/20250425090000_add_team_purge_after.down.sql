@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_teams_purge_after;
+
+ALTER TABLE teams
+DROP COLUMN IF EXISTS purge_after;
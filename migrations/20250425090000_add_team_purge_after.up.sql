@@ -0,0 +1,9 @@
+-- Add up migration script here
+ALTER TABLE teams
+ADD COLUMN IF NOT EXISTS purge_after TIMESTAMP
+WITH
+    TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_teams_purge_after ON teams (purge_after)
+WHERE
+    purge_after IS NOT NULL;
@@ -0,0 +1,438 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{token_error_status, AdminToken};
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::AuthenticationError;
+use crate::models::backup_code::BackupCodeError;
+use crate::models::user::{User, UserError};
+use crate::utils::admin::{search_users, set_admin};
+use crate::utils::api_keys::revoke_user_api_keys;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::backup_codes::reset_backup_codes;
+use crate::utils::sessions::revoke_user_sessions;
+use crate::{delete_resource_where_fields, find_one_resource_where_fields, update_resource};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    User(UserError),
+    BackupCode(BackupCodeError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<UserError> for ResponseError {
+    fn from(error: UserError) -> Self {
+        ResponseError::User(error)
+    }
+}
+
+impl From<BackupCodeError> for ResponseError {
+    fn from(error: BackupCodeError) -> Self {
+        ResponseError::BackupCode(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUsersResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl AdminUsersResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+fn admin_error(err: AuthenticationError) -> status::Custom<Value> {
+    println!("Error validating admin: {:?}", err);
+    status::Custom(
+        token_error_status(&err),
+        serde_json::to_value(AdminUsersResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+fn user_not_found() -> status::Custom<Value> {
+    status::Custom(
+        Status::NotFound,
+        serde_json::to_value(AdminUsersResponse::error(
+            UserError::UserNotFound,
+            UserError::UserNotFound.to_string(),
+        ))
+        .unwrap(),
+    )
+}
+
+/// Admins can't lock themselves out by archiving or demoting their own
+/// account; another admin has to do it.
+fn refuse_self(admin: &AdminToken, id: &str) -> Option<status::Custom<Value>> {
+    if admin.user_id() != id {
+        return None;
+    }
+    Some(status::Custom(
+        Status::BadRequest,
+        serde_json::to_value(AdminUsersResponse::error(
+            AuthenticationError::InvalidRequest,
+            "Administrators can't do this to their own account".to_string(),
+        ))
+        .unwrap(),
+    ))
+}
+
+async fn find_user(id: &str) -> Option<User> {
+    let user_params = [("id", id.to_string())];
+    match find_one_resource_where_fields!(User, user_params).await {
+        Ok(user) => Some(user),
+        Err(err) => {
+            println!("Error finding user: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Searches users by username, name or email. Set `archived` to search
+/// archived accounts instead of active ones.
+#[get("/?<q>&<archived>&<limit>&<offset>")]
+pub async fn get_users(
+    admin: Result<AdminToken, AuthenticationError>,
+    q: Option<String>,
+    archived: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> status::Custom<Value> {
+    if let Err(err) = admin {
+        return admin_error(err);
+    }
+
+    match search_users(q.as_deref(), archived.unwrap_or(false), limit, offset).await {
+        Ok(users) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(AdminUsersResponse::success(
+                serde_json::to_value(users).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AdminUsersResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
+
+#[get("/<id>")]
+pub async fn get_user(
+    admin: Result<AdminToken, AuthenticationError>,
+    id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = admin {
+        return admin_error(err);
+    }
+
+    match find_user(&id).await {
+        Some(user) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(AdminUsersResponse::success(
+                serde_json::to_value(user).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        None => user_not_found(),
+    }
+}
+
+/// Archives a user and signs them out everywhere, including their API keys.
+#[post("/<id>/archive")]
+pub async fn archive_user(
+    admin: Result<AdminToken, AuthenticationError>,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let admin = match admin {
+        Ok(admin) => admin,
+        Err(err) => return admin_error(err),
+    };
+    if let Some(response) = refuse_self(&admin, &id) {
+        return response;
+    }
+    let user = match find_user(&id).await {
+        Some(user) if user.archived_at.is_none() => user,
+        Some(_) => {
+            return status::Custom(
+                Status::Conflict,
+                serde_json::to_value(AdminUsersResponse::error(
+                    AuthenticationError::InvalidRequest,
+                    "User is already archived".to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+        None => return user_not_found(),
+    };
+
+    let delete_params = vec![("id", id.clone())];
+    if let Err(err) = delete_resource_where_fields!(User, delete_params).await {
+        println!("Error archiving user: {:?}", err);
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AdminUsersResponse::error(
+                UserError::UserDeletionFailed,
+                UserError::UserDeletionFailed.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+    if let Err(err) = revoke_user_sessions(&id, None).await {
+        println!("Error revoking sessions of archived user: {:?}", err);
+    }
+    if let Err(err) = revoke_user_api_keys(&id).await {
+        println!("Error revoking API keys of archived user: {:?}", err);
+    }
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::UserArchived)
+            .user(&id)
+            .actor(admin.user_id())
+            .target("user", &id)
+            .client(&client),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(AdminUsersResponse::success(
+            serde_json::to_value(user).unwrap(),
+            Some("User archived successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[post("/<id>/restore")]
+pub async fn restore_user(
+    admin: Result<AdminToken, AuthenticationError>,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let admin = match admin {
+        Ok(admin) => admin,
+        Err(err) => return admin_error(err),
+    };
+    match find_user(&id).await {
+        Some(user) if user.archived_at.is_some() => (),
+        Some(_) => {
+            return status::Custom(
+                Status::Conflict,
+                serde_json::to_value(AdminUsersResponse::error(
+                    AuthenticationError::InvalidRequest,
+                    "User is not archived".to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+        None => return user_not_found(),
+    }
+
+    let restore_params = vec![
+        ("archived_at", DatabaseValue::None),
+        ("purge_after", DatabaseValue::None),
+    ];
+    match update_resource!(User, id, restore_params).await {
+        Ok(user) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::UserRestored)
+                    .user(&id)
+                    .actor(admin.user_id())
+                    .target("user", &id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AdminUsersResponse::success(
+                    serde_json::to_value(user).unwrap(),
+                    Some("User restored successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error restoring user: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(AdminUsersResponse::error(
+                    UserError::UserUpdateFailed,
+                    UserError::UserUpdateFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+/// Signs a user out of every session.
+#[delete("/<id>/sessions")]
+pub async fn delete_user_sessions(
+    admin: Result<AdminToken, AuthenticationError>,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let admin = match admin {
+        Ok(admin) => admin,
+        Err(err) => return admin_error(err),
+    };
+    if find_user(&id).await.is_none() {
+        return user_not_found();
+    }
+
+    match revoke_user_sessions(&id, None).await {
+        Ok(revoked) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::UserSessionsRevoked)
+                    .user(&id)
+                    .actor(admin.user_id())
+                    .target("user", &id)
+                    .client(&client)
+                    .details(serde_json::json!({ "revoked": revoked })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AdminUsersResponse::success(
+                    serde_json::json!({ "revoked": revoked }),
+                    Some("Sessions revoked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AdminUsersResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
+
+/// Replaces a user's backup codes. The new codes are returned once, for the
+/// admin to hand over to the user.
+#[post("/<id>/backup-codes")]
+pub async fn reset_user_backup_codes(
+    admin: Result<AdminToken, AuthenticationError>,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let admin = match admin {
+        Ok(admin) => admin,
+        Err(err) => return admin_error(err),
+    };
+    if find_user(&id).await.is_none() {
+        return user_not_found();
+    }
+
+    match reset_backup_codes(&id).await {
+        Ok(codes) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::UserBackupCodesReset)
+                    .user(&id)
+                    .actor(admin.user_id())
+                    .target("user", &id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AdminUsersResponse::success(
+                    serde_json::to_value(codes).unwrap(),
+                    Some("Backup codes reset successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            let message = err.to_string();
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(AdminUsersResponse::error(err, message)).unwrap(),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminFlagRequest {
+    pub is_admin: bool,
+}
+
+/// Promotes a user to administrator or demotes them.
+#[put("/<id>/admin", data = "<admin_flag_request>")]
+pub async fn update_user_admin(
+    admin: Result<AdminToken, AuthenticationError>,
+    client: ClientInfo,
+    id: String,
+    admin_flag_request: Json<AdminFlagRequest>,
+) -> status::Custom<Value> {
+    let admin = match admin {
+        Ok(admin) => admin,
+        Err(err) => return admin_error(err),
+    };
+    if let Some(response) = refuse_self(&admin, &id) {
+        return response;
+    }
+    match find_user(&id).await {
+        Some(user) if user.archived_at.is_none() => (),
+        _ => return user_not_found(),
+    }
+
+    match set_admin(&id, admin_flag_request.is_admin).await {
+        Ok(user) => {
+            let action = if admin_flag_request.is_admin {
+                AuditAction::AdminGranted
+            } else {
+                AuditAction::AdminRevoked
+            };
+            record_audit_event(
+                AuditEntry::new(action)
+                    .user(&id)
+                    .actor(admin.user_id())
+                    .target("user", &id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AdminUsersResponse::success(
+                    serde_json::to_value(user).unwrap(),
+                    None,
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AdminUsersResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
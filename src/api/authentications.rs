@@ -1,27 +1,56 @@
+use crate::api::client::ClientInfo;
 use crate::api::token::{validate_token, RawToken};
 use crate::database::values::DatabaseValue;
 use crate::models::authentication::{Authentication, AuthenticationError};
-use crate::models::backup_code::{BackupCode, BackupCodeError};
+use crate::models::backup_code::BackupCodeError;
+use crate::models::password_reset::PasswordResetError;
+use crate::models::refresh_token::RefreshToken;
+use crate::models::team::{Team, TeamError};
+use crate::models::totp_secret::TotpSecret;
 use crate::models::user::{User, UserError};
-use crate::utils::backup_codes::generate_backup_codes;
-use crate::utils::passwords::hash_password;
+use crate::utils::account_deletion::{schedule_account_deletion, sole_admin_teams};
+use crate::utils::api_keys::revoke_user_api_keys;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::backup_codes::{generate_backup_codes, redeem_backup_code, store_backup_codes};
+use crate::utils::jwt::encode_access_token;
+use crate::utils::notifier::SharedNotifier;
+use crate::utils::organizations::join_default_organization;
+use crate::utils::password_resets::{consume_password_reset, request_password_reset};
+use crate::utils::passwords::{hash_password, verify_dummy_password, verify_password};
+use crate::utils::sessions::{
+    access_token_lifetime, capped_expiry, consume_refresh_token, generate_token,
+    issue_refresh_token, revoke_session, revoke_user_sessions, second_factor_lifetime,
+    session_max_lifetime,
+};
+use crate::utils::team_archival::archive_team;
+use crate::utils::teams::transfer_team_ownership;
+use crate::utils::throttle::{
+    check_throttle, clear_failures, login_keys, record_failure, ThrottleKey, Throttled,
+};
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use crate::utils::token_hash::hash_token;
+use crate::utils::totp::verify_second_factor;
 use crate::{
-    delete_resource_where_fields, find_one_archived_resource_where_fields,
+    find_all_unarchived_resources_where_fields, find_one_archived_resource_where_fields,
     find_one_resource_where_fields, find_one_unarchived_resource_where_fields, insert_resource,
     update_resource,
 };
-use rocket::http::Status;
+use rocket::http::{Header, Status};
 use rocket::response::status;
 use rocket::serde::json::Json;
 use rocket::serde::{Deserialize, Serialize};
+use rocket::State;
 use serde_json::Value;
-use uuid::Uuid;
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ResponseError {
     Authentication(AuthenticationError),
     User(UserError),
     BackupCode(BackupCodeError),
+    PasswordReset(PasswordResetError),
+    Team(TeamError),
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,6 +96,46 @@ impl From<BackupCodeError> for ResponseError {
     }
 }
 
+impl From<PasswordResetError> for ResponseError {
+    fn from(error: PasswordResetError) -> Self {
+        ResponseError::PasswordReset(error)
+    }
+}
+
+impl From<TeamError> for ResponseError {
+    fn from(error: TeamError) -> Self {
+        ResponseError::Team(error)
+    }
+}
+
+/// Response for endpoints that are rate limited, which need a `Retry-After`
+/// header alongside the usual JSON body when an attempt is throttled.
+#[derive(Responder)]
+pub enum ThrottledResponse {
+    Response(status::Custom<Value>),
+    #[response(status = 429)]
+    TooManyRequests(Value, Header<'static>),
+}
+
+impl From<status::Custom<Value>> for ThrottledResponse {
+    fn from(response: status::Custom<Value>) -> Self {
+        ThrottledResponse::Response(response)
+    }
+}
+
+impl From<Throttled> for ThrottledResponse {
+    fn from(throttled: Throttled) -> Self {
+        ThrottledResponse::TooManyRequests(
+            serde_json::to_value(AuthenticationResponse::error(
+                throttled.error.clone(),
+                throttled.error.to_string(),
+            ))
+            .unwrap(),
+            Header::new("Retry-After", throttled.retry_after.to_string()),
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthenticationRequest {
     pub username: String,
@@ -74,17 +143,33 @@ pub struct AuthenticationRequest {
 }
 
 #[post("/", data = "<authentication_request>")]
-pub async fn login(authentication_request: Json<AuthenticationRequest>) -> status::Custom<Value> {
-    let hashed_password = hash_password(&authentication_request.password);
+pub async fn login(
+    client: ClientInfo,
+    authentication_request: Json<AuthenticationRequest>,
+) -> ThrottledResponse {
+    let throttle_keys = login_keys(&authentication_request.username, &client);
+    if let Err(throttled) = check_throttle(&throttle_keys).await {
+        return throttled.into();
+    }
 
     let username = DatabaseValue::String(authentication_request.username.clone());
-    let password = DatabaseValue::String(hashed_password);
 
-    let login_params = vec![("username", &username), ("password_hash", &password)];
+    let login_params = [("username", &username)];
     let user = match find_one_unarchived_resource_where_fields!(User, login_params).await {
         Ok(user) => user,
         Err(err) => {
             println!("Error finding user: {:?}", err);
+            verify_dummy_password(&authentication_request.password).await;
+            record_failure(&throttle_keys).await;
+            record_audit_event(
+                AuditEntry::new(AuditAction::LoginFailed)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "username": authentication_request.username,
+                        "reason": "unknown_user",
+                    })),
+            )
+            .await;
             return status::Custom(
                 Status::NotFound,
                 serde_json::to_value(AuthenticationResponse::error(
@@ -92,75 +177,438 @@ pub async fn login(authentication_request: Json<AuthenticationRequest>) -> statu
                     UserError::UserNotFound.to_string(),
                 ))
                 .unwrap(),
-            );
+            )
+            .into();
         }
     };
 
+    let verification = verify_password(
+        &authentication_request.password,
+        &user.password_hash.clone().unwrap_or_default(),
+    )
+    .await;
+    if !verification.is_valid() {
+        println!("Invalid password for user: {:?}", user.id);
+        record_failure(&throttle_keys).await;
+        record_audit_event(
+            AuditEntry::new(AuditAction::LoginFailed)
+                .user(user.id.as_deref().unwrap_or_default())
+                .client(&client)
+                .details(serde_json::json!({ "reason": "invalid_password" })),
+        )
+        .await;
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(AuthenticationResponse::error(
+                UserError::UserNotFound,
+                UserError::UserNotFound.to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+
+    clear_failures(&throttle_keys[0]).await;
     let user_id = user.id.unwrap();
 
-    let auth_params = vec![("user_id", &user_id)];
-    match find_one_unarchived_resource_where_fields!(Authentication, auth_params).await {
+    if verification.needs_rehash() {
+        let rehashed_password = hash_password(&authentication_request.password).await;
+        let rehash_params = vec![("password_hash", DatabaseValue::String(rehashed_password))];
+        if let Err(err) = update_resource!(User, user_id, rehash_params).await {
+            println!("Error upgrading password hash: {:?}", err);
+        }
+    }
+
+    let totp_params = [("user_id", &user_id)];
+    let second_factor_pending =
+        match find_one_unarchived_resource_where_fields!(TotpSecret, totp_params).await {
+            Ok(totp_secret) => totp_secret.is_confirmed(),
+            Err(_) => false,
+        };
+
+    let response = match start_session(user_id.clone(), second_factor_pending, &client).await {
         Ok(authentication) => {
-            let id = DatabaseValue::String(authentication.id.clone());
-            match update_resource!(Authentication, id, vec![]).await {
-                Ok(_) => status::Custom(
-                    Status::Ok,
-                    serde_json::to_value(AuthenticationResponse::success(
-                        serde_json::to_value(authentication).unwrap(),
-                        None,
-                    ))
-                    .unwrap(),
-                ),
-                Err(err) => {
-                    println!("Error updating authentication: {:?}", err);
-                    return status::Custom(
-                        Status::InternalServerError,
-                        serde_json::to_value(AuthenticationResponse::error(
-                            AuthenticationError::SessionUpdateFailed,
-                            AuthenticationError::SessionUpdateFailed.to_string(),
-                        ))
-                        .unwrap(),
-                    );
-                }
-            }
+            record_audit_event(
+                AuditEntry::new(AuditAction::LoginSucceeded)
+                    .user(&user_id)
+                    .target("session", &authentication.authentication.id)
+                    .client(&client)
+                    .details(serde_json::json!({ "secondFactorPending": second_factor_pending })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AuthenticationResponse::success(
+                    serde_json::to_value(authentication).unwrap(),
+                    if second_factor_pending {
+                        Some(AuthenticationError::SecondFactorRequired.to_string())
+                    } else {
+                        None
+                    },
+                ))
+                .unwrap(),
+            )
         }
-        Err(_) => {
-            let token = Uuid::new_v4().to_string();
-            match insert_resource!(
-                Authentication,
-                vec![
-                    ("user_id", DatabaseValue::String(user_id)),
-                    ("token", DatabaseValue::String(token)),
-                ]
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        ),
+    };
+    response.into()
+}
+
+/// A session as handed to the client: the short-lived access token plus,
+/// once any second factor is done, the refresh token used to renew it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTokens {
+    #[serde(flatten)]
+    pub authentication: Authentication,
+    pub refresh_token: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub refresh_token_expires_at: Option<OffsetDateTime>,
+}
+
+impl SessionTokens {
+    /// Only a hash of `token` is stored on the session. Sessions still
+    /// waiting on a second factor get it back as an opaque token, which is
+    /// only accepted by the two-factor endpoint. Everything else gets a
+    /// signed access token carrying it as the `jti`.
+    async fn issue(
+        mut authentication: Authentication,
+        token: String,
+    ) -> Result<Self, AuthenticationError> {
+        authentication.token = token;
+        if authentication.second_factor_pending {
+            return Ok(Self {
+                authentication,
+                refresh_token: None,
+                refresh_token_expires_at: None,
+            });
+        }
+        authentication.token = encode_access_token(
+            &authentication.user_id,
+            &authentication.id,
+            &authentication.token,
+            authentication
+                .expires_at
+                .unwrap_or_else(|| OffsetDateTime::now_utc() + access_token_lifetime()),
+        )?;
+        let refresh_token = issue_refresh_token(
+            &authentication.id,
+            authentication
+                .created_at
+                .unwrap_or_else(OffsetDateTime::now_utc),
+        )
+        .await?;
+        Ok(Self {
+            authentication,
+            refresh_token: refresh_token.token,
+            refresh_token_expires_at: refresh_token.expires_at,
+        })
+    }
+}
+
+/// Creates a new session for every login so each device gets its own token
+/// and can be revoked on its own.
+pub async fn start_session(
+    user_id: String,
+    second_factor_pending: bool,
+    client: &ClientInfo,
+) -> Result<SessionTokens, AuthenticationError> {
+    let token = generate_token();
+    let mut session_params = vec![
+        ("user_id", DatabaseValue::String(user_id)),
+        ("token", DatabaseValue::String(hash_token(&token))),
+        (
+            "second_factor_pending",
+            DatabaseValue::Boolean(second_factor_pending.to_string()),
+        ),
+    ];
+    if second_factor_pending {
+        let expires_at = OffsetDateTime::now_utc() + second_factor_lifetime();
+        session_params.push((
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ));
+    }
+    if let Some(user_agent) = &client.user_agent {
+        session_params.push(("user_agent", DatabaseValue::String(user_agent.clone())));
+    }
+    if let Some(ip_address) = &client.ip_address {
+        session_params.push(("ip_address", DatabaseValue::String(ip_address.clone())));
+    }
+    match insert_resource!(Authentication, session_params).await {
+        Ok(authentication) => SessionTokens::issue(authentication, token).await,
+        Err(err) => {
+            println!("Error creating authentication: {:?}", err);
+            Err(AuthenticationError::SessionCreationFailed)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryRequest {
+    pub username: String,
+    pub backup_code: String,
+    pub new_password: String,
+}
+
+#[post("/recover", data = "<recovery_request>")]
+pub async fn recover(
+    client: ClientInfo,
+    recovery_request: Json<RecoveryRequest>,
+) -> ThrottledResponse {
+    if recovery_request.backup_code.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing backup code".to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+    if recovery_request.new_password.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing new password".to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+
+    let throttle_keys = login_keys(&recovery_request.username, &client);
+    if let Err(throttled) = check_throttle(&throttle_keys).await {
+        return throttled.into();
+    }
+
+    let username = DatabaseValue::String(recovery_request.username.clone());
+    let user_params = [("username", &username)];
+    let user = match find_one_unarchived_resource_where_fields!(User, user_params).await {
+        Ok(user) => user,
+        Err(err) => {
+            println!("Error finding user: {:?}", err);
+            verify_dummy_password(&recovery_request.new_password).await;
+            record_failure(&throttle_keys).await;
+            return status::Custom(
+                Status::NotFound,
+                serde_json::to_value(AuthenticationResponse::error(
+                    UserError::UserNotFound,
+                    UserError::UserNotFound.to_string(),
+                ))
+                .unwrap(),
             )
-            .await
-            {
-                Ok(authentication) => status::Custom(
-                    Status::Ok,
-                    serde_json::to_value(AuthenticationResponse::success(
-                        serde_json::to_value(authentication).unwrap(),
-                        None,
-                    ))
-                    .unwrap(),
-                ),
-                Err(err) => {
-                    println!("Error creating authentication: {:?}", err);
-                    return status::Custom(
-                        Status::InternalServerError,
-                        serde_json::to_value(AuthenticationResponse::error(
-                            AuthenticationError::SessionCreationFailed,
-                            AuthenticationError::SessionCreationFailed.to_string(),
-                        ))
-                        .unwrap(),
-                    );
-                }
+            .into();
+        }
+    };
+
+    let user_id = user.id.unwrap();
+    if let Err(err) = redeem_backup_code(&user_id, &recovery_request.backup_code).await {
+        println!("Error redeeming backup code: {:?}", err);
+        record_failure(&throttle_keys).await;
+        record_audit_event(
+            AuditEntry::new(AuditAction::RecoveryFailed)
+                .user(&user_id)
+                .client(&client),
+        )
+        .await;
+        let message = err.to_string();
+        return status::Custom(
+            Status::Unauthorized,
+            serde_json::to_value(AuthenticationResponse::error(err, message)).unwrap(),
+        )
+        .into();
+    }
+
+    clear_failures(&throttle_keys[0]).await;
+
+    let hashed_password = hash_password(&recovery_request.new_password).await;
+    let password_params = vec![("password_hash", DatabaseValue::String(hashed_password))];
+    if let Err(err) = update_resource!(User, user_id, password_params).await {
+        println!("Error updating password: {:?}", err);
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(
+                UserError::UserUpdateFailed,
+                UserError::UserUpdateFailed.to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::AccountRecovered)
+            .user(&user_id)
+            .client(&client),
+    )
+    .await;
+
+    if let Err(err) = revoke_user_sessions(&user_id, None).await {
+        println!("Error revoking sessions after recovery: {:?}", err);
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        )
+        .into();
+    }
+
+    let response = match start_session(user_id, false, &client).await {
+        Ok(authentication) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(AuthenticationResponse::success(
+                serde_json::to_value(authentication).unwrap(),
+                Some("Account recovered successfully".to_string()),
+            ))
+            .unwrap(),
+        ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        ),
+    };
+    response.into()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub username: String,
+}
+
+/// Starts a password reset. The response is the same whether or not the
+/// username exists, so this can't be used to discover accounts.
+#[post("/password-reset", data = "<password_reset_request>")]
+pub async fn forgot_password(
+    notifier: &State<SharedNotifier>,
+    client: ClientInfo,
+    password_reset_request: Json<PasswordResetRequest>,
+) -> status::Custom<Value> {
+    if password_reset_request.username.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing username".to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let username = DatabaseValue::String(password_reset_request.username.clone());
+    let user_params = [("username", &username)];
+    match find_one_unarchived_resource_where_fields!(User, user_params).await {
+        Ok(user) => {
+            if let Err(err) = request_password_reset(&user, notifier.inner().as_ref()).await {
+                println!("Error requesting password reset: {:?}", err);
             }
+            record_audit_event(
+                AuditEntry::new(AuditAction::PasswordResetRequested)
+                    .target("user", user.id.as_deref().unwrap_or_default())
+                    .client(&client),
+            )
+            .await;
+        }
+        Err(err) => println!("Error finding user: {:?}", err),
+    }
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(AuthenticationResponse::success(
+            serde_json::json!({}),
+            Some("If the account exists, a reset link has been sent".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Sets a new password with a reset token and signs the user out everywhere.
+#[post("/password-reset/confirm", data = "<password_reset_confirm_request>")]
+pub async fn reset_password(
+    client: ClientInfo,
+    password_reset_confirm_request: Json<PasswordResetConfirmRequest>,
+) -> status::Custom<Value> {
+    if password_reset_confirm_request.new_password.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing new password".to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let user_id = match consume_password_reset(&password_reset_confirm_request.token).await {
+        Ok(user_id) => user_id,
+        Err(err) => {
+            let message = err.to_string();
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(AuthenticationResponse::error(err, message)).unwrap(),
+            );
         }
+    };
+
+    let hashed_password = hash_password(&password_reset_confirm_request.new_password).await;
+    let password_params = vec![("password_hash", DatabaseValue::String(hashed_password))];
+    if let Err(err) = update_resource!(User, user_id, password_params).await {
+        println!("Error updating password: {:?}", err);
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(
+                UserError::UserUpdateFailed,
+                UserError::UserUpdateFailed.to_string(),
+            ))
+            .unwrap(),
+        );
     }
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::PasswordReset)
+            .user(&user_id)
+            .client(&client),
+    )
+    .await;
+
+    if let Err(err) = revoke_user_sessions(&user_id, None).await {
+        println!("Error revoking sessions after password reset: {:?}", err);
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        );
+    }
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(AuthenticationResponse::success(
+            serde_json::json!({}),
+            Some("Password reset successfully".to_string()),
+        ))
+        .unwrap(),
+    )
 }
 
 #[delete("/")]
-pub async fn logout(token: RawToken) -> status::Custom<Value> {
+pub async fn logout(token: RawToken, client: ClientInfo) -> status::Custom<Value> {
     let token_value = match validate_token(token).await {
         Ok(token) => token,
         Err(err) => {
@@ -173,36 +621,337 @@ pub async fn logout(token: RawToken) -> status::Custom<Value> {
         }
     };
 
-    let token_str = token_value.raw_token.unwrap().clone();
-    let logout_params = vec![("token", &token_str)];
-    match delete_resource_where_fields!(Authentication, logout_params).await {
-        Ok(_) => status::Custom(
+    match revoke_session(&token_value.authentication_id).await {
+        Ok(_) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::Logout)
+                    .user(&token_value.user_id)
+                    .target("session", &token_value.authentication_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(AuthenticationResponse::success(
+                    serde_json::json!(null),
+                    Some("Logged out successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error deleting authentication: {:?}", err);
+            return status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(AuthenticationResponse::error(
+                    AuthenticationError::SessionDeletionFailed,
+                    AuthenticationError::SessionDeletionFailed.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    }
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecondFactorRequest {
+    pub code: Option<String>,
+    pub backup_code: Option<String>,
+}
+
+#[post("/two-factor", data = "<second_factor_request>")]
+pub async fn verify_two_factor(
+    token: RawToken,
+    client: ClientInfo,
+    second_factor_request: Json<SecondFactorRequest>,
+) -> ThrottledResponse {
+    let token_hash = hash_token(&token.value);
+    let token_params = [("token", &token_hash)];
+    let authentication = match find_one_resource_where_fields!(Authentication, token_params).await {
+        Ok(authentication) if authentication.second_factor_pending => authentication,
+        Ok(_) => {
+            return status::Custom(
+                Status::BadRequest,
+                serde_json::to_value(AuthenticationResponse::error(
+                    AuthenticationError::InvalidRequest,
+                    AuthenticationError::InvalidRequest.to_string(),
+                ))
+                .unwrap(),
+            )
+            .into();
+        }
+        Err(err) => {
+            println!("Error finding authentication: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(AuthenticationResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            )
+            .into();
+        }
+    };
+
+    if authentication
+        .expires_at
+        .is_none_or(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    {
+        if let Err(err) = revoke_session(&authentication.id).await {
+            println!("Error revoking expired login: {:?}", err);
+        }
+        return status::Custom(
+            Status::Unauthorized,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::TokenExpired,
+                AuthenticationError::TokenExpired.to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+
+    // Counted per user rather than per login, so starting over with the
+    // password doesn't buy more guesses.
+    let throttle_keys = vec![ThrottleKey::second_factor(&authentication.user_id)];
+    if let Err(throttled) = check_throttle(&throttle_keys).await {
+        return throttled.into();
+    }
+
+    if let Err(err) = verify_second_factor(
+        &authentication.user_id,
+        second_factor_request.code.as_deref(),
+        second_factor_request.backup_code.as_deref(),
+    )
+    .await
+    {
+        println!("Error verifying second factor: {:?}", err);
+        let failures = record_failure(&throttle_keys).await;
+        if throttle_keys[0].is_locked_out(failures) {
+            if let Err(err) = revoke_session(&authentication.id).await {
+                println!("Error revoking login after failed second factor: {:?}", err);
+            }
+        }
+        record_audit_event(
+            AuditEntry::new(AuditAction::SecondFactorFailed)
+                .user(&authentication.user_id)
+                .target("session", &authentication.id)
+                .client(&client),
+        )
+        .await;
+        return status::Custom(
+            Status::Unauthorized,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        )
+        .into();
+    }
+
+    clear_failures(&throttle_keys[0]).await;
+    record_audit_event(
+        AuditEntry::new(AuditAction::SecondFactorSucceeded)
+            .user(&authentication.user_id)
+            .target("session", &authentication.id)
+            .client(&client),
+    )
+    .await;
+
+    let token = generate_token();
+    let expires_at = OffsetDateTime::now_utc() + access_token_lifetime();
+    let verified_params = vec![
+        ("token", DatabaseValue::String(hash_token(&token))),
+        (
+            "second_factor_pending",
+            DatabaseValue::Boolean(false.to_string()),
+        ),
+        (
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ),
+    ];
+    let authentication =
+        match update_resource!(Authentication, authentication.id, verified_params).await {
+            Ok(authentication) => authentication,
+            Err(err) => {
+                println!("Error updating authentication: {:?}", err);
+                return status::Custom(
+                    Status::InternalServerError,
+                    serde_json::to_value(AuthenticationResponse::error(
+                        AuthenticationError::SessionUpdateFailed,
+                        AuthenticationError::SessionUpdateFailed.to_string(),
+                    ))
+                    .unwrap(),
+                )
+                .into();
+            }
+        };
+
+    let response = match SessionTokens::issue(authentication, token).await {
+        Ok(session) => status::Custom(
             Status::Ok,
             serde_json::to_value(AuthenticationResponse::success(
-                serde_json::json!(null),
-                Some("Logged out successfully".to_string()),
+                serde_json::to_value(session).unwrap(),
+                None,
             ))
             .unwrap(),
         ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        ),
+    };
+    response.into()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Trades a refresh token for a new access token and a new refresh token.
+/// Each refresh token works once; presenting one that was already used means
+/// it has leaked, so the whole session is revoked.
+#[post("/refresh", data = "<refresh_request>")]
+pub async fn refresh(
+    client: ClientInfo,
+    refresh_request: Json<RefreshRequest>,
+) -> status::Custom<Value> {
+    let refresh_token_hash = hash_token(&refresh_request.refresh_token);
+    let refresh_params = [("token", &refresh_token_hash)];
+    let refresh_token = match find_one_resource_where_fields!(RefreshToken, refresh_params).await {
+        Ok(refresh_token) => refresh_token,
         Err(err) => {
-            println!("Error deleting authentication: {:?}", err);
+            println!("Error finding refresh token: {:?}", err);
             return status::Custom(
-                Status::InternalServerError,
+                Status::Unauthorized,
                 serde_json::to_value(AuthenticationResponse::error(
-                    AuthenticationError::SessionDeletionFailed,
-                    AuthenticationError::SessionDeletionFailed.to_string(),
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
                 ))
                 .unwrap(),
             );
         }
+    };
+    let authentication_id = refresh_token.authentication_id.clone().unwrap_or_default();
+
+    let consumed = match refresh_token.is_used() {
+        true => Err(AuthenticationError::RefreshTokenReused),
+        false => consume_refresh_token(&refresh_token.id.clone().unwrap_or_default()).await,
+    };
+    if let Err(err) = consumed {
+        if let AuthenticationError::RefreshTokenReused = err {
+            println!(
+                "Refresh token reused, revoking session: {:?}",
+                authentication_id
+            );
+            let session_params = [("id", &authentication_id)];
+            if let Ok(authentication) =
+                find_one_resource_where_fields!(Authentication, session_params).await
+            {
+                record_audit_event(
+                    AuditEntry::new(AuditAction::RefreshTokenReused)
+                        .user(&authentication.user_id)
+                        .target("session", &authentication_id)
+                        .client(&client),
+                )
+                .await;
+            }
+            let _ = revoke_session(&authentication_id).await;
+        }
+        return status::Custom(
+            Status::Unauthorized,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        );
+    }
+    if refresh_token.is_expired() {
+        return status::Custom(
+            Status::Unauthorized,
+            serde_json::to_value(AuthenticationResponse::error(
+                AuthenticationError::TokenExpired,
+                AuthenticationError::TokenExpired.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let session_params = [("id", &authentication_id)];
+    let authentication = match find_one_resource_where_fields!(Authentication, session_params).await
+    {
+        Ok(authentication) => authentication,
+        Err(err) => {
+            println!("Error finding authentication: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(AuthenticationResponse::error(
+                    AuthenticationError::SessionNotFound,
+                    AuthenticationError::SessionNotFound.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let expires_at = capped_expiry(
+        OffsetDateTime::now_utc() + access_token_lifetime(),
+        authentication
+            .created_at
+            .unwrap_or_else(OffsetDateTime::now_utc),
+        session_max_lifetime(),
+    );
+    let token = generate_token();
+    let rotate_params = vec![
+        ("token", DatabaseValue::String(hash_token(&token))),
+        (
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ),
+        (
+            "last_seen_at",
+            DatabaseValue::DateTime(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()),
+        ),
+    ];
+    let authentication =
+        match update_resource!(Authentication, authentication_id, rotate_params).await {
+            Ok(authentication) => authentication,
+            Err(err) => {
+                println!("Error rotating access token: {:?}", err);
+                return status::Custom(
+                    Status::InternalServerError,
+                    serde_json::to_value(AuthenticationResponse::error(
+                        AuthenticationError::SessionUpdateFailed,
+                        AuthenticationError::SessionUpdateFailed.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
+        };
+
+    match SessionTokens::issue(authentication, token).await {
+        Ok(session) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(AuthenticationResponse::success(
+                serde_json::to_value(session).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                .unwrap(),
+        ),
     }
 }
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RegisterRequest {
     pub first_name: String,
     pub last_name: String,
     pub username: String,
     pub password: String,
+    pub email: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -242,8 +991,25 @@ pub struct ResponseUser {
     pub backup_codes: Vec<String>,
 }
 
+/// What a registration that can't go through answers, whether the username
+/// is in use or a deleted account's password didn't match.
+fn registration_failed() -> ThrottledResponse {
+    status::Custom(
+        Status::InternalServerError,
+        serde_json::to_value(RegisterResponse::error(
+            UserError::UserCreationFailed,
+            UserError::UserCreationFailed.to_string(),
+        ))
+        .unwrap(),
+    )
+    .into()
+}
+
 #[post("/register", data = "<register_request>")]
-pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom<Value> {
+pub async fn register(
+    client: ClientInfo,
+    register_request: Json<RegisterRequest>,
+) -> ThrottledResponse {
     if register_request.first_name.is_empty() {
         return status::Custom(
             Status::BadRequest,
@@ -252,7 +1018,8 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
                 "Missing first name".to_string(),
             ))
             .unwrap(),
-        );
+        )
+        .into();
     }
     if register_request.last_name.is_empty() {
         return status::Custom(
@@ -262,7 +1029,8 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
                 "Missing last name".to_string(),
             ))
             .unwrap(),
-        );
+        )
+        .into();
     }
     if register_request.username.is_empty() {
         return status::Custom(
@@ -272,7 +1040,8 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
                 "Missing username".to_string(),
             ))
             .unwrap(),
-        );
+        )
+        .into();
     }
     if register_request.password.is_empty() {
         return status::Custom(
@@ -282,67 +1051,115 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
                 "Missing password".to_string(),
             ))
             .unwrap(),
-        );
+        )
+        .into();
     }
 
-    let hashed_password = hash_password(&register_request.password);
+    let hashed_password = hash_password(&register_request.password).await;
     let first_name = DatabaseValue::String(register_request.first_name.clone());
     let last_name = DatabaseValue::String(register_request.last_name.clone());
     let username = DatabaseValue::String(register_request.username.clone());
     let password = DatabaseValue::String(hashed_password);
 
-    let user_params = vec![("username", &username), ("password_hash", &password)];
-    let _ = match find_one_archived_resource_where_fields!(User, user_params).await {
-        Ok(user) => {
-            let id = user.id.clone().unwrap();
-            let id_value = DatabaseValue::String(id);
-            let archived_at = DatabaseValue::None;
+    let throttle_keys = login_keys(&register_request.username, &client);
+    if let Err(throttled) = check_throttle(&throttle_keys).await {
+        return throttled.into();
+    }
 
-            match update_resource!(User, id_value, vec![("archived_at", archived_at)]).await {
-                Ok(user) => {
-                    return status::Custom(
-                        Status::Ok,
-                        serde_json::to_value(AuthenticationResponse::success(
-                            serde_json::to_value(user).unwrap(),
-                            None,
-                        ))
-                        .unwrap(),
-                    )
-                }
-                Err(err) => {
-                    println!("Error updating user: {:?}", err);
-                    return status::Custom(
-                        Status::InternalServerError,
-                        serde_json::to_value(RegisterResponse::error(
-                            UserError::UserUpdateFailed,
-                            UserError::UserUpdateFailed.to_string(),
-                        ))
-                        .unwrap(),
-                    );
-                }
+    let user_params = [("username", &username)];
+    // Accounts deleted by their owner can be taken back during the grace
+    // period by registering again with the same credentials. Guesses count
+    // against the login throttle, and a wrong password fails the same way
+    // as any other taken username.
+    if let Ok(user) = find_one_archived_resource_where_fields!(User, user_params).await {
+        let restorable = user
+            .purge_after
+            .is_some_and(|purge_after| purge_after > OffsetDateTime::now_utc())
+            && verify_password(
+                &register_request.password,
+                &user.password_hash.clone().unwrap_or_default(),
+            )
+            .await
+            .is_valid();
+        if !restorable {
+            println!("Invalid restore attempt for user: {:?}", user.id);
+            record_failure(&throttle_keys).await;
+            return registration_failed();
+        }
+        clear_failures(&throttle_keys[0]).await;
+
+        let id = user.id.clone().unwrap();
+        let id_value = DatabaseValue::String(id.clone());
+        let archived_at = DatabaseValue::None;
+
+        let restore_params = vec![
+            ("archived_at", archived_at),
+            ("purge_after", DatabaseValue::None),
+            ("password_hash", password.clone()),
+        ];
+        let user = match update_resource!(User, id_value, restore_params).await {
+            Ok(user) => user,
+            Err(err) => {
+                println!("Error updating user: {:?}", err);
+                return status::Custom(
+                    Status::InternalServerError,
+                    serde_json::to_value(RegisterResponse::error(
+                        UserError::UserUpdateFailed,
+                        UserError::UserUpdateFailed.to_string(),
+                    ))
+                    .unwrap(),
+                )
+                .into();
             }
+        };
+
+        let backup_codes = generate_backup_codes().await;
+        if let Err(err) = store_backup_codes(&id, &backup_codes).await {
+            let message = err.to_string();
+            return status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(RegisterResponse::error(err, message)).unwrap(),
+            )
+            .into();
         }
-        Err(_) => (),
-    };
 
-    let register_params = vec![
+        record_audit_event(
+            AuditEntry::new(AuditAction::AccountRestored)
+                .user(&id)
+                .client(&client),
+        )
+        .await;
+
+        return status::Custom(
+            Status::Ok,
+            serde_json::to_value(RegisterResponse::success(
+                serde_json::to_value(ResponseUser { user, backup_codes }).unwrap(),
+                Some("Account restored successfully".to_string()),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+
+    let mut register_params = vec![
         ("first_name", first_name),
         ("last_name", last_name),
         ("username", username),
         ("password_hash", password),
     ];
+    if let Some(email) = register_request
+        .email
+        .clone()
+        .filter(|email| !email.is_empty())
+    {
+        register_params.push(("email", DatabaseValue::String(email)));
+    }
     let user = match insert_resource!(User, register_params).await {
         Ok(user) => user,
         Err(err) => {
             println!("Error registering user: {:?}", err);
-            return status::Custom(
-                Status::InternalServerError,
-                serde_json::to_value(RegisterResponse::error(
-                    UserError::UserCreationFailed,
-                    UserError::UserCreationFailed.to_string(),
-                ))
-                .unwrap(),
-            );
+            record_failure(&throttle_keys).await;
+            return registration_failed();
         }
     };
 
@@ -351,26 +1168,22 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
     let user_response = user.clone();
     let response_codes = backup_codes.clone();
 
-    for code in backup_codes {
-        let backup_code_params = vec![
-            ("code", DatabaseValue::String(code)),
-            ("user_id", DatabaseValue::String(user_id.clone())),
-        ];
-        let _ = match insert_resource!(BackupCode, backup_code_params).await {
-            Ok(_) => (),
-            Err(err) => {
-                println!("Error inserting backup code: {:?}", err);
-                return status::Custom(
-                    Status::InternalServerError,
-                    serde_json::to_value(RegisterResponse::error(
-                        BackupCodeError::CodeCreationFailed,
-                        BackupCodeError::CodeCreationFailed.to_string(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        };
+    if let Err(err) = store_backup_codes(&user_id, &backup_codes).await {
+        let message = err.to_string();
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(RegisterResponse::error(err, message)).unwrap(),
+        )
+        .into();
     }
+    join_default_organization(&user_id).await;
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::Registered)
+            .user(&user_id)
+            .client(&client),
+    )
+    .await;
 
     status::Custom(
         Status::Ok,
@@ -384,10 +1197,38 @@ pub async fn register(register_request: Json<RegisterRequest>) -> status::Custom
         ))
         .unwrap(),
     )
+    .into()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamTransfer {
+    pub team_id: String,
+    pub new_owner_id: String,
+}
+
+/// How to deal with the teams a user owns when deleting their account. Each
+/// owned team has to be either transferred to one of its members or, with
+/// `archiveOwnedTeams`, archived along with the account.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccountRequest {
+    #[serde(default)]
+    pub transfers: Vec<TeamTransfer>,
+    #[serde(default)]
+    pub archive_owned_teams: bool,
 }
 
-#[delete("/register")]
-pub async fn unregister(token: RawToken) -> status::Custom<Value> {
+/// Deletes the current user's account. It is archived and signed out
+/// everywhere straight away, can be restored by registering again with the
+/// same username and password during the grace period, and is purged for
+/// good afterwards.
+#[delete("/register", data = "<delete_account_request>")]
+pub async fn unregister(
+    token: RawToken,
+    client: ClientInfo,
+    delete_account_request: Option<Json<DeleteAccountRequest>>,
+) -> status::Custom<Value> {
     let token_value = match validate_token(token).await {
         Ok(token) => token,
         Err(_) => {
@@ -401,11 +1242,14 @@ pub async fn unregister(token: RawToken) -> status::Custom<Value> {
             );
         }
     };
+    let delete_account_request = delete_account_request
+        .map(|request| request.into_inner())
+        .unwrap_or_default();
 
     let user_id = token_value.user_id.clone();
 
     let user_params = vec![("id", &user_id)];
-    let _ = match find_one_resource_where_fields!(User, user_params).await {
+    let _ = match find_one_unarchived_resource_where_fields!(User, user_params).await {
         Ok(user) => user,
         Err(err) => {
             println!("Error finding user: {:?}", err);
@@ -420,58 +1264,164 @@ pub async fn unregister(token: RawToken) -> status::Custom<Value> {
         }
     };
 
-    let codes_params = vec![("user_id", &user_id)];
-    let _ = match delete_resource_where_fields!(BackupCode, codes_params).await {
-        Ok(_) => (),
+    let owned_teams_params = [("owner_id", &user_id)];
+    let owned_teams =
+        match find_all_unarchived_resources_where_fields!(Team, owned_teams_params).await {
+            Ok(teams) => teams,
+            Err(err) => {
+                println!("Error finding owned teams: {:?}", err);
+                return status::Custom(
+                    Status::InternalServerError,
+                    serde_json::to_value(AuthenticationResponse::error(
+                        TeamError::TeamNotFound,
+                        TeamError::TeamNotFound.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
+        };
+    let unhandled_teams = owned_teams
+        .iter()
+        .filter(|team| {
+            !delete_account_request.archive_owned_teams
+                && !delete_account_request
+                    .transfers
+                    .iter()
+                    .any(|transfer| Some(&transfer.team_id) == team.id.as_ref())
+        })
+        .filter_map(|team| team.team_name.clone())
+        .collect::<Vec<String>>();
+    if !unhandled_teams.is_empty() {
+        return status::Custom(
+            Status::Conflict,
+            serde_json::to_value(AuthenticationResponse::error(
+                UserError::OwnsTeams,
+                format!("{}: {}", UserError::OwnsTeams, unhandled_teams.join(", ")),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let sole_admin_teams = match sole_admin_teams(&user_id).await {
+        Ok(teams) => teams,
         Err(err) => {
-            println!("Error deleting backup codes: {:?}", err);
+            println!("Error finding teams the user is last admin of: {:?}", err);
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(AuthenticationResponse::error(
-                    BackupCodeError::CodeDeletionFailed,
-                    BackupCodeError::CodeDeletionFailed.to_string(),
+                    UserError::UserDeletionFailed,
+                    UserError::UserDeletionFailed.to_string(),
                 ))
                 .unwrap(),
             );
         }
     };
+    if !sole_admin_teams.is_empty() {
+        return status::Custom(
+            Status::Conflict,
+            serde_json::to_value(AuthenticationResponse::error(
+                UserError::LastTeamAdmin,
+                format!(
+                    "{}: {}",
+                    UserError::LastTeamAdmin,
+                    sole_admin_teams.join(", ")
+                ),
+            ))
+            .unwrap(),
+        );
+    }
 
-    let auth_params = vec![("user_id", &user_id)];
-    let _ = match delete_resource_where_fields!(Authentication, auth_params).await {
-        Ok(_) => (),
+    for team in &owned_teams {
+        let team_id = team.id.clone().unwrap_or_default();
+        match delete_account_request
+            .transfers
+            .iter()
+            .find(|transfer| transfer.team_id == team_id)
+        {
+            Some(transfer) => {
+                if let Err(err) =
+                    transfer_team_ownership(&team_id, &user_id, &transfer.new_owner_id).await
+                {
+                    let message =
+                        format!("{}: {}", err, team.team_name.clone().unwrap_or_default());
+                    return status::Custom(
+                        Status::BadRequest,
+                        serde_json::to_value(AuthenticationResponse::error(err, message)).unwrap(),
+                    );
+                }
+                record_audit_event(
+                    AuditEntry::new(AuditAction::TeamOwnershipTransferred)
+                        .actor(&user_id)
+                        .team(&team_id)
+                        .target("team", &team_id)
+                        .client(&client)
+                        .details(serde_json::json!({ "newOwnerId": transfer.new_owner_id })),
+                )
+                .await;
+            }
+            None => {
+                if let Err(err) = archive_team(&team_id).await {
+                    println!("Error archiving team: {:?}", err);
+                    return status::Custom(
+                        Status::InternalServerError,
+                        serde_json::to_value(AuthenticationResponse::error(
+                            TeamError::TeamDeletionFailed,
+                            TeamError::TeamDeletionFailed.to_string(),
+                        ))
+                        .unwrap(),
+                    );
+                }
+                record_audit_event(
+                    AuditEntry::new(AuditAction::TeamArchived)
+                        .actor(&user_id)
+                        .team(&team_id)
+                        .target("team", &team_id)
+                        .client(&client)
+                        .details(serde_json::json!({ "reason": "account_deleted" })),
+                )
+                .await;
+            }
+        }
+    }
+
+    let purge_after = match schedule_account_deletion(&user_id).await {
+        Ok(purge_after) => purge_after,
         Err(err) => {
-            println!("Error deleting authentication: {:?}", err);
+            let status = match err {
+                UserError::LastTeamAdmin => Status::Conflict,
+                _ => Status::InternalServerError,
+            };
             return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(AuthenticationResponse::error(
-                    AuthenticationError::SessionNotFound,
-                    AuthenticationError::SessionNotFound.to_string(),
-                ))
-                .unwrap(),
+                status,
+                serde_json::to_value(AuthenticationResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
 
-    let delete_params = vec![("id", &user_id)];
-    match delete_resource_where_fields!(User, delete_params).await {
-        Ok(_) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(AuthenticationResponse::success(
-                serde_json::json!(null),
-                Some("User deleted successfully".to_string()),
-            ))
-            .unwrap(),
-        ),
-        Err(err) => {
-            println!("Error deleting user: {:?}", err);
-            status::Custom(
-                Status::InternalServerError,
-                serde_json::to_value(AuthenticationResponse::error(
-                    UserError::UserDeletionFailed,
-                    UserError::UserDeletionFailed.to_string(),
-                ))
-                .unwrap(),
-            )
-        }
+    if let Err(err) = revoke_user_api_keys(&user_id).await {
+        println!("Error revoking API keys: {:?}", err);
     }
+    if let Err(err) = revoke_user_sessions(&user_id, None).await {
+        println!("Error revoking sessions: {:?}", err);
+    }
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::Unregistered)
+            .user(&user_id)
+            .client(&client)
+            .details(serde_json::json!({
+                "purgeAfter": purge_after.format(&Iso8601::DEFAULT).unwrap(),
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(AuthenticationResponse::success(
+            serde_json::json!({ "purgeAfter": purge_after.format(&Iso8601::DEFAULT).unwrap() }),
+            Some("Account scheduled for deletion".to_string()),
+        ))
+        .unwrap(),
+    )
 }
@@ -0,0 +1,34 @@
+use rocket::{
+    request::{FromRequest, Outcome},
+    Request,
+};
+use serde::{Deserialize, Serialize};
+
+const MAX_USER_AGENT_LENGTH: usize = 255;
+
+/// Device details recorded against a session. The IP address honours
+/// Rocket's `ip_header` setting (`X-Real-IP` by default) when running behind
+/// a proxy.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user_agent = request
+            .headers()
+            .get_one("User-Agent")
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect());
+        let ip_address = request.client_ip().map(|ip| ip.to_string());
+        Outcome::Success(ClientInfo {
+            user_agent,
+            ip_address,
+        })
+    }
+}
@@ -1,5 +1,6 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::invitation::{Invitation, InvitationError};
 use crate::models::team::TeamError;
@@ -79,17 +80,14 @@ pub struct InvitationResponse {
 
 #[get("/")]
 pub async fn get_invitations(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -151,17 +149,14 @@ pub async fn get_invitations(token: RawToken) -> status::Custom<Value> {
 
 #[get("/<invitation_id>")]
 pub async fn get_invitation(token: RawToken, invitation_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -0,0 +1,217 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{token_error_status, validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::find_one_unarchived_resource_where_fields;
+use crate::models::authentication::AuthenticationError;
+use crate::models::join_link::JoinLinkError;
+use crate::models::team::{Team, TeamError};
+use crate::models::team_role::TeamRole;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::join_links::{find_join_link, usable, use_join_link, JoinOutcome};
+use crate::utils::organizations::is_in_team_organization;
+use rocket::http::Status;
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Team(TeamError),
+    JoinLink(JoinLinkError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<TeamError> for ResponseError {
+    fn from(error: TeamError) -> Self {
+        ResponseError::Team(error)
+    }
+}
+
+impl From<JoinLinkError> for ResponseError {
+    fn from(error: JoinLinkError) -> Self {
+        ResponseError::JoinLink(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinLinksResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl JoinLinksResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+fn join_link_error(err: JoinLinkError) -> status::Custom<Value> {
+    let status = match err {
+        JoinLinkError::JoinLinkNotFound => Status::NotFound,
+        JoinLinkError::JoinLinkExpired
+        | JoinLinkError::JoinLinkRevoked
+        | JoinLinkError::JoinLinkExhausted => Status::Gone,
+        JoinLinkError::AlreadyTeamMember | JoinLinkError::JoinRequestAlreadyPending => {
+            Status::Conflict
+        }
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(JoinLinksResponse::error(err, message)).unwrap(),
+    )
+}
+
+/// What someone opening a join link gets to see before using it.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinLinkPreview {
+    pub team_id: Option<String>,
+    pub team_name: Option<String>,
+    pub team_description: Option<String>,
+    pub team_role: Option<TeamRole>,
+    pub requires_approval: bool,
+}
+
+#[get("/<code>")]
+pub async fn get_join_link(token: RawToken, code: String) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(JoinLinksResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    let join_link = match find_join_link(&code).await {
+        Ok(join_link) => join_link,
+        Err(err) => return join_link_error(err),
+    };
+    if let Err(err) = usable(&join_link) {
+        return join_link_error(err);
+    }
+    let team_id = join_link.team_id.clone().unwrap_or_default();
+    if !is_in_team_organization(&team_id, &token_value.user_id).await {
+        return join_link_error(JoinLinkError::JoinLinkNotFound);
+    }
+
+    let team_id = DatabaseValue::String(team_id);
+    let team_params = [("id", &team_id)];
+    let team = match find_one_unarchived_resource_where_fields!(Team, team_params).await {
+        Ok(team) => team,
+        Err(err) => {
+            println!("Error finding team: {:?}", err);
+            return join_link_error(JoinLinkError::JoinLinkNotFound);
+        }
+    };
+
+    let preview = JoinLinkPreview {
+        team_id: team.id,
+        team_name: team.team_name,
+        team_description: team.team_description,
+        team_role: join_link.team_role,
+        requires_approval: join_link.requires_approval,
+    };
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(JoinLinksResponse::success(
+            serde_json::to_value(preview).unwrap(),
+            None,
+        ))
+        .unwrap(),
+    )
+}
+
+/// Joins the team behind the link, or files a join request for the team's
+/// admins and managers when the link requires approval.
+#[post("/<code>")]
+pub async fn join_team(token: RawToken, client: ClientInfo, code: String) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(JoinLinksResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    match use_join_link(&code, &token_value.user_id).await {
+        Ok(JoinOutcome::Joined(team_user)) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinLinkUsed)
+                    .user(&token_value.user_id)
+                    .team(team_user.team_id.as_deref().unwrap_or_default())
+                    .target(
+                        "join_link",
+                        team_user.join_link_id.as_deref().unwrap_or_default(),
+                    )
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "teamRole": team_user.team_role.as_ref().map(|role| role.to_string()),
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Created,
+                serde_json::to_value(JoinLinksResponse::success(
+                    serde_json::to_value(team_user).unwrap(),
+                    Some("Joined team successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Ok(JoinOutcome::Requested(join_request)) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinRequestCreated)
+                    .user(&token_value.user_id)
+                    .team(join_request.team_id.as_deref().unwrap_or_default())
+                    .target(
+                        "join_request",
+                        join_request.id.as_deref().unwrap_or_default(),
+                    )
+                    .client(&client)
+                    .details(serde_json::json!({ "joinLinkId": join_request.join_link_id })),
+            )
+            .await;
+            status::Custom(
+                Status::Accepted,
+                serde_json::to_value(JoinLinksResponse::success(
+                    serde_json::to_value(join_request).unwrap(),
+                    Some("Join request sent to the team for approval".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error using join link: {:?}", err);
+            join_link_error(err)
+        }
+    }
+}
@@ -1,7 +1,14 @@
+pub mod admin;
 pub mod authentications;
+pub mod client;
 pub mod home;
 pub mod invitations;
+pub mod join_links;
 pub mod my;
+pub mod oidc;
+pub mod organization_access;
+pub mod organizations;
+pub mod team_access;
 pub mod teams;
 pub mod token;
 pub mod users;
@@ -1,11 +1,13 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
 use crate::find_all_unarchived_resources_where_fields;
 use crate::find_one_unarchived_resource_where_fields;
 use crate::models::activity::{Activity, ActivityError};
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::team::TeamError;
 use crate::update_resource;
+use crate::utils::team_archival::is_team_archived;
 use rocket::http::Status;
 use rocket::response::status;
 use serde::{Deserialize, Serialize};
@@ -65,17 +67,14 @@ impl ActivitiesResponse {
 
 #[get("/")]
 pub async fn get_activities(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -130,17 +129,14 @@ pub async fn get_activities(token: RawToken) -> status::Custom<Value> {
 
 #[get("/<activity_id>")]
 pub async fn get_activity(token: RawToken, activity_id: &str) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -174,17 +170,14 @@ pub async fn get_activity(token: RawToken, activity_id: &str) -> status::Custom<
 
 #[post("/<activity_id>/complete")]
 pub async fn complete_activity(token: RawToken, activity_id: &str) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -193,7 +186,7 @@ pub async fn complete_activity(token: RawToken, activity_id: &str) -> status::Cu
     let find_params = vec![("id", &activity_id_value)];
     let _ = match find_one_unarchived_resource_where_fields!(Activity, find_params).await {
         Ok(activity) => {
-            if activity.assigned_to.clone().unwrap() != token_value.user_id {
+            if activity.assigned_to.as_deref() != Some(token_value.user_id.as_str()) {
                 return status::Custom(
                     Status::Forbidden,
                     serde_json::to_value(ActivitiesResponse::error(
@@ -203,6 +196,17 @@ pub async fn complete_activity(token: RawToken, activity_id: &str) -> status::Cu
                     .unwrap(),
                 );
             }
+            // Activities of an archived team are frozen along with it.
+            if is_team_archived(activity.team_id.as_deref().unwrap_or_default()).await {
+                return status::Custom(
+                    Status::Conflict,
+                    serde_json::to_value(ActivitiesResponse::error(
+                        TeamError::TeamArchived,
+                        TeamError::TeamArchived.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
             activity
         }
         Err(err) => {
@@ -248,17 +252,14 @@ pub async fn complete_activity(token: RawToken, activity_id: &str) -> status::Cu
 
 #[post("/<activity_id>/reopen")]
 pub async fn reopen_activity(token: RawToken, activity_id: &str) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -267,7 +268,7 @@ pub async fn reopen_activity(token: RawToken, activity_id: &str) -> status::Cust
     let find_params = vec![("id", &activity_id_value)];
     let _ = match find_one_unarchived_resource_where_fields!(Activity, find_params).await {
         Ok(activity) => {
-            if activity.assigned_to.clone().unwrap() != token_value.user_id {
+            if activity.assigned_to.as_deref() != Some(token_value.user_id.as_str()) {
                 return status::Custom(
                     Status::Forbidden,
                     serde_json::to_value(ActivitiesResponse::error(
@@ -277,6 +278,17 @@ pub async fn reopen_activity(token: RawToken, activity_id: &str) -> status::Cust
                     .unwrap(),
                 );
             }
+            // Activities of an archived team are frozen along with it.
+            if is_team_archived(activity.team_id.as_deref().unwrap_or_default()).await {
+                return status::Custom(
+                    Status::Conflict,
+                    serde_json::to_value(ActivitiesResponse::error(
+                        TeamError::TeamArchived,
+                        TeamError::TeamArchived.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
             activity
         }
         Err(err) => {
@@ -319,17 +331,14 @@ pub async fn reopen_activity(token: RawToken, activity_id: &str) -> status::Cust
 
 #[post("/<activity_id>/pause")]
 pub async fn pause_activity(token: RawToken, activity_id: &str) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -338,7 +347,7 @@ pub async fn pause_activity(token: RawToken, activity_id: &str) -> status::Custo
     let find_params = vec![("id", &activity_id_value)];
     let _ = match find_one_unarchived_resource_where_fields!(Activity, find_params).await {
         Ok(activity) => {
-            if activity.assigned_to.clone().unwrap() != token_value.user_id {
+            if activity.assigned_to.as_deref() != Some(token_value.user_id.as_str()) {
                 return status::Custom(
                     Status::Forbidden,
                     serde_json::to_value(ActivitiesResponse::error(
@@ -348,6 +357,17 @@ pub async fn pause_activity(token: RawToken, activity_id: &str) -> status::Custo
                     .unwrap(),
                 );
             }
+            // Activities of an archived team are frozen along with it.
+            if is_team_archived(activity.team_id.as_deref().unwrap_or_default()).await {
+                return status::Custom(
+                    Status::Conflict,
+                    serde_json::to_value(ActivitiesResponse::error(
+                        TeamError::TeamArchived,
+                        TeamError::TeamArchived.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
             activity
         }
         Err(err) => {
@@ -393,17 +413,14 @@ pub async fn pause_activity(token: RawToken, activity_id: &str) -> status::Custo
 
 #[post("/<activity_id>/resume")]
 pub async fn resume_activity(token: RawToken, activity_id: &str) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ActivitiesWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(ActivitiesResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(ActivitiesResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -412,7 +429,7 @@ pub async fn resume_activity(token: RawToken, activity_id: &str) -> status::Cust
     let find_params = vec![("id", &activity_id_value)];
     let _ = match find_one_unarchived_resource_where_fields!(Activity, find_params).await {
         Ok(activity) => {
-            if activity.assigned_to.clone().unwrap() != token_value.user_id {
+            if activity.assigned_to.as_deref() != Some(token_value.user_id.as_str()) {
                 return status::Custom(
                     Status::Forbidden,
                     serde_json::to_value(ActivitiesResponse::error(
@@ -422,6 +439,17 @@ pub async fn resume_activity(token: RawToken, activity_id: &str) -> status::Cust
                     .unwrap(),
                 );
             }
+            // Activities of an archived team are frozen along with it.
+            if is_team_archived(activity.team_id.as_deref().unwrap_or_default()).await {
+                return status::Custom(
+                    Status::Conflict,
+                    serde_json::to_value(ActivitiesResponse::error(
+                        TeamError::TeamArchived,
+                        TeamError::TeamArchived.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
             activity
         }
         Err(err) => {
@@ -0,0 +1,320 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::api_key::{ApiKey, ApiKeyError, ApiKeyScope};
+use crate::models::authentication::AuthenticationError;
+use crate::utils::api_keys::{generate_api_key, join_scopes};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::time::deserialize_offset_date_time;
+use crate::utils::token_hash::hash_token;
+use crate::{
+    delete_resource_where_fields, find_all_unarchived_resources_where_fields,
+    find_one_unarchived_resource_where_fields, insert_resource,
+};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    ApiKey(ApiKeyError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<ApiKeyError> for ResponseError {
+    fn from(error: ApiKeyError) -> Self {
+        ResponseError::ApiKey(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiKeysResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl ApiKeysResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[get("/")]
+pub async fn get_api_keys(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(ApiKeysResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let key_params = [("user_id", &token_value.user_id)];
+    match find_all_unarchived_resources_where_fields!(ApiKey, key_params).await {
+        Ok(api_keys) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(ApiKeysResponse::success(
+                serde_json::to_value(api_keys).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding API keys: {:?}", err);
+            status::Custom(
+                Status::NotFound,
+                serde_json::to_value(ApiKeysResponse::error(
+                    ApiKeyError::ApiKeyNotFound,
+                    ApiKeyError::ApiKeyNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+
+    #[serde(default, deserialize_with = "deserialize_offset_date_time")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// A newly created key. `secret` is only ever returned here.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    pub api_key: ApiKey,
+    pub secret: String,
+}
+
+#[post("/", data = "<api_key_request>")]
+pub async fn create_api_key(
+    token: RawToken,
+    client: ClientInfo,
+    api_key_request: Json<CreateApiKeyRequest>,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(ApiKeysResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    if api_key_request.name.trim().is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(ApiKeysResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing name".to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+    let mut scopes = Vec::new();
+    for name in &api_key_request.scopes {
+        match ApiKeyScope::parse(name.trim()) {
+            Some(scope) if !scopes.contains(&scope) => scopes.push(scope),
+            Some(_) => (),
+            None => {
+                return status::Custom(
+                    Status::BadRequest,
+                    serde_json::to_value(ApiKeysResponse::error(
+                        ApiKeyError::InvalidScope,
+                        format!("{}: {}", ApiKeyError::InvalidScope, name),
+                    ))
+                    .unwrap(),
+                );
+            }
+        }
+    }
+    if scopes.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(ApiKeysResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing scopes".to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+    if let Some(expires_at) = api_key_request.expires_at {
+        if expires_at <= OffsetDateTime::now_utc() {
+            return status::Custom(
+                Status::BadRequest,
+                serde_json::to_value(ApiKeysResponse::error(
+                    AuthenticationError::InvalidRequest,
+                    "Expiry must be in the future".to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    }
+
+    let (secret, prefix) = generate_api_key();
+    let mut key_params = vec![
+        (
+            "user_id",
+            DatabaseValue::String(token_value.user_id.clone()),
+        ),
+        (
+            "name",
+            DatabaseValue::String(api_key_request.name.trim().to_string()),
+        ),
+        ("prefix", DatabaseValue::String(prefix)),
+        ("token", DatabaseValue::String(hash_token(&secret))),
+        ("scopes", DatabaseValue::String(join_scopes(&scopes))),
+    ];
+    if let Some(expires_at) = api_key_request.expires_at {
+        key_params.push((
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ));
+    }
+
+    match insert_resource!(ApiKey, key_params).await {
+        Ok(api_key) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::ApiKeyCreated)
+                    .user(&token_value.user_id)
+                    .target("api_key", api_key.id.as_deref().unwrap_or_default())
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "name": api_key.name,
+                        "scopes": api_key.scopes,
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(ApiKeysResponse::success(
+                    serde_json::to_value(CreatedApiKey { api_key, secret }).unwrap(),
+                    Some(
+                        "API key created, copy the secret now as it won't be shown again"
+                            .to_string(),
+                    ),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error creating API key: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(ApiKeysResponse::error(
+                    ApiKeyError::ApiKeyCreationFailed,
+                    ApiKeyError::ApiKeyCreationFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+#[delete("/<id>")]
+pub async fn delete_api_key(
+    token: RawToken,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(ApiKeysResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let key_params = [("id", &id), ("user_id", &token_value.user_id)];
+    if let Err(err) = find_one_unarchived_resource_where_fields!(ApiKey, key_params).await {
+        println!("Error finding API key: {:?}", err);
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(ApiKeysResponse::error(
+                ApiKeyError::ApiKeyNotFound,
+                ApiKeyError::ApiKeyNotFound.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let delete_params = vec![("id", id.clone())];
+    match delete_resource_where_fields!(ApiKey, delete_params).await {
+        Ok(_) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::ApiKeyRevoked)
+                    .user(&token_value.user_id)
+                    .target("api_key", &id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(ApiKeysResponse::success(
+                    serde_json::json!(null),
+                    Some("API key revoked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error revoking API key: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(ApiKeysResponse::error(
+                    ApiKeyError::ApiKeyDeletionFailed,
+                    ApiKeyError::ApiKeyDeletionFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::api::token::{validate_token, RawToken};
+use crate::models::audit_event::AuditError;
+use crate::models::authentication::AuthenticationError;
+use crate::utils::audit::user_audit_events;
+use rocket::http::Status;
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Audit(AuditError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<AuditError> for ResponseError {
+    fn from(error: AuditError) -> Self {
+        ResponseError::Audit(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl AuditResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Security events on the current user's account, newest first. Pass the id
+/// of the last event seen as `before` to get the next page.
+#[get("/?<limit>&<before>")]
+pub async fn get_audit_events(
+    token: RawToken,
+    limit: Option<i64>,
+    before: Option<String>,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(AuditResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    match user_audit_events(&token_value.user_id, limit, before.as_deref()).await {
+        Ok(events) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(AuditResponse::success(
+                serde_json::to_value(events).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(AuditResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
@@ -1,12 +1,14 @@
-use crate::api::token::{RawToken, VerifiedToken};
+use crate::api::client::ClientInfo;
+use crate::api::token::{validate_token, RawToken};
 use crate::database::values::DatabaseValue;
 use crate::models::authentication::AuthenticationError;
 use crate::models::backup_code::{BackupCode, BackupCodeError};
 use crate::models::user::{User, UserError};
-use crate::utils::backup_codes::generate_backup_codes;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::backup_codes::{generate_backup_codes, store_backup_codes};
 use crate::{
     delete_resource_where_fields, find_all_unarchived_resources_where_fields,
-    find_one_unarchived_resource_where_fields, insert_resource,
+    find_one_unarchived_resource_where_fields,
 };
 use rocket::http::Status;
 use rocket::response::status;
@@ -76,7 +78,7 @@ pub async fn get_backup_codes(token: RawToken) -> status::Custom<Value> {
         );
     }
 
-    let token_value = match VerifiedToken::from_raw(token).await {
+    let token_value = match validate_token(token).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error verifying token: {:?}", err);
@@ -115,13 +117,7 @@ pub async fn get_backup_codes(token: RawToken) -> status::Custom<Value> {
         Ok(codes) => status::Custom(
             Status::Ok,
             serde_json::to_value(BackupCodesResponse::success(
-                serde_json::to_value(
-                    codes
-                        .into_iter()
-                        .map(|code| code.code.unwrap())
-                        .collect::<Vec<String>>(),
-                )
-                .unwrap(),
+                serde_json::to_value(codes).unwrap(),
                 Some("Backup codes fetched successfully".to_string()),
             ))
             .unwrap(),
@@ -141,7 +137,7 @@ pub async fn get_backup_codes(token: RawToken) -> status::Custom<Value> {
 }
 
 #[post("/generate")]
-pub async fn regenerate_backup_codes(token: RawToken) -> status::Custom<Value> {
+pub async fn regenerate_backup_codes(token: RawToken, client: ClientInfo) -> status::Custom<Value> {
     if token.value.is_empty() {
         return status::Custom(
             Status::BadRequest,
@@ -153,7 +149,7 @@ pub async fn regenerate_backup_codes(token: RawToken) -> status::Custom<Value> {
         );
     }
 
-    let token_value = match VerifiedToken::from_raw(token).await {
+    let token_value = match validate_token(token).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error verifying token: {:?}", err);
@@ -226,55 +222,27 @@ pub async fn regenerate_backup_codes(token: RawToken) -> status::Custom<Value> {
     }
 
     let codes = generate_backup_codes().await;
-    let user_id_value = user_id.clone();
-    for code in codes {
-        let backup_code_params = vec![
-            ("code", DatabaseValue::String(code.clone())),
-            ("user_id", user_id_value.clone()),
-        ];
-        let _ = match insert_resource!(BackupCode, backup_code_params).await {
-            Ok(_) => (),
-            Err(err) => {
-                println!("Error inserting backup code: {:?}", err);
-                return status::Custom(
-                    Status::InternalServerError,
-                    serde_json::to_value(BackupCodesResponse::error(
-                        BackupCodeError::CodeCreationFailed,
-                        BackupCodeError::CodeCreationFailed.to_string(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        };
+    if let Err(err) = store_backup_codes(&token_value.user_id, &codes).await {
+        let message = err.to_string();
+        return status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(BackupCodesResponse::error(err, message)).unwrap(),
+        );
     }
 
-    let codes_params = vec![("user_id", &user_id)];
-    let codes_result = find_all_unarchived_resources_where_fields!(BackupCode, codes_params).await;
-    match codes_result {
-        Ok(codes) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(BackupCodesResponse::success(
-                serde_json::to_value(
-                    codes
-                        .into_iter()
-                        .map(|code| code.code.unwrap())
-                        .collect::<Vec<String>>(),
-                )
-                .unwrap(),
-                Some("Backup codes generated successfully".to_string()),
-            ))
-            .unwrap(),
-        ),
-        Err(err) => {
-            println!("Error fetching backup codes: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(BackupCodesResponse::error(
-                    BackupCodeError::CodeNotFound,
-                    BackupCodeError::CodeNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    }
+    record_audit_event(
+        AuditEntry::new(AuditAction::BackupCodesRegenerated)
+            .user(&token_value.user_id)
+            .client(&client),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(BackupCodesResponse::success(
+            serde_json::to_value(codes).unwrap(),
+            Some("Backup codes generated successfully".to_string()),
+        ))
+        .unwrap(),
+    )
 }
@@ -0,0 +1,320 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::AuthenticationError;
+use crate::models::data_export::{DataExport, DataExportError, DataExportStatus};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::data_exports::{download_url, export_path, spawn_export};
+use crate::{find_all_resources_where_fields, find_one_resource_where_fields, insert_resource};
+use rocket::http::{Header, Status};
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    DataExport(DataExportError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<DataExportError> for ResponseError {
+    fn from(error: DataExportError) -> Self {
+        ResponseError::DataExport(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl ExportResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// An export as shown to its owner, with the download link once it's ready.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportStatus {
+    #[serde(flatten)]
+    pub export: DataExport,
+    pub download_url: Option<String>,
+}
+
+impl From<DataExport> for ExportStatus {
+    fn from(export: DataExport) -> Self {
+        let download_url = match export.status {
+            DataExportStatus::Ready if !export.is_expired() => {
+                export.id.as_deref().map(download_url)
+            }
+            _ => None,
+        };
+        Self {
+            export,
+            download_url,
+        }
+    }
+}
+
+fn unauthorized() -> status::Custom<Value> {
+    status::Custom(
+        Status::Unauthorized,
+        serde_json::to_value(ExportResponse::error(
+            AuthenticationError::InvalidToken,
+            AuthenticationError::InvalidToken.to_string(),
+        ))
+        .unwrap(),
+    )
+}
+
+fn export_not_found() -> status::Custom<Value> {
+    status::Custom(
+        Status::NotFound,
+        serde_json::to_value(ExportResponse::error(
+            DataExportError::NotFound,
+            DataExportError::NotFound.to_string(),
+        ))
+        .unwrap(),
+    )
+}
+
+#[get("/")]
+pub async fn get_exports(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return unauthorized();
+        }
+    };
+
+    let export_params = [("user_id", DatabaseValue::String(token_value.user_id))];
+    match find_all_resources_where_fields!(DataExport, export_params).await {
+        Ok(exports) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(ExportResponse::success(
+                serde_json::to_value(
+                    exports
+                        .into_iter()
+                        .map(ExportStatus::from)
+                        .collect::<Vec<ExportStatus>>(),
+                )
+                .unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding data exports: {:?}", err);
+            export_not_found()
+        }
+    }
+}
+
+/// Starts building an archive of everything held about the current user.
+/// Poll the returned export until it has a `downloadUrl`. Only one export
+/// runs at a time; asking again while one is in progress returns it.
+#[post("/")]
+pub async fn create_export(token: RawToken, client: ClientInfo) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return unauthorized();
+        }
+    };
+
+    let export_params = [(
+        "user_id",
+        DatabaseValue::String(token_value.user_id.clone()),
+    )];
+    if let Ok(exports) = find_all_resources_where_fields!(DataExport, export_params).await {
+        if let Some(export) = exports
+            .into_iter()
+            .find(|export| export.is_in_progress() && !export.is_expired())
+        {
+            return status::Custom(
+                Status::Accepted,
+                serde_json::to_value(ExportResponse::success(
+                    serde_json::to_value(ExportStatus::from(export)).unwrap(),
+                    Some("Data export already in progress".to_string()),
+                ))
+                .unwrap(),
+            );
+        }
+    }
+
+    let export_params = vec![(
+        "user_id",
+        DatabaseValue::String(token_value.user_id.clone()),
+    )];
+    let export = match insert_resource!(DataExport, export_params).await {
+        Ok(export) => export,
+        Err(err) => {
+            println!("Error creating data export: {:?}", err);
+            return status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(ExportResponse::error(
+                    DataExportError::CreationFailed,
+                    DataExportError::CreationFailed.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+    spawn_export(&export);
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::DataExportRequested)
+            .user(&token_value.user_id)
+            .target("data_export", export.id.as_deref().unwrap_or_default())
+            .client(&client),
+    )
+    .await;
+
+    status::Custom(
+        Status::Accepted,
+        serde_json::to_value(ExportResponse::success(
+            serde_json::to_value(ExportStatus::from(export)).unwrap(),
+            Some("Data export started".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[get("/<id>")]
+pub async fn get_export(token: RawToken, id: String) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return unauthorized();
+        }
+    };
+
+    let export_params = [("id", &id), ("user_id", &token_value.user_id)];
+    match find_one_resource_where_fields!(DataExport, export_params).await {
+        Ok(export) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(ExportResponse::success(
+                serde_json::to_value(ExportStatus::from(export)).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding data export: {:?}", err);
+            export_not_found()
+        }
+    }
+}
+
+#[derive(Responder)]
+#[response(content_type = "application/zip")]
+pub struct ExportArchive {
+    pub body: Vec<u8>,
+    pub disposition: Header<'static>,
+}
+
+#[derive(Responder)]
+pub enum ExportDownload {
+    Archive(ExportArchive),
+    Response(status::Custom<Value>),
+}
+
+impl From<status::Custom<Value>> for ExportDownload {
+    fn from(response: status::Custom<Value>) -> Self {
+        ExportDownload::Response(response)
+    }
+}
+
+#[get("/<id>/download")]
+pub async fn download_export(token: RawToken, client: ClientInfo, id: String) -> ExportDownload {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return unauthorized().into();
+        }
+    };
+
+    let export_params = [("id", &id), ("user_id", &token_value.user_id)];
+    let export = match find_one_resource_where_fields!(DataExport, export_params).await {
+        Ok(export) => export,
+        Err(err) => {
+            println!("Error finding data export: {:?}", err);
+            return export_not_found().into();
+        }
+    };
+    if export.is_expired() {
+        return status::Custom(
+            Status::Gone,
+            serde_json::to_value(ExportResponse::error(
+                DataExportError::Expired,
+                DataExportError::Expired.to_string(),
+            ))
+            .unwrap(),
+        )
+        .into();
+    }
+    let file_name = match (&export.status, &export.file_name) {
+        (DataExportStatus::Ready, Some(file_name)) => file_name,
+        _ => {
+            return status::Custom(
+                Status::Conflict,
+                serde_json::to_value(ExportResponse::error(
+                    DataExportError::NotReady,
+                    DataExportError::NotReady.to_string(),
+                ))
+                .unwrap(),
+            )
+            .into();
+        }
+    };
+
+    let archive = match rocket::tokio::fs::read(export_path(file_name)).await {
+        Ok(archive) => archive,
+        Err(err) => {
+            println!("Error reading export archive: {:?}", err);
+            return export_not_found().into();
+        }
+    };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::DataExportDownloaded)
+            .user(&token_value.user_id)
+            .target("data_export", &id)
+            .client(&client),
+    )
+    .await;
+
+    ExportDownload::Archive(ExportArchive {
+        body: archive,
+        disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"capabilities-export-{}.zip\"", id),
+        ),
+    })
+}
@@ -0,0 +1,208 @@
+use crate::api::client::ClientInfo;
+use crate::api::oidc::oidc_error_status;
+use crate::api::token::{validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::AuthenticationError;
+use crate::models::user_identity::{OidcError, UserIdentity};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::oidc::begin_oidc_login;
+use crate::{
+    delete_resource_where_fields, find_all_resources_where_fields, find_one_resource_where_fields,
+};
+use rocket::http::Status;
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Oidc(OidcError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<OidcError> for ResponseError {
+    fn from(error: OidcError) -> Self {
+        ResponseError::Oidc(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdentitiesResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl IdentitiesResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[get("/")]
+pub async fn get_identities(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(IdentitiesResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let identity_params = [("user_id", DatabaseValue::String(token_value.user_id))];
+    match find_all_resources_where_fields!(UserIdentity, identity_params).await {
+        Ok(identities) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(IdentitiesResponse::success(
+                serde_json::to_value(identities).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding identities: {:?}", err);
+            status::Custom(
+                Status::NotFound,
+                serde_json::to_value(IdentitiesResponse::error(
+                    OidcError::IdentityNotFound,
+                    OidcError::IdentityNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+/// Starts a sign-on whose identity gets linked to the current user. It is
+/// completed through the same callback as a login.
+#[post("/oidc")]
+pub async fn link_oidc_identity(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(IdentitiesResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    match begin_oidc_login(Some(&token_value.user_id)).await {
+        Ok(authorization_url) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(IdentitiesResponse::success(
+                serde_json::json!({ "authorizationUrl": authorization_url }),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            let message = err.to_string();
+            status::Custom(
+                oidc_error_status(&err),
+                serde_json::to_value(IdentitiesResponse::error(err, message)).unwrap(),
+            )
+        }
+    }
+}
+
+#[delete("/<id>")]
+pub async fn delete_identity(
+    token: RawToken,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(IdentitiesResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let identity_params = [("id", &id), ("user_id", &token_value.user_id)];
+    let identity = match find_one_resource_where_fields!(UserIdentity, identity_params).await {
+        Ok(identity) => identity,
+        Err(err) => {
+            println!("Error finding identity: {:?}", err);
+            return status::Custom(
+                Status::NotFound,
+                serde_json::to_value(IdentitiesResponse::error(
+                    OidcError::IdentityNotFound,
+                    OidcError::IdentityNotFound.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let delete_params = vec![("id", id.clone())];
+    match delete_resource_where_fields!(UserIdentity, delete_params).await {
+        Ok(_) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::IdentityUnlinked)
+                    .user(&token_value.user_id)
+                    .target("identity", &id)
+                    .client(&client)
+                    .details(serde_json::json!({ "issuer": identity.issuer })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(IdentitiesResponse::success(
+                    serde_json::json!(null),
+                    Some("Identity unlinked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error unlinking identity: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(IdentitiesResponse::error(
+                    OidcError::IdentityNotFound,
+                    OidcError::IdentityNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::client::ClientInfo;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::invitation::{Invitation, InvitationError};
 use crate::models::user::{User, UserError};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::invitations::{accept_invitation as accept_team_invitation, answerable};
 use crate::{
     find_all_unarchived_resources_where_fields, find_one_unarchived_resource_where_fields,
     update_resource,
@@ -74,17 +78,14 @@ impl InvitationsResponse {
 
 #[get("/")]
 pub async fn get_invitations(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -132,17 +133,14 @@ pub async fn get_invitations(token: RawToken) -> status::Custom<Value> {
 
 #[get("/<invitation_id>")]
 pub async fn get_invitation(token: RawToken, invitation_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -215,18 +213,19 @@ pub async fn get_invitation(token: RawToken, invitation_id: String) -> status::C
 }
 
 #[post("/<invitation_id>/accept")]
-pub async fn accept_invitation(token: RawToken, invitation_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+pub async fn accept_invitation(
+    token: RawToken,
+    client: ClientInfo,
+    invitation_id: String,
+) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -248,68 +247,62 @@ pub async fn accept_invitation(token: RawToken, invitation_id: String) -> status
         }
     };
 
-    let invitation_params = vec![("id", &invitation_id), ("user_id", &token_value.user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(Invitation, invitation_params).await {
-        Ok(invitation) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(InvitationsResponse::success(
-                serde_json::to_value(invitation).unwrap(),
-                None,
-            ))
-            .unwrap(),
-        ),
-        Err(err) => {
-            println!("Error finding invitation: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(InvitationsResponse::error(
-                    InvitationError::InvitationNotFound,
-                    InvitationError::InvitationNotFound.to_string(),
+    match accept_team_invitation(&invitation_id, &token_value.user_id).await {
+        Ok((invitation, team_user)) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::InvitationAccepted)
+                    .user(&token_value.user_id)
+                    .team(invitation.team_id.as_deref().unwrap_or_default())
+                    .target("invitation", &invitation_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "teamRole": team_user.team_role.map(|role| role.to_string()),
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(InvitationsResponse::success(
+                    serde_json::to_value(invitation).unwrap(),
+                    None,
                 ))
                 .unwrap(),
-            );
+            )
         }
-    };
-
-    let accepted_at =
-        DatabaseValue::DateTime(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap());
-    let accepted_at_param = vec![("accepted_at", accepted_at)];
-    match update_resource!(Invitation, invitation_id, accepted_at_param).await {
-        Ok(invitation) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(InvitationsResponse::success(
-                serde_json::to_value(invitation).unwrap(),
-                None,
-            ))
-            .unwrap(),
-        ),
         Err(err) => {
-            println!("Error updating invitation: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(InvitationsResponse::error(
-                    InvitationError::InvitationNotFound,
-                    InvitationError::InvitationNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
+            println!("Error accepting invitation: {:?}", err);
+            let status = match err {
+                InvitationError::InvitationNotFound => Status::NotFound,
+                InvitationError::InvitationAlreadyAnswered
+                | InvitationError::InviteeAlreadyMember => Status::Conflict,
+                InvitationError::InvitationExpired | InvitationError::InvitationRevoked => {
+                    Status::Gone
+                }
+                _ => Status::InternalServerError,
+            };
+            let message = err.to_string();
+            status::Custom(
+                status,
+                serde_json::to_value(InvitationsResponse::error(err, message)).unwrap(),
+            )
         }
     }
 }
 
 #[post("/<invitation_id>/reject")]
-pub async fn reject_invitation(token: RawToken, invitation_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+pub async fn reject_invitation(
+    token: RawToken,
+    client: ClientInfo,
+    invitation_id: String,
+) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::InvitationsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -332,40 +325,56 @@ pub async fn reject_invitation(token: RawToken, invitation_id: String) -> status
     };
 
     let invitation_params = vec![("id", &invitation_id), ("user_id", &token_value.user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(Invitation, invitation_params).await {
-        Ok(invitation) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(InvitationsResponse::success(
-                serde_json::to_value(invitation).unwrap(),
-                None,
-            ))
-            .unwrap(),
-        ),
-        Err(err) => {
-            println!("Error finding invitation: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(InvitationsResponse::error(
-                    InvitationError::InvitationNotFound,
-                    InvitationError::InvitationNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    let invitation =
+        match find_one_unarchived_resource_where_fields!(Invitation, invitation_params).await {
+            Ok(invitation) => invitation,
+            Err(err) => {
+                println!("Error finding invitation: {:?}", err);
+                return status::Custom(
+                    Status::NotFound,
+                    serde_json::to_value(InvitationsResponse::error(
+                        InvitationError::InvitationNotFound,
+                        InvitationError::InvitationNotFound.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
+        };
+
+    if let Err(err) = answerable(&invitation) {
+        let status = match err {
+            InvitationError::InvitationAlreadyAnswered => Status::Conflict,
+            _ => Status::Gone,
+        };
+        let message = err.to_string();
+        return status::Custom(
+            status,
+            serde_json::to_value(InvitationsResponse::error(err, message)).unwrap(),
+        );
+    }
 
     let rejected_at =
         DatabaseValue::DateTime(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap());
     let rejected_at_param = vec![("rejected_at", rejected_at)];
     match update_resource!(Invitation, invitation_id, rejected_at_param).await {
-        Ok(invitation) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(InvitationsResponse::success(
-                serde_json::to_value(invitation).unwrap(),
-                None,
-            ))
-            .unwrap(),
-        ),
+        Ok(invitation) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::InvitationRejected)
+                    .user(&token_value.user_id)
+                    .team(invitation.team_id.as_deref().unwrap_or_default())
+                    .target("invitation", &invitation_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(InvitationsResponse::success(
+                    serde_json::to_value(invitation).unwrap(),
+                    None,
+                ))
+                .unwrap(),
+            )
+        }
         Err(err) => {
             println!("Error updating invitation: {:?}", err);
             return status::Custom(
@@ -0,0 +1,90 @@
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::join_request::JoinRequestError;
+use crate::utils::join_requests::user_join_requests;
+use rocket::http::Status;
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    JoinRequest(JoinRequestError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<JoinRequestError> for ResponseError {
+    fn from(error: JoinRequestError) -> Self {
+        ResponseError::JoinRequest(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinRequestsResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl JoinRequestsResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+/// The user's join requests and how each was decided.
+#[get("/")]
+pub async fn get_join_requests(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsRead).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(JoinRequestsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    match user_join_requests(&token_value.user_id).await {
+        Ok(join_requests) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(JoinRequestsResponse::success(
+                serde_json::to_value(join_requests).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding join requests: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(JoinRequestsResponse::error(
+                    JoinRequestError::JoinRequestNotFound,
+                    JoinRequestError::JoinRequestNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
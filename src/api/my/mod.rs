@@ -1,6 +1,13 @@
 pub mod activities;
+pub mod api_keys;
+pub mod audit;
 pub mod backup_codes;
+pub mod export;
+pub mod identities;
 pub mod invitations;
+pub mod join_requests;
+pub mod sessions;
 pub mod teams;
+pub mod two_factor;
 pub mod user;
 pub mod user_skills;
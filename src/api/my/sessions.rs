@@ -0,0 +1,247 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::{Authentication, AuthenticationError};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::sessions::{revoke_session, revoke_user_sessions};
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use crate::{find_all_resources_where_fields, find_one_resource_where_fields};
+use rocket::http::Status;
+use rocket::response::status;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub data: Option<Value>,
+    pub message: Option<String>,
+    pub error: Option<ResponseError>,
+}
+
+impl SessionsResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            data: Some(data),
+            message,
+            error: None,
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            data: None,
+            message: Some(message),
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// A session as shown to its owner. The token itself is never included.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub id: String,
+    pub current: bool,
+    pub second_factor_pending: bool,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub last_seen_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl Session {
+    pub fn from_authentication(authentication: Authentication, current_id: &str) -> Self {
+        Self {
+            current: authentication.id == current_id,
+            id: authentication.id,
+            second_factor_pending: authentication.second_factor_pending,
+            user_agent: authentication.user_agent,
+            ip_address: authentication.ip_address,
+            created_at: authentication.created_at,
+            last_seen_at: authentication.last_seen_at,
+            expires_at: authentication.expires_at,
+        }
+    }
+}
+
+#[get("/")]
+pub async fn get_sessions(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(SessionsResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let session_params = [(
+        "user_id",
+        DatabaseValue::String(token_value.user_id.clone()),
+    )];
+    match find_all_resources_where_fields!(Authentication, session_params).await {
+        Ok(authentications) => {
+            let mut sessions = authentications
+                .into_iter()
+                .map(|authentication| {
+                    Session::from_authentication(authentication, &token_value.authentication_id)
+                })
+                .collect::<Vec<Session>>();
+            sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(SessionsResponse::success(
+                    serde_json::to_value(sessions).unwrap(),
+                    None,
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error finding sessions: {:?}", err);
+            status::Custom(
+                Status::NotFound,
+                serde_json::to_value(SessionsResponse::error(
+                    AuthenticationError::SessionNotFound,
+                    AuthenticationError::SessionNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+#[delete("/<id>")]
+pub async fn delete_session(
+    token: RawToken,
+    client: ClientInfo,
+    id: String,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(SessionsResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let session_params = [("id", &id), ("user_id", &token_value.user_id)];
+    if let Err(err) = find_one_resource_where_fields!(Authentication, session_params).await {
+        println!("Error finding session: {:?}", err);
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(SessionsResponse::error(
+                AuthenticationError::SessionNotFound,
+                AuthenticationError::SessionNotFound.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    match revoke_session(&id).await {
+        Ok(_) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::SessionRevoked)
+                    .user(&token_value.user_id)
+                    .target("session", &id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(SessionsResponse::success(
+                    serde_json::json!(null),
+                    Some("Session revoked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(SessionsResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
+
+#[delete("/")]
+pub async fn delete_other_sessions(token: RawToken, client: ClientInfo) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(SessionsResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    match revoke_user_sessions(&token_value.user_id, Some(&token_value.authentication_id)).await {
+        Ok(revoked) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::OtherSessionsRevoked)
+                    .user(&token_value.user_id)
+                    .client(&client)
+                    .details(serde_json::json!({ "revoked": revoked })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(SessionsResponse::success(
+                    serde_json::json!({ "revoked": revoked }),
+                    Some("Other sessions revoked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(SessionsResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
@@ -1,17 +1,39 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::client::ClientInfo;
+use crate::api::team_access::TeamAccess;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
 use crate::models::activity::{Activity, ActivityError};
+use crate::models::api_key::ApiKeyScope;
+use crate::models::audit_event::AuditError;
 use crate::models::authentication::AuthenticationError;
 use crate::models::invitation::{Invitation, InvitationError};
+use crate::models::organization::OrganizationError;
+use crate::models::permission::{
+    ActivityAssign, ActivityCreate, ActivityDelete, ActivityRead, ActivityUpdate, InvitationRevoke,
+    Permission, PermissionError, TeamAccessError, TeamAudit, TeamDelete, TeamInvite, TeamRead,
+    TeamTakeOwnership, TeamTransfer, TeamUpdate,
+};
 use crate::models::team::{Team, TeamError};
 use crate::models::team_role::TeamRole;
 use crate::models::user::{User, UserError};
+use crate::utils::audit::{record_audit_event, team_audit_events, AuditAction, AuditEntry};
+use crate::utils::invitations::{
+    create_invitation as create_team_invitation, resend_invitation as resend_team_invitation,
+    revoke_invitation as revoke_team_invitation,
+};
+use crate::utils::organizations::{is_in_team_organization, organization_role, user_organizations};
+use crate::utils::team_archival::{archive_team, restore_team as restore_archived_team};
+use crate::utils::team_hierarchy::{has_team_permission, set_parent_team};
+use crate::utils::teams::{
+    accept_team_ownership, cancel_team_ownership_offer, is_team_member, offer_team_ownership,
+    team_members,
+};
 use crate::{
-    delete_resource_where_fields, find_all_unarchived_resources_where_fields,
-    find_one_resource_where_fields, find_one_unarchived_resource_where_fields, insert_resource,
-    update_resource,
+    delete_resource_where_fields, find_all_archived_resources_where_fields,
+    find_all_unarchived_resources_where_fields, find_one_resource_where_fields,
+    find_one_unarchived_resource_where_fields, insert_resource, update_resource,
 };
-use futures::future::try_join_all;
+use futures::future::join_all;
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::{Json, Value};
@@ -25,7 +47,10 @@ pub enum ResponseError {
     User(UserError),
     Team(TeamError),
     Invitation(InvitationError),
+    Organization(OrganizationError),
     Activity(ActivityError),
+    Audit(AuditError),
+    Permission(PermissionError),
 }
 
 impl From<AuthenticationError> for ResponseError {
@@ -52,12 +77,34 @@ impl From<InvitationError> for ResponseError {
     }
 }
 
+impl From<OrganizationError> for ResponseError {
+    fn from(error: OrganizationError) -> Self {
+        ResponseError::Organization(error)
+    }
+}
+
 impl From<ActivityError> for ResponseError {
     fn from(error: ActivityError) -> Self {
         ResponseError::Activity(error)
     }
 }
 
+impl From<AuditError> for ResponseError {
+    fn from(error: AuditError) -> Self {
+        ResponseError::Audit(error)
+    }
+}
+
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TeamsResponse {
     pub error: Option<ResponseError>,
@@ -83,19 +130,24 @@ impl TeamsResponse {
     }
 }
 
-#[get("/")]
-pub async fn get_teams(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+/// The teams the caller owns. With `?archived=true`, the archived ones that
+/// can still be restored instead.
+#[get("/?<archived>")]
+pub async fn get_teams(token: RawToken, archived: Option<bool>) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
@@ -118,7 +170,12 @@ pub async fn get_teams(token: RawToken) -> status::Custom<Value> {
     };
 
     let owned_teams_params = vec![("owner_id", &user_id)];
-    match find_all_unarchived_resources_where_fields!(Team, owned_teams_params).await {
+    let teams = if archived.unwrap_or(false) {
+        find_all_archived_resources_where_fields!(Team, owned_teams_params).await
+    } else {
+        find_all_unarchived_resources_where_fields!(Team, owned_teams_params).await
+    };
+    match teams {
         Ok(teams) => status::Custom(
             Status::Ok,
             serde_json::to_value(TeamsResponse::success(
@@ -157,40 +214,30 @@ pub struct TeamResponse {
 }
 
 #[get("/<team_id>")]
-pub async fn get_team(token: RawToken, team_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
+pub async fn get_team(
+    access: Result<TeamAccess<TeamRead>, TeamAccessError>,
+    team_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
     };
 
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let find_user_params = vec![("id", &user_id)];
-    let user = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
-        Ok(user) => user,
+    let users = match team_members(&team_id).await {
+        Ok(users) => users,
         Err(err) => {
-            println!("Error finding user: {:?}", err);
+            println!("Error finding team members: {:?}", err);
             return status::Custom(
                 Status::NotFound,
                 serde_json::to_value(TeamsResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
+                    TeamError::TeamNotFound,
+                    TeamError::TeamNotFound.to_string(),
                 ))
                 .unwrap(),
             );
         }
     };
 
-    let users = vec![user];
     let invitations_params = vec![("team_id", &team_id)];
     let invitations =
         match find_all_unarchived_resources_where_fields!(Invitation, invitations_params).await {
@@ -210,74 +257,114 @@ pub async fn get_team(token: RawToken, team_id: String) -> status::Custom<Value>
     let invitation_responses = invitations.iter().map(|invitation| async move {
         let user_id = invitation.user_id.clone();
         let user_params = vec![("id", &user_id)];
+        // Invitees who deleted their account are left out.
         let user = match find_one_unarchived_resource_where_fields!(User, user_params).await {
             Ok(user) => user,
             Err(err) => {
                 println!("Error finding user: {:?}", err);
-                return Err(err);
+                return None;
             }
         };
-        Ok(InvitationResponse {
+        Some(InvitationResponse {
             invitation: invitation.clone(),
             user,
             accepted: invitation.is_accepted(),
             rejected: invitation.is_rejected(),
         })
     });
-    let invitations_response = try_join_all(invitation_responses).await.unwrap();
+    let invitations_response = join_all(invitation_responses)
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<InvitationResponse>>();
 
-    let team_id_param = vec![("id", &team_id), ("owner_id", &token_value.user_id)];
-    match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(TeamsResponse::success(
-                serde_json::to_value(TeamResponse {
-                    team,
-                    users,
-                    invitations: invitations_response,
-                })
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(TeamsResponse::success(
+            serde_json::to_value(TeamResponse {
+                team: access.team,
+                users,
+                invitations: invitations_response,
+            })
+            .unwrap(),
+            Some("Team fetched successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTeamRequest {
+    pub team_name: String,
+    pub team_description: Option<String>,
+
+    #[serde(default)]
+    pub is_discoverable: bool,
+
+    /// Required when the caller belongs to more than one organization.
+    pub organization_id: Option<String>,
+}
+
+/// The organization a new team goes into: the one asked for, as long as the
+/// caller belongs to it, or otherwise the caller's only organization.
+async fn team_organization(
+    user_id: &str,
+    organization_id: Option<&str>,
+) -> Result<String, status::Custom<Value>> {
+    if let Some(organization_id) = organization_id {
+        if organization_role(organization_id, user_id).await.is_none() {
+            return Err(status::Custom(
+                Status::NotFound,
+                serde_json::to_value(TeamsResponse::error(
+                    OrganizationError::OrganizationNotFound,
+                    OrganizationError::OrganizationNotFound.to_string(),
+                ))
                 .unwrap(),
-                Some("Team fetched successfully".to_string()),
+            ));
+        }
+        return Ok(organization_id.to_string());
+    }
+
+    match user_organizations(user_id).await {
+        Ok(organizations) if organizations.len() == 1 => {
+            Ok(organizations[0].id.clone().unwrap_or_default())
+        }
+        Ok(_) => Err(status::Custom(
+            Status::UnprocessableEntity,
+            serde_json::to_value(TeamsResponse::error(
+                OrganizationError::OrganizationRequired,
+                OrganizationError::OrganizationRequired.to_string(),
             ))
             .unwrap(),
-        ),
+        )),
         Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
+            println!("Error finding organizations: {:?}", err);
+            Err(status::Custom(
+                Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    TeamError::TeamCreationFailed,
+                    TeamError::TeamCreationFailed.to_string(),
                 ))
                 .unwrap(),
-            );
+            ))
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CreateTeamRequest {
-    pub team_name: String,
-    pub team_description: Option<String>,
-}
-
 #[post("/", data = "<team_data>")]
 pub async fn create_team(
     token: RawToken,
+    client: ClientInfo,
     team_data: Json<CreateTeamRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
@@ -299,19 +386,39 @@ pub async fn create_team(
         }
     };
 
+    let organization_id =
+        match team_organization(&token_value.user_id, team_data.organization_id.as_deref()).await {
+            Ok(organization_id) => organization_id,
+            Err(response) => return response,
+        };
+
     let team_params = vec![
         ("owner_id", user_id),
         (
             "team_name",
             DatabaseValue::String(team_data.team_name.clone()),
         ),
+        (
+            "organization_id",
+            DatabaseValue::String(organization_id.clone()),
+        ),
     ];
 
     match find_one_resource_where_fields!(Team, team_params).await {
+        Ok(team) if team.archived_at.is_some() => {
+            // Archived teams only come back through the restore endpoint
+            status::Custom(
+                Status::Conflict,
+                serde_json::to_value(TeamsResponse::error(
+                    TeamError::TeamArchived,
+                    TeamError::TeamArchived.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
         Ok(team) => {
             // Team exists, update it
             let team_params = vec![
-                ("archived_at", DatabaseValue::None),
                 (
                     "team_name",
                     DatabaseValue::String(team_data.team_name.clone()),
@@ -320,16 +427,30 @@ pub async fn create_team(
                     "team_description",
                     DatabaseValue::String(team_data.team_description.clone().unwrap_or_default()),
                 ),
+                (
+                    "is_discoverable",
+                    DatabaseValue::Boolean(team_data.is_discoverable.to_string()),
+                ),
             ];
             match update_resource!(Team, team.id, team_params).await {
-                Ok(team) => status::Custom(
-                    Status::Ok,
-                    serde_json::to_value(TeamsResponse::success(
-                        serde_json::to_value(team).unwrap(),
-                        Some("Team updated successfully".to_string()),
-                    ))
-                    .unwrap(),
-                ),
+                Ok(team) => {
+                    record_audit_event(
+                        AuditEntry::new(AuditAction::TeamUpdated)
+                            .actor(&token_value.user_id)
+                            .team(team.id.as_deref().unwrap_or_default())
+                            .target("team", team.id.as_deref().unwrap_or_default())
+                            .client(&client),
+                    )
+                    .await;
+                    status::Custom(
+                        Status::Ok,
+                        serde_json::to_value(TeamsResponse::success(
+                            serde_json::to_value(team).unwrap(),
+                            Some("Team updated successfully".to_string()),
+                        ))
+                        .unwrap(),
+                    )
+                }
                 Err(err) => {
                     println!("Error updating team: {:?}", err);
                     return status::Custom(
@@ -350,6 +471,7 @@ pub async fn create_team(
                     "owner_id",
                     DatabaseValue::String(token_value.user_id.clone()),
                 ),
+                ("organization_id", DatabaseValue::String(organization_id)),
                 (
                     "team_name",
                     DatabaseValue::String(team_data.team_name.clone()),
@@ -361,6 +483,10 @@ pub async fn create_team(
                         None => DatabaseValue::None,
                     },
                 ),
+                (
+                    "is_discoverable",
+                    DatabaseValue::Boolean(team_data.is_discoverable.to_string()),
+                ),
             ];
 
             let team = match insert_resource!(Team, team_params).await {
@@ -378,6 +504,15 @@ pub async fn create_team(
                 }
             };
 
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamCreated)
+                    .actor(&token_value.user_id)
+                    .team(team.id.as_deref().unwrap_or_default())
+                    .target("team", team.id.as_deref().unwrap_or_default())
+                    .client(&client),
+            )
+            .await;
+
             let team_response = TeamsResponse::success(
                 serde_json::to_value(team.clone()).unwrap(),
                 Some("Team created successfully".to_string()),
@@ -396,63 +531,23 @@ pub async fn create_team(
 pub struct UpdateTeamRequest {
     pub team_name: String,
     pub team_description: Option<String>,
+    /// Left out, the team stays as discoverable as it was.
+    pub is_discoverable: Option<bool>,
 }
 
 #[put("/<team_id>", data = "<team_data>")]
 pub async fn update_team(
-    token: RawToken,
+    access: Result<TeamAccess<TeamUpdate>, TeamAccessError>,
+    client: ClientInfo,
     team_id: String,
     team_data: Json<UpdateTeamRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let find_user_params = vec![("id", &user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
-        Ok(user) => user,
-        Err(err) => {
-            println!("Error finding user: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![("id", &team_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
     };
 
-    let team_update_params = vec![
+    let mut team_update_params = vec![
         (
             "team_name",
             DatabaseValue::String(team_data.team_name.clone()),
@@ -465,9 +560,28 @@ pub async fn update_team(
             },
         ),
     ];
+    if let Some(is_discoverable) = team_data.is_discoverable {
+        team_update_params.push((
+            "is_discoverable",
+            DatabaseValue::Boolean(is_discoverable.to_string()),
+        ));
+    }
 
     match update_resource!(Team, team_id, team_update_params).await {
         Ok(team) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamUpdated)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("team", &team_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "teamName": team_data.team_name,
+                        "isDiscoverable": team_data.is_discoverable,
+                    })),
+            )
+            .await;
+
             let team_response = TeamsResponse::success(
                 serde_json::to_value(team).unwrap(),
                 Some("Team updated successfully".to_string()),
@@ -489,75 +603,171 @@ pub async fn update_team(
     }
 }
 
-#[delete("/<team_id>")]
-pub async fn delete_team(token: RawToken, team_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamParentRequest {
+    /// `None` moves the team back to the top level.
+    pub parent_team_id: Option<String>,
+
+    #[serde(default)]
+    pub inherits_permissions: bool,
+}
+
+/// Moves the team under another team of its organization, or back to the
+/// top level. Besides updating this team, the caller needs to be able to
+/// update the new parent.
+#[put("/<team_id>/parent", data = "<parent_data>")]
+pub async fn update_team_parent(
+    access: Result<TeamAccess<TeamUpdate>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    parent_data: Json<TeamParentRequest>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
     };
 
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let find_user_params = vec![("id", &user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
-        Ok(user) => user,
-        Err(err) => {
-            println!("Error finding user: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
+    if let Some(parent_team_id) = &parent_data.parent_team_id {
+        if !has_team_permission(parent_team_id, access.user_id(), Permission::TeamUpdate).await {
+            return access_error(TeamAccessError::Permission(
+                PermissionError::PermissionDenied,
+            ));
         }
-    };
+    }
 
-    let team_id_param = vec![("id", &team_id)];
-    let team = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
+    let team = match set_parent_team(
+        &access.team,
+        parent_data.parent_team_id.as_deref(),
+        parent_data.inherits_permissions,
+    )
+    .await
+    {
         Ok(team) => team,
         Err(err) => {
-            println!("Error finding team: {:?}", err);
+            let status = match err {
+                TeamError::ParentTeamNotFound => Status::NotFound,
+                TeamError::TeamHierarchyCycle => Status::UnprocessableEntity,
+                _ => Status::InternalServerError,
+            };
             return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
+                status,
+                serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
 
-    match delete_resource_where_fields!(Team, team_id_param).await {
-        Ok(_) => {
+    record_audit_event(
+        AuditEntry::new(AuditAction::TeamParentChanged)
+            .actor(access.user_id())
+            .team(&team_id)
+            .target("team", &team_id)
+            .client(&client)
+            .details(serde_json::json!({
+                "previousParentTeamId": access.team.parent_team_id,
+                "parentTeamId": team.parent_team_id,
+                "inheritsPermissions": team.inherits_permissions,
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(TeamsResponse::success(
+            serde_json::to_value(team).unwrap(),
+            Some("Team moved successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+fn archival_error(err: TeamError) -> status::Custom<Value> {
+    let status = match err {
+        TeamError::TeamArchived | TeamError::TeamNotArchived => Status::Conflict,
+        _ => Status::InternalServerError,
+    };
+    status::Custom(
+        status,
+        serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+/// Archives the team. It stays readable to its members but can't be
+/// changed, and is purged for good once the retention period is over unless
+/// the owner restores it first.
+#[delete("/<team_id>")]
+pub async fn delete_team(
+    access: Result<TeamAccess<TeamDelete>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match archive_team(&team_id).await {
+        Ok(team) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamArchived)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("team", &team_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "teamName": team.team_name,
+                        "purgeAfter": team.purge_after.and_then(|purge_after| purge_after.format(&Iso8601::DEFAULT).ok()),
+                    })),
+            )
+            .await;
+
             let team_response = TeamsResponse::success(
                 serde_json::to_value(team).unwrap(),
-                Some("Team deleted successfully".to_string()),
+                Some("Team archived successfully".to_string()),
             );
 
             status::Custom(Status::Ok, serde_json::to_value(team_response).unwrap())
         }
         Err(err) => {
-            println!("Error deleting team: {:?}", err);
-            return status::Custom(
-                Status::InternalServerError,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamDeletionFailed,
-                    TeamError::TeamDeletionFailed.to_string(),
-                ))
-                .unwrap(),
+            println!("Error archiving team: {:?}", err);
+            archival_error(err)
+        }
+    }
+}
+
+/// Brings an archived team back before it is purged.
+#[post("/<team_id>/restore")]
+pub async fn restore_team(
+    access: Result<TeamAccess<TeamDelete>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match restore_archived_team(&team_id).await {
+        Ok(team) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamRestored)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("team", &team_id)
+                    .client(&client),
+            )
+            .await;
+
+            let team_response = TeamsResponse::success(
+                serde_json::to_value(team).unwrap(),
+                Some("Team restored successfully".to_string()),
             );
+
+            status::Custom(Status::Ok, serde_json::to_value(team_response).unwrap())
+        }
+        Err(err) => {
+            println!("Error restoring team: {:?}", err);
+            archival_error(err)
         }
     }
 }
@@ -568,95 +778,391 @@ pub struct InvitationRequest {
     pub team_role: TeamRole,
 }
 
+fn invitation_error(err: InvitationError) -> status::Custom<Value> {
+    let status = match err {
+        InvitationError::InvitationNotFound => Status::NotFound,
+        InvitationError::InvitationAlreadyPending
+        | InvitationError::InviteeAlreadyMember
+        | InvitationError::InvitationAlreadyAnswered
+        | InvitationError::InvitationRevoked => Status::Conflict,
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(TeamsResponse::error(err, message)).unwrap(),
+    )
+}
+
 #[post("/<team_id>/invitations", data = "<invitation_data>")]
 pub async fn create_invitation(
-    token: RawToken,
+    access: Result<TeamAccess<TeamInvite>, TeamAccessError>,
+    client: ClientInfo,
     team_id: String,
     invitation_data: Json<InvitationRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    // Inviting someone as an admin hands out more than team.invite, so it
+    // takes the same standing as changing the team itself.
+    if invitation_data.team_role == TeamRole::Admin && !access.can(Permission::TeamUpdate) {
+        return access_error(TeamAccessError::Permission(
+            PermissionError::PermissionDenied,
+        ));
+    }
+
+    let invitee_params = [("id", &invitation_data.user_id)];
+    if let Err(err) = find_one_unarchived_resource_where_fields!(User, invitee_params).await {
+        println!("Error finding invitee: {:?}", err);
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(TeamsResponse::error(
+                UserError::UserNotFound,
+                UserError::UserNotFound.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+    if !is_in_team_organization(&team_id, &invitation_data.user_id).await {
+        return status::Custom(
+            Status::UnprocessableEntity,
+            serde_json::to_value(TeamsResponse::error(
+                OrganizationError::NotOrganizationMember,
+                OrganizationError::NotOrganizationMember.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let invitation = match create_team_invitation(
+        &team_id,
+        &invitation_data.user_id,
+        access.user_id(),
+        &invitation_data.team_role,
+    )
+    .await
+    {
+        Ok(invitation) => invitation,
         Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
+            println!("Error creating invitation: {:?}", err);
+            return invitation_error(err);
         }
     };
 
-    let team_id_param = vec![("id", &team_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
+    record_audit_event(
+        AuditEntry::new(AuditAction::InvitationCreated)
+            .actor(access.user_id())
+            .team(&team_id)
+            .target("invitation", invitation.id.as_deref().unwrap_or_default())
+            .client(&client)
+            .details(serde_json::json!({
+                "userId": invitation_data.user_id,
+                "teamRole": invitation_data.team_role.to_string(),
+            })),
+    )
+    .await;
+
+    let invitation_response = TeamsResponse::success(
+        serde_json::to_value(invitation).unwrap(),
+        Some("Invitation created successfully".to_string()),
+    );
+
+    status::Custom(
+        Status::Created,
+        serde_json::to_value(invitation_response).unwrap(),
+    )
+}
+
+/// Gives a pending invitation a fresh expiry.
+#[post("/<team_id>/invitations/<invitation_id>/resend")]
+pub async fn resend_invitation(
+    access: Result<TeamAccess<TeamInvite>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    invitation_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match resend_team_invitation(&team_id, &invitation_id).await {
+        Ok(invitation) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::InvitationResent)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("invitation", &invitation_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TeamsResponse::success(
+                    serde_json::to_value(invitation).unwrap(),
+                    Some("Invitation resent successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
         Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+            println!("Error resending invitation: {:?}", err);
+            invitation_error(err)
+        }
+    }
+}
+
+#[delete("/<team_id>/invitations/<invitation_id>")]
+pub async fn revoke_invitation(
+    access: Result<TeamAccess<InvitationRevoke>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    invitation_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match revoke_team_invitation(&team_id, &invitation_id).await {
+        Ok(invitation) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::InvitationRevoked)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("invitation", &invitation_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TeamsResponse::success(
+                    serde_json::to_value(invitation).unwrap(),
+                    Some("Invitation revoked successfully".to_string()),
                 ))
                 .unwrap(),
-            );
+            )
+        }
+        Err(err) => {
+            println!("Error revoking invitation: {:?}", err);
+            invitation_error(err)
         }
+    }
+}
+
+fn ownership_error(err: TeamError) -> status::Custom<Value> {
+    let status = match err {
+        TeamError::TeamNotFound | TeamError::NoOwnershipOffer => Status::NotFound,
+        TeamError::NomineeNotAdmin => Status::UnprocessableEntity,
+        _ => Status::InternalServerError,
     };
+    status::Custom(
+        status,
+        serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
 
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let team_id = DatabaseValue::String(team_id.clone());
-    let team_role = DatabaseValue::String(invitation_data.0.team_role.to_string());
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferTeamRequest {
+    pub new_owner_id: String,
+}
 
-    let invitation_params = vec![
-        ("user_id", user_id),
-        ("team_id", team_id),
-        ("team_role", team_role),
-    ];
+/// Offers the team to one of its admins. Nothing changes hands until they
+/// accept.
+#[post("/<team_id>/transfer", data = "<transfer_data>")]
+pub async fn offer_team_transfer(
+    access: Result<TeamAccess<TeamTransfer>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    transfer_data: Json<TransferTeamRequest>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
 
-    let invitation = match insert_resource!(Invitation, invitation_params).await {
-        Ok(invitation) => invitation,
+    match offer_team_ownership(&team_id, access.user_id(), &transfer_data.new_owner_id).await {
+        Ok(team) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamOwnershipOffered)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("user", &transfer_data.new_owner_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TeamsResponse::success(
+                    serde_json::to_value(team).unwrap(),
+                    Some("Team ownership offered".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
         Err(err) => {
-            println!("Error creating invitation: {:?}", err);
-            return status::Custom(
-                Status::InternalServerError,
-                serde_json::to_value(TeamsResponse::error(
-                    InvitationError::InvitationCreationFailed,
-                    InvitationError::InvitationCreationFailed.to_string(),
+            println!("Error offering team ownership: {:?}", err);
+            ownership_error(err)
+        }
+    }
+}
+
+async fn cancel_team_transfer(
+    team_id: &str,
+    user_id: &str,
+    client: &ClientInfo,
+    message: &str,
+) -> status::Custom<Value> {
+    match cancel_team_ownership_offer(team_id, user_id).await {
+        Ok(team) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamOwnershipOfferCancelled)
+                    .actor(user_id)
+                    .team(team_id)
+                    .target("team", team_id)
+                    .client(client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TeamsResponse::success(
+                    serde_json::to_value(team).unwrap(),
+                    Some(message.to_string()),
                 ))
                 .unwrap(),
-            );
+            )
         }
-    };
+        Err(err) => {
+            println!("Error cancelling ownership offer: {:?}", err);
+            ownership_error(err)
+        }
+    }
+}
 
-    let invitation_response = TeamsResponse::success(
-        serde_json::to_value(invitation).unwrap(),
-        Some("Invitation created successfully".to_string()),
-    );
+#[delete("/<team_id>/transfer")]
+pub async fn withdraw_team_transfer(
+    access: Result<TeamAccess<TeamTransfer>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+) -> status::Custom<Value> {
+    match access {
+        Ok(access) => {
+            cancel_team_transfer(
+                &team_id,
+                access.user_id(),
+                &client,
+                "Team ownership offer withdrawn",
+            )
+            .await
+        }
+        Err(err) => access_error(err),
+    }
+}
 
-    status::Custom(
-        Status::Created,
-        serde_json::to_value(invitation_response).unwrap(),
-    )
+#[post("/<team_id>/transfer/decline")]
+pub async fn decline_team_transfer(
+    access: Result<TeamAccess<TeamTakeOwnership>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+) -> status::Custom<Value> {
+    match access {
+        Ok(access) => {
+            cancel_team_transfer(
+                &team_id,
+                access.user_id(),
+                &client,
+                "Team ownership offer declined",
+            )
+            .await
+        }
+        Err(err) => access_error(err),
+    }
 }
 
-#[get("/<team_id>/activities")]
-pub async fn get_team_activities(token: RawToken, team_id: &str) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
+/// Completes a transfer offered to the caller. The previous owner stays on
+/// as an admin.
+#[post("/<team_id>/transfer/accept")]
+pub async fn accept_team_transfer(
+    access: Result<TeamAccess<TeamTakeOwnership>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match accept_team_ownership(&team_id, access.user_id()).await {
+        Ok((team, previous_owner_id)) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TeamOwnershipTransferred)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("team", &team_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "previousOwnerId": previous_owner_id,
+                        "newOwnerId": access.user_id(),
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TeamsResponse::success(
+                    serde_json::to_value(team).unwrap(),
+                    Some("Team ownership transferred".to_string()),
                 ))
                 .unwrap(),
-            );
+            )
         }
-    };
+        Err(err) => {
+            println!("Error accepting team ownership: {:?}", err);
+            ownership_error(err)
+        }
+    }
+}
+
+/// Security events on a team, newest first. Only the owner and team admins
+/// can see them.
+#[get("/<team_id>/audit?<limit>&<before>")]
+pub async fn get_team_audit_events(
+    access: Result<TeamAccess<TeamAudit>, TeamAccessError>,
+    team_id: String,
+    limit: Option<i64>,
+    before: Option<String>,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    match team_audit_events(&team_id, limit, before.as_deref()).await {
+        Ok(events) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(TeamsResponse::success(
+                serde_json::to_value(events).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
+
+#[get("/<team_id>/activities")]
+pub async fn get_team_activities(
+    access: Result<TeamAccess<ActivityRead>, TeamAccessError>,
+    team_id: &str,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
     let team_id_param = vec![("team_id", DatabaseValue::String(team_id.to_string()))];
     match find_all_unarchived_resources_where_fields!(Activity, team_id_param).await {
@@ -682,32 +1188,41 @@ pub async fn get_team_activities(token: RawToken, team_id: &str) -> status::Cust
     }
 }
 
-#[get("/<team_id>/activities/<activity_id>")]
-pub async fn get_team_activity(
-    token: RawToken,
+/// Loads an activity that belongs to the team, answering 404 otherwise.
+async fn find_team_activity(
     team_id: &str,
     activity_id: &str,
-) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
+) -> Result<Activity, status::Custom<Value>> {
     let activity_id_param = vec![
         ("id", DatabaseValue::String(activity_id.to_string())),
         ("team_id", DatabaseValue::String(team_id.to_string())),
     ];
-    match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
+    find_one_unarchived_resource_where_fields!(Activity, activity_id_param)
+        .await
+        .map_err(|err| {
+            println!("Error finding activity: {:?}", err);
+            status::Custom(
+                Status::NotFound,
+                serde_json::to_value(TeamsResponse::error(
+                    ActivityError::ActivityNotFound,
+                    ActivityError::ActivityNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        })
+}
+
+#[get("/<team_id>/activities/<activity_id>")]
+pub async fn get_team_activity(
+    access: Result<TeamAccess<ActivityRead>, TeamAccessError>,
+    team_id: &str,
+    activity_id: &str,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    match find_team_activity(team_id, activity_id).await {
         Ok(activity) => status::Custom(
             Status::Ok,
             serde_json::to_value(TeamsResponse::success(
@@ -716,17 +1231,7 @@ pub async fn get_team_activity(
             ))
             .unwrap(),
         ),
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
+        Err(response) => response,
     }
 }
 
@@ -738,24 +1243,13 @@ pub struct CreateTeamActivityRequest {
 
 #[post("/<team_id>/activities", data = "<activity_data>")]
 pub async fn create_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityCreate>, TeamAccessError>,
     team_id: &str,
     activity_data: Json<CreateTeamActivityRequest>,
 ) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
     let activity_params = vec![
         ("team_id", DatabaseValue::String(team_id.to_string())),
@@ -803,50 +1297,40 @@ pub struct UpdateTeamActivityRequest {
 
 #[put("/<team_id>/activities/<activity_id>", data = "<activity_data>")]
 pub async fn update_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityUpdate>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
     activity_data: Json<UpdateTeamActivityRequest>,
 ) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let team_id_param = vec![("id", DatabaseValue::String(team_id.to_string()))];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
+    let activity = match find_team_activity(team_id, activity_id).await {
+        Ok(activity) => activity,
+        Err(response) => return response,
     };
 
     let activity_params = vec![
         (
             "activity_name",
-            DatabaseValue::String(activity_data.activity_name.clone().unwrap()),
+            DatabaseValue::String(
+                activity_data
+                    .activity_name
+                    .clone()
+                    .or(activity.activity_name)
+                    .unwrap_or_default(),
+            ),
         ),
         (
             "activity_description",
-            DatabaseValue::String(activity_data.activity_description.clone().unwrap()),
+            DatabaseValue::String(
+                activity_data
+                    .activity_description
+                    .clone()
+                    .or(activity.activity_description)
+                    .unwrap_or_default(),
+            ),
         ),
     ];
 
@@ -876,24 +1360,13 @@ pub async fn update_team_activity(
 
 #[delete("/<team_id>/activities/<activity_id>")]
 pub async fn delete_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityDelete>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
 ) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
     let activity_id_param = vec![
         ("id", activity_id.to_string()),
@@ -929,120 +1402,29 @@ pub struct AssignTeamActivityRequest {
 
 #[post("/<team_id>/activities/<activity_id>/assign", data = "<assign_data>")]
 pub async fn assign_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityAssign>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
     assign_data: Json<AssignTeamActivityRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let team_id_param = vec![
-        ("id", DatabaseValue::String(team_id.to_string())),
-        (
-            "user_id",
-            DatabaseValue::String(token_value.user_id.clone()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let activity_id_param = vec![
-        ("id", DatabaseValue::String(activity_id.to_string())),
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
-        Ok(activity) => activity,
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let user_id_param = vec![("id", DatabaseValue::String(assign_data.user_id.clone()))];
-    let _ = match find_one_unarchived_resource_where_fields!(User, user_id_param).await {
-        Ok(user) => user,
-        Err(err) => {
-            println!("Error finding user: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![("id", DatabaseValue::String(team_id.to_string()))];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(response) = find_team_activity(team_id, activity_id).await {
+        return response;
+    }
 
-    let team_activity_params = vec![
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-        (
-            "activity_id",
-            DatabaseValue::String(activity_id.to_string()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, team_activity_params).await {
-        Ok(team_activity) => team_activity,
-        Err(err) => {
-            println!("Error finding team activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if !is_team_member(team_id, &assign_data.user_id).await {
+        return status::Custom(
+            Status::UnprocessableEntity,
+            serde_json::to_value(TeamsResponse::error(
+                ActivityError::AssigneeNotTeamMember,
+                ActivityError::AssigneeNotTeamMember.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
 
     let assignment_params = vec![(
         "assigned_to",
@@ -1062,8 +1444,8 @@ pub async fn assign_team_activity(
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    ActivityError::ActivityUpdateFailed,
+                    ActivityError::ActivityUpdateFailed.to_string(),
                 ))
                 .unwrap(),
             );
@@ -1073,65 +1455,17 @@ pub async fn assign_team_activity(
 
 #[post("/<team_id>/activities/<activity_id>/unassign")]
 pub async fn unassign_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityAssign>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![
-        ("id", DatabaseValue::String(team_id.to_string())),
-        (
-            "user_id",
-            DatabaseValue::String(token_value.user_id.clone()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let activity_id_param = vec![
-        ("id", DatabaseValue::String(activity_id.to_string())),
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
-        Ok(activity) => activity,
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(response) = find_team_activity(team_id, activity_id).await {
+        return response;
+    }
 
     let unassign_params = vec![("assigned_to", DatabaseValue::None)];
     let activity_id = activity_id.to_string();
@@ -1148,8 +1482,8 @@ pub async fn unassign_team_activity(
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    ActivityError::ActivityUpdateFailed,
+                    ActivityError::ActivityUpdateFailed.to_string(),
                 ))
                 .unwrap(),
             );
@@ -1159,65 +1493,17 @@ pub async fn unassign_team_activity(
 
 #[post("/<team_id>/activities/<activity_id>/pause")]
 pub async fn pause_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityUpdate>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![
-        ("id", DatabaseValue::String(team_id.to_string())),
-        (
-            "user_id",
-            DatabaseValue::String(token_value.user_id.clone()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let activity_id_param = vec![
-        ("id", DatabaseValue::String(activity_id.to_string())),
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
-        Ok(activity) => activity,
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(response) = find_team_activity(team_id, activity_id).await {
+        return response;
+    }
 
     let pause_params = vec![(
         "paused_at",
@@ -1237,8 +1523,8 @@ pub async fn pause_team_activity(
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    ActivityError::ActivityUpdateFailed,
+                    ActivityError::ActivityUpdateFailed.to_string(),
                 ))
                 .unwrap(),
             );
@@ -1248,65 +1534,17 @@ pub async fn pause_team_activity(
 
 #[post("/<team_id>/activities/<activity_id>/resume")]
 pub async fn resume_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityUpdate>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![
-        ("id", DatabaseValue::String(team_id.to_string())),
-        (
-            "user_id",
-            DatabaseValue::String(token_value.user_id.clone()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let activity_id_param = vec![
-        ("id", DatabaseValue::String(activity_id.to_string())),
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
-        Ok(activity) => activity,
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(response) = find_team_activity(team_id, activity_id).await {
+        return response;
+    }
 
     let resume_params = vec![("paused_at", DatabaseValue::None)];
     let activity_id = activity_id.to_string();
@@ -1323,8 +1561,8 @@ pub async fn resume_team_activity(
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    ActivityError::ActivityUpdateFailed,
+                    ActivityError::ActivityUpdateFailed.to_string(),
                 ))
                 .unwrap(),
             );
@@ -1334,67 +1572,19 @@ pub async fn resume_team_activity(
 
 #[post("/<team_id>/activities/<activity_id>/reopen")]
 pub async fn reopen_team_activity(
-    token: RawToken,
+    access: Result<TeamAccess<ActivityUpdate>, TeamAccessError>,
     team_id: &str,
     activity_id: &str,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
-
-    let team_id_param = vec![
-        ("id", DatabaseValue::String(team_id.to_string())),
-        (
-            "user_id",
-            DatabaseValue::String(token_value.user_id.clone()),
-        ),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let activity_id_param = vec![
-        ("id", DatabaseValue::String(activity_id.to_string())),
-        ("team_id", DatabaseValue::String(team_id.to_string())),
-    ];
-    let _ = match find_one_unarchived_resource_where_fields!(Activity, activity_id_param).await {
-        Ok(activity) => activity,
-        Err(err) => {
-            println!("Error finding activity: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+    if let Err(response) = find_team_activity(team_id, activity_id).await {
+        return response;
+    }
 
-    let reopen_params = vec![("completed_at", DatabaseValue::None)];
+    let reopen_params = vec![("ended_at", DatabaseValue::None)];
     let activity_id = activity_id.to_string();
     match update_resource!(Activity, activity_id, reopen_params).await {
         Ok(activity) => {
@@ -1409,8 +1599,8 @@ pub async fn reopen_team_activity(
             return status::Custom(
                 Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+                    ActivityError::ActivityUpdateFailed,
+                    ActivityError::ActivityUpdateFailed.to_string(),
                 ))
                 .unwrap(),
             );
@@ -0,0 +1,393 @@
+use crate::api::client::ClientInfo;
+use crate::api::token::{validate_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::AuthenticationError;
+use crate::models::totp_secret::TotpSecret;
+use crate::models::user::{User, UserError};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use crate::utils::totp::{generate_secret, otpauth_uri, verify_code, verify_second_factor};
+use crate::{
+    delete_resource_where_fields, find_one_unarchived_resource_where_fields, insert_resource,
+    update_resource,
+};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::{Json, Value};
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Iso8601;
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    User(UserError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<UserError> for ResponseError {
+    fn from(error: UserError) -> Self {
+        ResponseError::User(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl TwoFactorResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub pending_confirmation: bool,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub confirmed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: Option<String>,
+    pub backup_code: Option<String>,
+}
+
+#[get("/")]
+pub async fn get_two_factor(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let secret_params = [("user_id", &token_value.user_id)];
+    let totp_secret = find_one_unarchived_resource_where_fields!(TotpSecret, secret_params)
+        .await
+        .ok();
+    let two_factor_status = TwoFactorStatus {
+        enabled: totp_secret
+            .as_ref()
+            .is_some_and(|totp_secret| totp_secret.is_confirmed()),
+        pending_confirmation: totp_secret
+            .as_ref()
+            .is_some_and(|totp_secret| !totp_secret.is_confirmed()),
+        confirmed_at: totp_secret.and_then(|totp_secret| totp_secret.confirmed_at),
+    };
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(TwoFactorResponse::success(
+            serde_json::to_value(two_factor_status).unwrap(),
+            None,
+        ))
+        .unwrap(),
+    )
+}
+
+#[post("/")]
+pub async fn enroll_two_factor(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let user_id = DatabaseValue::String(token_value.user_id.clone());
+    let find_user_params = [("id", &user_id)];
+    let user = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
+        Ok(user) => user,
+        Err(err) => {
+            println!("Error finding user: {:?}", err);
+            return status::Custom(
+                Status::NotFound,
+                serde_json::to_value(TwoFactorResponse::error(
+                    UserError::UserNotFound,
+                    UserError::UserNotFound.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let secret_params = [("user_id", &user_id)];
+    if let Ok(totp_secret) =
+        find_one_unarchived_resource_where_fields!(TotpSecret, secret_params).await
+    {
+        if totp_secret.is_confirmed() {
+            return status::Custom(
+                Status::Conflict,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::TwoFactorAlreadyEnabled,
+                    AuthenticationError::TwoFactorAlreadyEnabled.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    }
+
+    let unconfirmed_params = vec![("user_id", token_value.user_id.clone())];
+    if let Err(err) = delete_resource_where_fields!(TotpSecret, unconfirmed_params).await {
+        println!("Error archiving unconfirmed TOTP secrets: {:?}", err);
+    }
+
+    let secret = generate_secret();
+    let totp_params = vec![
+        ("user_id", user_id.clone()),
+        ("secret", DatabaseValue::String(secret.clone())),
+    ];
+    match insert_resource!(TotpSecret, totp_params).await {
+        Ok(_) => status::Custom(
+            Status::Created,
+            serde_json::to_value(TwoFactorResponse::success(
+                serde_json::to_value(TwoFactorEnrollment {
+                    otpauth_uri: otpauth_uri(&secret, &user.username.unwrap_or_default()),
+                    secret,
+                })
+                .unwrap(),
+                Some("Confirm enrollment with a code from your authenticator".to_string()),
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error creating TOTP secret: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(TwoFactorResponse::error(
+                    UserError::UserUpdateFailed,
+                    UserError::UserUpdateFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+#[post("/confirm", data = "<code_request>")]
+pub async fn confirm_two_factor(
+    token: RawToken,
+    client: ClientInfo,
+    code_request: Json<TwoFactorCodeRequest>,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let secret_params = [("user_id", &token_value.user_id)];
+    let totp_secret =
+        match find_one_unarchived_resource_where_fields!(TotpSecret, secret_params).await {
+            Ok(totp_secret) if !totp_secret.is_confirmed() => totp_secret,
+            Ok(_) => {
+                return status::Custom(
+                    Status::Conflict,
+                    serde_json::to_value(TwoFactorResponse::error(
+                        AuthenticationError::TwoFactorAlreadyEnabled,
+                        AuthenticationError::TwoFactorAlreadyEnabled.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
+            Err(err) => {
+                println!("Error finding TOTP secret: {:?}", err);
+                return status::Custom(
+                    Status::NotFound,
+                    serde_json::to_value(TwoFactorResponse::error(
+                        AuthenticationError::TwoFactorNotEnabled,
+                        AuthenticationError::TwoFactorNotEnabled.to_string(),
+                    ))
+                    .unwrap(),
+                );
+            }
+        };
+
+    let code = code_request.code.clone().unwrap_or_default();
+    let step = match verify_code(
+        &totp_secret.secret.clone().unwrap_or_default(),
+        &code,
+        totp_secret.last_used_step,
+    ) {
+        Some(step) => step,
+        None => {
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::InvalidSecondFactor,
+                    AuthenticationError::InvalidSecondFactor.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    let confirm_params = vec![
+        (
+            "confirmed_at",
+            DatabaseValue::DateTime(OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap()),
+        ),
+        ("last_used_step", DatabaseValue::Int64(step.to_string())),
+    ];
+    let totp_secret_id = totp_secret.id.unwrap();
+    match update_resource!(TotpSecret, totp_secret_id, confirm_params).await {
+        Ok(totp_secret) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TwoFactorEnabled)
+                    .user(&token_value.user_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TwoFactorResponse::success(
+                    serde_json::to_value(totp_secret).unwrap(),
+                    Some("Two-factor authentication enabled".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error confirming TOTP secret: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(TwoFactorResponse::error(
+                    UserError::UserUpdateFailed,
+                    UserError::UserUpdateFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
+
+#[post("/disable", data = "<code_request>")]
+pub async fn disable_two_factor(
+    token: RawToken,
+    client: ClientInfo,
+    code_request: Json<TwoFactorCodeRequest>,
+) -> status::Custom<Value> {
+    let token_value = match validate_token(token).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                Status::Unauthorized,
+                serde_json::to_value(TwoFactorResponse::error(
+                    AuthenticationError::InvalidToken,
+                    AuthenticationError::InvalidToken.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+
+    if let Err(err) = verify_second_factor(
+        &token_value.user_id,
+        code_request.code.as_deref(),
+        code_request.backup_code.as_deref(),
+    )
+    .await
+    {
+        println!("Error verifying second factor: {:?}", err);
+        let status = match err {
+            AuthenticationError::TwoFactorNotEnabled => Status::NotFound,
+            _ => Status::Unauthorized,
+        };
+        return status::Custom(
+            status,
+            serde_json::to_value(TwoFactorResponse::error(err.clone(), err.to_string())).unwrap(),
+        );
+    }
+
+    let secret_params = vec![("user_id", token_value.user_id.clone())];
+    match delete_resource_where_fields!(TotpSecret, secret_params).await {
+        Ok(_) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::TwoFactorDisabled)
+                    .user(&token_value.user_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(TwoFactorResponse::success(
+                    serde_json::json!(null),
+                    Some("Two-factor authentication disabled".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error archiving TOTP secrets: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(TwoFactorResponse::error(
+                    UserError::UserUpdateFailed,
+                    UserError::UserUpdateFailed.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
+    }
+}
@@ -1,10 +1,16 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::authentications::ThrottledResponse;
+use crate::api::client::ClientInfo;
+use crate::api::token::{token_error_status, validate_scoped_token, validate_token, RawToken};
 use crate::database::values::DatabaseValue;
 use crate::find_one_unarchived_resource_where_fields;
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::user::{User, UserError};
 use crate::update_resource;
-use crate::utils::passwords::hash_password;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::passwords::{hash_password, verify_password};
+use crate::utils::sessions::revoke_user_sessions;
+use crate::utils::throttle::{check_throttle, clear_failures, login_keys, record_failure};
 use rocket::get;
 use rocket::http::Status;
 use rocket::response::status;
@@ -56,17 +62,13 @@ impl UserResponse {
 
 #[get("/")]
 pub async fn get_user(token: RawToken) -> rocket::response::status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ProfileRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
@@ -96,22 +98,28 @@ pub async fn get_user(token: RawToken) -> rocket::response::status::Custom<Value
     }
 }
 
+/// The profile fields a user can change on their own account.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserRequest {
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+    pub email: Option<String>,
+}
+
 #[put("/", data = "<user>")]
 pub async fn update_user(
     token: RawToken,
-    user: Json<User>,
+    user: Json<UpdateUserRequest>,
 ) -> rocket::response::status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::ProfileWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
@@ -134,7 +142,7 @@ pub async fn update_user(
         }
     };
 
-    let user_params = vec![
+    let mut user_params = vec![
         (
             "first_name",
             DatabaseValue::String(user.0.first_name.unwrap_or_default()),
@@ -148,6 +156,9 @@ pub async fn update_user(
             DatabaseValue::String(user.0.username.unwrap_or_default()),
         ),
     ];
+    if let Some(email) = user.0.email {
+        user_params.push(("email", DatabaseValue::String(email)));
+    }
 
     let user_id = token_value.user_id.clone();
     match update_resource!(User, user_id, user_params).await {
@@ -180,8 +191,9 @@ pub struct UserChangePasswordRequest {
 #[post("/change-password", data = "<user_change_password_request>")]
 pub async fn change_password(
     token: RawToken,
+    client: ClientInfo,
     user_change_password_request: Json<UserChangePasswordRequest>,
-) -> rocket::response::status::Custom<Value> {
+) -> ThrottledResponse {
     let token_value = match validate_token(token).await {
         Ok(token) => token,
         Err(err) => {
@@ -193,20 +205,16 @@ pub async fn change_password(
                     AuthenticationError::InvalidToken.to_string(),
                 ))
                 .unwrap(),
-            );
+            )
+            .into();
         }
     };
 
     let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let hashed_original_password = hash_password(&user_change_password_request.old_password);
 
     let user_id_param = ("id", &user_id);
-    let password_param = (
-        "password_hash",
-        &DatabaseValue::String(hashed_original_password),
-    );
-    let find_user_params = vec![user_id_param, password_param];
-    let _ = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
+    let find_user_params = [user_id_param];
+    let user = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
         Ok(user) => user,
         Err(err) => {
             println!("Error finding user: {:?}", err);
@@ -217,22 +225,72 @@ pub async fn change_password(
                     UserError::UserNotFound.to_string(),
                 ))
                 .unwrap(),
-            );
+            )
+            .into();
         }
     };
 
-    let hashed_new_password = hash_password(&user_change_password_request.new_password);
+    let throttle_keys = login_keys(&user.username.clone().unwrap_or_default(), &client);
+    if let Err(throttled) = check_throttle(&throttle_keys).await {
+        return throttled.into();
+    }
 
-    let user_params = vec![("password_hash", DatabaseValue::String(hashed_new_password))];
-    match update_resource!(User, user_id, user_params).await {
-        Ok(user) => status::Custom(
-            Status::Ok,
-            serde_json::to_value(UserResponse::success(
-                serde_json::to_value(user).unwrap(),
-                Some("Password updated successfully".to_string()),
+    if !verify_password(
+        &user_change_password_request.old_password,
+        &user.password_hash.unwrap_or_default(),
+    )
+    .await
+    .is_valid()
+    {
+        println!("Invalid old password for user: {:?}", user.id);
+        record_failure(&throttle_keys).await;
+        record_audit_event(
+            AuditEntry::new(AuditAction::PasswordChangeFailed)
+                .user(&token_value.user_id)
+                .client(&client),
+        )
+        .await;
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(UserResponse::error(
+                UserError::UserNotFound,
+                UserError::UserNotFound.to_string(),
             ))
             .unwrap(),
-        ),
+        )
+        .into();
+    }
+
+    clear_failures(&throttle_keys[0]).await;
+
+    let hashed_new_password = hash_password(&user_change_password_request.new_password).await;
+
+    let user_params = vec![("password_hash", DatabaseValue::String(hashed_new_password))];
+    match update_resource!(User, user_id, user_params).await {
+        Ok(user) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::PasswordChanged)
+                    .user(&token_value.user_id)
+                    .client(&client),
+            )
+            .await;
+            // Other sessions started with the old password are signed out.
+            if let Err(err) =
+                revoke_user_sessions(&token_value.user_id, Some(&token_value.authentication_id))
+                    .await
+            {
+                println!("Error revoking sessions after password change: {:?}", err);
+            }
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(UserResponse::success(
+                    serde_json::to_value(user).unwrap(),
+                    Some("Password updated successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+            .into()
+        }
         Err(err) => {
             println!("Error updating user: {:?}", err);
             return status::Custom(
@@ -241,7 +299,8 @@ pub async fn change_password(
                     UserError::UserUpdateFailed,
                     UserError::UserUpdateFailed.to_string()
                 )),
-            );
+            )
+            .into();
         }
     }
 }
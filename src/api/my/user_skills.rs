@@ -1,5 +1,6 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::user::{User, UserError};
 use crate::models::user_skill::{UserSkill, UserSkillError};
@@ -65,17 +66,14 @@ impl UserSkillsResponse {
 
 #[get("/")]
 pub async fn get_user_skills(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::SkillsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserSkillsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserSkillsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -134,17 +132,14 @@ pub async fn create_user_skill(
     token: RawToken,
     user_skill: Json<CreateUserSkillRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::SkillsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserSkillsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserSkillsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -212,17 +207,14 @@ pub async fn update_user_skill(
     user_skill_id: &str,
     user_skill: Json<UpdateUserSkillRequest>,
 ) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::SkillsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserSkillsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserSkillsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
@@ -281,17 +273,14 @@ pub async fn update_user_skill(
 
 #[delete("/<user_skill_id>")]
 pub async fn delete_user_skill(token: RawToken, user_skill_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::SkillsWrite).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UserSkillsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UserSkillsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
             );
         }
     };
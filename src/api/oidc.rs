@@ -0,0 +1,235 @@
+use crate::api::authentications::start_session;
+use crate::api::client::ClientInfo;
+use crate::find_one_unarchived_resource_where_fields;
+use crate::models::authentication::AuthenticationError;
+use crate::models::totp_secret::TotpSecret;
+use crate::models::user::{User, UserError};
+use crate::models::user_identity::OidcError;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::oidc::{
+    begin_oidc_login, consume_oidc_login, exchange_code, find_identity, link_identity,
+    provision_user,
+};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    User(UserError),
+    Oidc(OidcError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<UserError> for ResponseError {
+    fn from(error: UserError) -> Self {
+        ResponseError::User(error)
+    }
+}
+
+impl From<OidcError> for ResponseError {
+    fn from(error: OidcError) -> Self {
+        ResponseError::Oidc(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl OidcResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+pub fn oidc_error_status(err: &OidcError) -> Status {
+    match err {
+        OidcError::NotConfigured => Status::NotFound,
+        OidcError::DiscoveryFailed | OidcError::TokenExchangeFailed => Status::BadGateway,
+        OidcError::InvalidState | OidcError::StateExpired => Status::BadRequest,
+        OidcError::InvalidIdToken => Status::Unauthorized,
+        OidcError::IdentityAlreadyLinked => Status::Conflict,
+        OidcError::IdentityNotFound => Status::NotFound,
+        OidcError::IdentityCreationFailed => Status::InternalServerError,
+    }
+}
+
+fn oidc_error(err: OidcError) -> status::Custom<Value> {
+    let message = err.to_string();
+    status::Custom(
+        oidc_error_status(&err),
+        serde_json::to_value(OidcResponse::error(err, message)).unwrap(),
+    )
+}
+
+/// Starts a single sign-on login. The client sends the user to the returned
+/// URL; the provider redirects back to `OIDC_REDIRECT_URL`, and the client
+/// then posts the `code` and `state` it received to the callback.
+#[post("/oidc/authorize")]
+pub async fn oidc_authorize() -> status::Custom<Value> {
+    match begin_oidc_login(None).await {
+        Ok(authorization_url) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(OidcResponse::success(
+                serde_json::json!({ "authorizationUrl": authorization_url }),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => oidc_error(err),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+/// Finishes a sign-on. Logins get a session, creating the user on first
+/// sign-in; sign-ons started from `/api/my/identities` link the identity to
+/// that user instead.
+#[post("/oidc/callback", data = "<callback_request>")]
+pub async fn oidc_callback(
+    client: ClientInfo,
+    callback_request: Json<OidcCallbackRequest>,
+) -> status::Custom<Value> {
+    if callback_request.code.is_empty() || callback_request.state.is_empty() {
+        return status::Custom(
+            Status::BadRequest,
+            serde_json::to_value(OidcResponse::error(
+                AuthenticationError::InvalidRequest,
+                "Missing code or state".to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let oidc_login = match consume_oidc_login(&callback_request.state).await {
+        Ok(oidc_login) => oidc_login,
+        Err(err) => return oidc_error(err),
+    };
+    let claims = match exchange_code(&callback_request.code, &oidc_login).await {
+        Ok(claims) => claims,
+        Err(err) => return oidc_error(err),
+    };
+
+    if let Some(user_id) = &oidc_login.user_id {
+        return match link_identity(user_id, &claims).await {
+            Ok(identity) => {
+                record_audit_event(
+                    AuditEntry::new(AuditAction::IdentityLinked)
+                        .user(user_id)
+                        .target("identity", identity.id.as_deref().unwrap_or_default())
+                        .client(&client)
+                        .details(serde_json::json!({ "issuer": identity.issuer })),
+                )
+                .await;
+                status::Custom(
+                    Status::Ok,
+                    serde_json::to_value(OidcResponse::success(
+                        serde_json::to_value(identity).unwrap(),
+                        Some("Identity linked successfully".to_string()),
+                    ))
+                    .unwrap(),
+                )
+            }
+            Err(err) => oidc_error(err),
+        };
+    }
+
+    let user_id = match find_identity(&claims).await {
+        Some(identity) => identity.user_id.unwrap_or_default(),
+        None => match provision_user(&claims).await {
+            Ok(user) => user.id.unwrap_or_default(),
+            Err(err) => return oidc_error(err),
+        },
+    };
+
+    // A linked identity doesn't get past an archived account.
+    let user_params = [("id", &user_id)];
+    if let Err(err) = find_one_unarchived_resource_where_fields!(User, user_params).await {
+        println!("Error finding user: {:?}", err);
+        record_audit_event(
+            AuditEntry::new(AuditAction::LoginFailed)
+                .user(&user_id)
+                .client(&client)
+                .details(serde_json::json!({
+                    "method": "oidc",
+                    "reason": "archived_user",
+                })),
+        )
+        .await;
+        return status::Custom(
+            Status::NotFound,
+            serde_json::to_value(OidcResponse::error(
+                UserError::UserNotFound,
+                UserError::UserNotFound.to_string(),
+            ))
+            .unwrap(),
+        );
+    }
+
+    let totp_params = [("user_id", &user_id)];
+    let second_factor_pending =
+        match find_one_unarchived_resource_where_fields!(TotpSecret, totp_params).await {
+            Ok(totp_secret) => totp_secret.is_confirmed(),
+            Err(_) => false,
+        };
+
+    match start_session(user_id.clone(), second_factor_pending, &client).await {
+        Ok(authentication) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::LoginSucceeded)
+                    .user(&user_id)
+                    .target("session", &authentication.authentication.id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "method": "oidc",
+                        "secondFactorPending": second_factor_pending,
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(OidcResponse::success(
+                    serde_json::to_value(authentication).unwrap(),
+                    if second_factor_pending {
+                        Some(AuthenticationError::SecondFactorRequired.to_string())
+                    } else {
+                        None
+                    },
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => status::Custom(
+            Status::InternalServerError,
+            serde_json::to_value(OidcResponse::error(err.clone(), err.to_string())).unwrap(),
+        ),
+    }
+}
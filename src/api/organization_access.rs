@@ -0,0 +1,128 @@
+use crate::api::token::VerifiedToken;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
+use crate::find_one_unarchived_resource_where_fields;
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::organization::{Organization, OrganizationAccessError, OrganizationError};
+use crate::models::organization_role::OrganizationRole;
+use crate::models::permission::PermissionError;
+use crate::utils::organizations::organization_role;
+use rocket::{
+    http::{Method, Status},
+    request::{FromRequest, Outcome},
+    Request,
+};
+
+/// Request guard for routes under an `<organization_id>` segment. It
+/// authenticates the caller and loads the organization along with the
+/// caller's role in it. Reads need `organizations:read`, anything else
+/// `organizations:write`. Organizations the caller doesn't belong to answer
+/// 404, as if they didn't exist.
+#[derive(Debug, Clone)]
+pub struct OrganizationAccess {
+    pub token: VerifiedToken,
+    pub organization: Organization,
+    pub role: OrganizationRole,
+}
+
+impl OrganizationAccess {
+    pub fn user_id(&self) -> &str {
+        &self.token.user_id
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role.is_admin()
+    }
+
+    /// Turns away callers who can't manage the organization's members.
+    pub fn require_admin(&self) -> Result<(), OrganizationAccessError> {
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(OrganizationAccessError::Permission(
+                PermissionError::PermissionDenied,
+            ))
+        }
+    }
+}
+
+impl OrganizationAccessError {
+    pub fn status(&self) -> Status {
+        match self {
+            OrganizationAccessError::Authentication(err) => token_error_status(err),
+            OrganizationAccessError::Organization(_) => Status::NotFound,
+            OrganizationAccessError::Permission(_) => Status::Forbidden,
+        }
+    }
+}
+
+fn reject<T>(error: OrganizationAccessError) -> Outcome<T, OrganizationAccessError> {
+    Outcome::Error((error.status(), error))
+}
+
+/// The `<organization_id>` segment of the matched route.
+fn routed_organization_id(request: &Request<'_>) -> Option<String> {
+    let route = request.route()?;
+    let position = route
+        .uri
+        .unmounted_origin
+        .path()
+        .segments()
+        .position(|segment| segment == "<organization_id>")?;
+    request.routed_segment(position).map(str::to_string)
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for OrganizationAccess {
+    type Error = OrganizationAccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let raw_token = match RawToken::from_request(request).await {
+            Outcome::Success(raw_token) => raw_token,
+            _ => {
+                return reject(OrganizationAccessError::Authentication(
+                    AuthenticationError::InvalidToken,
+                ))
+            }
+        };
+        let scope = match request.method() {
+            Method::Get => ApiKeyScope::OrganizationsRead,
+            _ => ApiKeyScope::OrganizationsWrite,
+        };
+        let token = match validate_scoped_token(raw_token, scope).await {
+            Ok(token) => token,
+            Err(err) => {
+                println!("Error validating token: {:?}", err);
+                return reject(OrganizationAccessError::Authentication(err));
+            }
+        };
+
+        let not_found =
+            || OrganizationAccessError::Organization(OrganizationError::OrganizationNotFound);
+        let organization_id = match routed_organization_id(request) {
+            Some(organization_id) => organization_id,
+            None => return reject(not_found()),
+        };
+        let role = match organization_role(&organization_id, &token.user_id).await {
+            Some(role) => role,
+            None => return reject(not_found()),
+        };
+        let organization_params = [("id", &organization_id)];
+        let organization =
+            match find_one_unarchived_resource_where_fields!(Organization, organization_params)
+                .await
+            {
+                Ok(organization) => organization,
+                Err(err) => {
+                    println!("Error finding organization: {:?}", err);
+                    return reject(not_found());
+                }
+            };
+
+        Outcome::Success(OrganizationAccess {
+            token,
+            organization,
+            role,
+        })
+    }
+}
@@ -0,0 +1,555 @@
+use crate::api::client::ClientInfo;
+use crate::api::organization_access::OrganizationAccess;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
+use crate::database::values::DatabaseValue;
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::organization::{Organization, OrganizationAccessError, OrganizationError};
+use crate::models::organization_role::OrganizationRole;
+use crate::models::permission::PermissionError;
+use crate::models::team::Team;
+use crate::models::user::{User, UserError};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::organizations::{
+    add_organization_member as add_member, change_organization_role, create_organization as create,
+    organization_members, organization_role, organization_teams, remove_organization_member,
+    user_organizations,
+};
+use crate::{find_one_unarchived_resource_where_fields, update_resource};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Organization(OrganizationError),
+    Permission(PermissionError),
+    User(UserError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<OrganizationError> for ResponseError {
+    fn from(error: OrganizationError) -> Self {
+        ResponseError::Organization(error)
+    }
+}
+
+impl From<UserError> for ResponseError {
+    fn from(error: UserError) -> Self {
+        ResponseError::User(error)
+    }
+}
+
+impl From<OrganizationAccessError> for ResponseError {
+    fn from(error: OrganizationAccessError) -> Self {
+        match error {
+            OrganizationAccessError::Authentication(error) => ResponseError::Authentication(error),
+            OrganizationAccessError::Organization(error) => ResponseError::Organization(error),
+            OrganizationAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OrganizationsResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl OrganizationsResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+fn access_error(err: OrganizationAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(OrganizationsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+fn organization_error(err: OrganizationError) -> status::Custom<Value> {
+    let status = match err {
+        OrganizationError::OrganizationNotFound | OrganizationError::NotOrganizationMember => {
+            Status::NotFound
+        }
+        OrganizationError::AlreadyOrganizationMember
+        | OrganizationError::LastOrganizationOwner
+        | OrganizationError::MemberOwnsTeams => Status::Conflict,
+        OrganizationError::OrganizationRequired => Status::UnprocessableEntity,
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(OrganizationsResponse::error(err, message)).unwrap(),
+    )
+}
+
+/// Only owners may hand out ownership or act on another owner.
+fn require_owner_for(
+    access: &OrganizationAccess,
+    roles: &[&OrganizationRole],
+) -> Result<(), OrganizationAccessError> {
+    access.require_admin()?;
+    let touches_owner = roles.contains(&&OrganizationRole::Owner);
+    if touches_owner && access.role != OrganizationRole::Owner {
+        return Err(OrganizationAccessError::Permission(
+            PermissionError::PermissionDenied,
+        ));
+    }
+    Ok(())
+}
+
+/// The organizations the caller belongs to.
+#[get("/")]
+pub async fn get_organizations(token: RawToken) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::OrganizationsRead).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(OrganizationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    match user_organizations(&token_value.user_id).await {
+        Ok(organizations) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(OrganizationsResponse::success(
+                serde_json::to_value(organizations).unwrap(),
+                Some("Organizations fetched successfully".to_string()),
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding organizations: {:?}", err);
+            organization_error(OrganizationError::OrganizationNotFound)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationRequest {
+    pub organization_name: String,
+    pub organization_description: Option<String>,
+}
+
+/// Creates an organization owned by the caller.
+#[post("/", data = "<organization_data>")]
+pub async fn create_organization(
+    token: RawToken,
+    client: ClientInfo,
+    organization_data: Json<OrganizationRequest>,
+) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::OrganizationsWrite).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(OrganizationsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    let organization = match create(
+        &organization_data.organization_name,
+        organization_data.organization_description.as_deref(),
+        &token_value.user_id,
+    )
+    .await
+    {
+        Ok(organization) => organization,
+        Err(err) => return organization_error(err),
+    };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::OrganizationCreated)
+            .actor(&token_value.user_id)
+            .target(
+                "organization",
+                organization.id.as_deref().unwrap_or_default(),
+            )
+            .client(&client)
+            .details(serde_json::json!({
+                "organizationName": organization_data.organization_name,
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Created,
+        serde_json::to_value(OrganizationsResponse::success(
+            serde_json::to_value(organization).unwrap(),
+            Some("Organization created successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationResponse {
+    pub organization: Organization,
+    pub organization_role: OrganizationRole,
+    pub teams: Vec<Team>,
+}
+
+#[get("/<organization_id>")]
+pub async fn get_organization(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    organization_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    let teams = match organization_teams(&organization_id).await {
+        Ok(teams) => teams,
+        Err(err) => {
+            println!("Error finding organization teams: {:?}", err);
+            return organization_error(OrganizationError::OrganizationNotFound);
+        }
+    };
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(OrganizationsResponse::success(
+            serde_json::to_value(OrganizationResponse {
+                organization: access.organization,
+                organization_role: access.role,
+                teams,
+            })
+            .unwrap(),
+            Some("Organization fetched successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+/// Renames or redescribes the organization. Owners and admins only.
+#[put("/<organization_id>", data = "<organization_data>")]
+pub async fn update_organization(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    client: ClientInfo,
+    organization_id: String,
+    organization_data: Json<OrganizationRequest>,
+) -> status::Custom<Value> {
+    let access = match access.and_then(|access| access.require_admin().map(|_| access)) {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    let organization_params = vec![
+        (
+            "organization_name",
+            DatabaseValue::String(organization_data.organization_name.clone()),
+        ),
+        (
+            "organization_description",
+            match &organization_data.organization_description {
+                Some(desc) => DatabaseValue::String(desc.clone()),
+                None => DatabaseValue::None,
+            },
+        ),
+    ];
+    match update_resource!(Organization, organization_id, organization_params).await {
+        Ok(organization) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::OrganizationUpdated)
+                    .actor(access.user_id())
+                    .target("organization", &organization_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "organizationName": organization_data.organization_name,
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(OrganizationsResponse::success(
+                    serde_json::to_value(organization).unwrap(),
+                    Some("Organization updated successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error updating organization: {:?}", err);
+            organization_error(OrganizationError::OrganizationUpdateFailed)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationMember {
+    pub user: User,
+    pub organization_role: Option<OrganizationRole>,
+}
+
+#[get("/<organization_id>/members")]
+pub async fn get_members(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    organization_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    match organization_members(&organization_id).await {
+        Ok(members) => {
+            let members = members
+                .into_iter()
+                .map(|(user, membership)| OrganizationMember {
+                    user,
+                    organization_role: membership.organization_role,
+                })
+                .collect::<Vec<OrganizationMember>>();
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(OrganizationsResponse::success(
+                    serde_json::to_value(members).unwrap(),
+                    Some("Members fetched successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error finding organization members: {:?}", err);
+            organization_error(OrganizationError::OrganizationNotFound)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddMemberRequest {
+    pub username: String,
+    pub organization_role: OrganizationRole,
+}
+
+/// Adds an existing user to the organization. Owners and admins only;
+/// making someone an owner takes an owner.
+#[post("/<organization_id>/members", data = "<member_data>")]
+pub async fn add_organization_member(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    client: ClientInfo,
+    organization_id: String,
+    member_data: Json<AddMemberRequest>,
+) -> status::Custom<Value> {
+    let access = match access.and_then(|access| {
+        require_owner_for(&access, &[&member_data.organization_role]).map(|_| access)
+    }) {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    let username = DatabaseValue::String(member_data.username.clone());
+    let user_params = [("username", &username)];
+    let user = match find_one_unarchived_resource_where_fields!(User, user_params).await {
+        Ok(user) => user,
+        Err(err) => {
+            println!("Error finding user: {:?}", err);
+            return status::Custom(
+                Status::NotFound,
+                serde_json::to_value(OrganizationsResponse::error(
+                    UserError::UserNotFound,
+                    UserError::UserNotFound.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
+    let user_id = user.id.clone().unwrap_or_default();
+
+    let membership =
+        match add_member(&organization_id, &user_id, &member_data.organization_role).await {
+            Ok(membership) => membership,
+            Err(err) => return organization_error(err),
+        };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::OrganizationMemberAdded)
+            .actor(access.user_id())
+            .target("organization", &organization_id)
+            .client(&client)
+            .details(serde_json::json!({
+                "userId": user_id,
+                "organizationRole": member_data.organization_role.to_string(),
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Created,
+        serde_json::to_value(OrganizationsResponse::success(
+            serde_json::to_value(membership).unwrap(),
+            Some("Member added successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateMemberRequest {
+    pub organization_role: OrganizationRole,
+}
+
+/// Changes a member's role. Owners and admins only; anything involving the
+/// owner role takes an owner.
+#[put("/<organization_id>/members/<user_id>", data = "<member_data>")]
+pub async fn update_organization_member(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    client: ClientInfo,
+    organization_id: String,
+    user_id: String,
+    member_data: Json<UpdateMemberRequest>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+    let current_role = match organization_role(&organization_id, &user_id).await {
+        Some(role) => role,
+        None => return organization_error(OrganizationError::NotOrganizationMember),
+    };
+    if let Err(err) = require_owner_for(&access, &[&current_role, &member_data.organization_role]) {
+        return access_error(err);
+    }
+
+    let membership =
+        match change_organization_role(&organization_id, &user_id, &member_data.organization_role)
+            .await
+        {
+            Ok(membership) => membership,
+            Err(err) => return organization_error(err),
+        };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::OrganizationMemberRoleChanged)
+            .actor(access.user_id())
+            .target("organization", &organization_id)
+            .client(&client)
+            .details(serde_json::json!({
+                "userId": user_id,
+                "previousRole": current_role.to_string(),
+                "organizationRole": member_data.organization_role.to_string(),
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(OrganizationsResponse::success(
+            serde_json::to_value(membership).unwrap(),
+            Some("Member updated successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+/// Removes a member, or lets the caller leave. Their memberships of the
+/// organization's teams go with it.
+#[delete("/<organization_id>/members/<user_id>")]
+pub async fn remove_member(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    client: ClientInfo,
+    organization_id: String,
+    user_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+    let current_role = match organization_role(&organization_id, &user_id).await {
+        Some(role) => role,
+        None => return organization_error(OrganizationError::NotOrganizationMember),
+    };
+    if access.user_id() != user_id {
+        if let Err(err) = require_owner_for(&access, &[&current_role]) {
+            return access_error(err);
+        }
+    }
+
+    let membership = match remove_organization_member(&organization_id, &user_id).await {
+        Ok(membership) => membership,
+        Err(err) => return organization_error(err),
+    };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::OrganizationMemberRemoved)
+            .actor(access.user_id())
+            .target("organization", &organization_id)
+            .client(&client)
+            .details(serde_json::json!({
+                "userId": user_id,
+                "organizationRole": current_role.to_string(),
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(OrganizationsResponse::success(
+            serde_json::to_value(membership).unwrap(),
+            Some("Member removed successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[get("/<organization_id>/teams")]
+pub async fn get_organization_teams(
+    access: Result<OrganizationAccess, OrganizationAccessError>,
+    organization_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    match organization_teams(&organization_id).await {
+        Ok(teams) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(OrganizationsResponse::success(
+                serde_json::to_value(teams).unwrap(),
+                Some("Teams fetched successfully".to_string()),
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding organization teams: {:?}", err);
+            organization_error(OrganizationError::OrganizationNotFound)
+        }
+    }
+}
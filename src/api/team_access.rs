@@ -0,0 +1,142 @@
+use crate::api::token::VerifiedToken;
+use crate::api::token::{token_error_status, validate_scoped_token, validate_token, RawToken};
+use crate::find_one_resource_where_fields;
+use crate::models::authentication::AuthenticationError;
+use crate::models::permission::{Permission, PermissionError, TeamAccessError, TeamPermission};
+use crate::models::team::{Team, TeamError};
+use crate::models::team_role::TeamRole;
+use crate::utils::team_hierarchy::effective_team_role;
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    Request,
+};
+use std::marker::PhantomData;
+
+/// Request guard for routes under a `<team_id>` segment. It authenticates
+/// the caller, loads the team and checks that the caller's role grants `P`.
+/// Roles on parent teams count wherever the team inherits permissions.
+/// Take it as `Result<TeamAccess<P>, TeamAccessError>` to answer failures
+/// with the usual JSON body: 401 for a bad token, 404 for an unknown team,
+/// 409 for changes to an archived team and 403 for anyone the team doesn't
+/// let do `P`.
+#[derive(Debug, Clone)]
+pub struct TeamAccess<P: TeamPermission> {
+    pub token: VerifiedToken,
+    pub team: Team,
+    pub is_owner: bool,
+    pub role: Option<TeamRole>,
+    permission: PhantomData<P>,
+}
+
+impl<P: TeamPermission> TeamAccess<P> {
+    pub fn user_id(&self) -> &str {
+        &self.token.user_id
+    }
+
+    /// Whether the caller could also do `permission` on this team.
+    pub fn can(&self, permission: Permission) -> bool {
+        self.is_owner
+            || self
+                .role
+                .as_ref()
+                .is_some_and(|role| permission.allows(role))
+    }
+}
+
+impl TeamAccessError {
+    pub fn status(&self) -> Status {
+        match self {
+            TeamAccessError::Authentication(err) => token_error_status(err),
+            TeamAccessError::Team(TeamError::TeamArchived) => Status::Conflict,
+            TeamAccessError::Team(_) => Status::NotFound,
+            TeamAccessError::Permission(_) => Status::Forbidden,
+        }
+    }
+}
+
+fn reject<T>(error: TeamAccessError) -> Outcome<T, TeamAccessError> {
+    Outcome::Error((error.status(), error))
+}
+
+/// The `<team_id>` segment of the matched route. Routes are mounted at
+/// different depths, so find it by name rather than by position.
+fn routed_team_id(request: &Request<'_>) -> Option<String> {
+    let route = request.route()?;
+    let position = route
+        .uri
+        .unmounted_origin
+        .path()
+        .segments()
+        .position(|segment| segment == "<team_id>")?;
+    request.routed_segment(position).map(str::to_string)
+}
+
+#[rocket::async_trait]
+impl<'r, P: TeamPermission> FromRequest<'r> for TeamAccess<P> {
+    type Error = TeamAccessError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let permission = P::PERMISSION;
+        let raw_token = match RawToken::from_request(request).await {
+            Outcome::Success(raw_token) => raw_token,
+            _ => {
+                return reject(TeamAccessError::Authentication(
+                    AuthenticationError::InvalidToken,
+                ))
+            }
+        };
+        let token = match permission.scope() {
+            Some(scope) => validate_scoped_token(raw_token, scope).await,
+            None => validate_token(raw_token).await,
+        };
+        let token = match token {
+            Ok(token) => token,
+            Err(err) => {
+                println!("Error validating token: {:?}", err);
+                return reject(TeamAccessError::Authentication(err));
+            }
+        };
+
+        let team_id = match routed_team_id(request) {
+            Some(team_id) => team_id,
+            None => return reject(TeamAccessError::Team(TeamError::TeamNotFound)),
+        };
+        let team_params = [("id", &team_id)];
+        let team = match find_one_resource_where_fields!(Team, team_params).await {
+            Ok(team) => team,
+            Err(err) => {
+                println!("Error finding team: {:?}", err);
+                return reject(TeamAccessError::Team(TeamError::TeamNotFound));
+            }
+        };
+
+        let is_owner = team.owner_id.as_deref() == Some(token.user_id.as_str());
+        let role = effective_team_role(&team_id, &token.user_id).await;
+        let access = TeamAccess {
+            token,
+            team,
+            is_owner,
+            role,
+            permission: PhantomData,
+        };
+        if !access.is_owner && access.role.is_none() {
+            return reject(TeamAccessError::Permission(PermissionError::NotTeamMember));
+        }
+        if access.team.archived_at.is_some() && !permission.applies_to_archived() {
+            return reject(TeamAccessError::Team(TeamError::TeamArchived));
+        }
+        if !access.can(permission) {
+            println!(
+                "User {} lacks {} on team {}",
+                access.user_id(),
+                permission.as_str(),
+                team_id
+            );
+            return reject(TeamAccessError::Permission(
+                PermissionError::PermissionDenied,
+            ));
+        }
+        Outcome::Success(access)
+    }
+}
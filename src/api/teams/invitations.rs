@@ -1,13 +1,10 @@
-use crate::api::token::{validate_token, RawToken};
-use crate::database::values::DatabaseValue;
+use crate::api::team_access::TeamAccess;
+use crate::find_all_unarchived_resources_where_fields;
 use crate::models::authentication::AuthenticationError;
 use crate::models::invitation::{Invitation, InvitationError};
-use crate::models::team::{Team, TeamError};
-use crate::models::user::{User, UserError};
-use crate::{
-    find_all_unarchived_resources_where_fields, find_one_unarchived_resource_where_fields,
-    join_all_resources_where_fields_on,
-};
+use crate::models::permission::{PermissionError, TeamAccessError, TeamRead};
+use crate::models::team::TeamError;
+use crate::models::user::UserError;
 use rocket::http::Status;
 use rocket::response::status;
 use serde::{Deserialize, Serialize};
@@ -19,6 +16,7 @@ pub enum ResponseError {
     Invitation(InvitationError),
     Team(TeamError),
     User(UserError),
+    Permission(PermissionError),
 }
 
 impl From<AuthenticationError> for ResponseError {
@@ -45,6 +43,16 @@ impl From<UserError> for ResponseError {
     }
 }
 
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InvitationsResponse {
     pub error: Option<ResponseError>,
@@ -70,39 +78,21 @@ impl InvitationsResponse {
     }
 }
 
-#[get("/teams/<team_id>/invitations")]
-pub async fn get_invitations(token: RawToken, team_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(InvitationsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(InvitationsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
 
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let find_user_params = vec![("id", &user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
-        Ok(user) => user,
-        Err(err) => {
-            println!("Error finding user: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(InvitationsResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+#[get("/teams/<team_id>/invitations")]
+pub async fn get_invitations(
+    access: Result<TeamAccess<TeamRead>, TeamAccessError>,
+    team_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
     let invitations_params = vec![("team_id", &team_id)];
     let invitations =
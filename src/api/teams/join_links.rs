@@ -0,0 +1,259 @@
+use crate::api::client::ClientInfo;
+use crate::api::team_access::TeamAccess;
+use crate::models::authentication::AuthenticationError;
+use crate::models::join_link::{JoinLink, JoinLinkError};
+use crate::models::permission::{JoinLinkManage, Permission, PermissionError, TeamAccessError};
+use crate::models::team::TeamError;
+use crate::models::team_role::TeamRole;
+use crate::models::user::User;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::join_links::{
+    create_join_link as create_team_join_link, join_link_members,
+    revoke_join_link as revoke_team_join_link, team_join_links,
+};
+use crate::utils::time::deserialize_offset_date_time;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Team(TeamError),
+    Permission(PermissionError),
+    JoinLink(JoinLinkError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<TeamError> for ResponseError {
+    fn from(error: TeamError) -> Self {
+        ResponseError::Team(error)
+    }
+}
+
+impl From<JoinLinkError> for ResponseError {
+    fn from(error: JoinLinkError) -> Self {
+        ResponseError::JoinLink(error)
+    }
+}
+
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinLinksResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl JoinLinksResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(JoinLinksResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+fn join_link_error(err: JoinLinkError) -> status::Custom<Value> {
+    let status = match err {
+        JoinLinkError::JoinLinkNotFound => Status::NotFound,
+        JoinLinkError::InvalidMaxUses | JoinLinkError::InvalidExpiry => Status::BadRequest,
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(JoinLinksResponse::error(err, message)).unwrap(),
+    )
+}
+
+/// A join link along with everyone who joined the team through it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinLinkResponse {
+    #[serde(flatten)]
+    pub join_link: JoinLink,
+    pub members: Vec<User>,
+}
+
+#[get("/<team_id>/join-links")]
+pub async fn get_join_links(
+    access: Result<TeamAccess<JoinLinkManage>, TeamAccessError>,
+    team_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    let join_links = match team_join_links(&team_id).await {
+        Ok(join_links) => join_links,
+        Err(err) => {
+            println!("Error finding join links: {:?}", err);
+            return join_link_error(JoinLinkError::JoinLinkNotFound);
+        }
+    };
+
+    let mut join_links_response = Vec::with_capacity(join_links.len());
+    for join_link in join_links {
+        let members = match join_link_members(join_link.id.as_deref().unwrap_or_default()).await {
+            Ok(members) => members,
+            Err(err) => {
+                println!("Error finding join link members: {:?}", err);
+                Vec::new()
+            }
+        };
+        join_links_response.push(JoinLinkResponse { join_link, members });
+    }
+
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(JoinLinksResponse::success(
+            serde_json::to_value(join_links_response).unwrap(),
+            None,
+        ))
+        .unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinLinkRequest {
+    pub team_role: TeamRole,
+
+    #[serde(default)]
+    pub requires_approval: bool,
+    pub max_uses: Option<i32>,
+
+    #[serde(default, deserialize_with = "deserialize_offset_date_time")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+#[post("/<team_id>/join-links", data = "<join_link_data>")]
+pub async fn create_join_link(
+    access: Result<TeamAccess<JoinLinkManage>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    join_link_data: Json<JoinLinkRequest>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    // Same rule as invitations: handing out admin takes team.update.
+    if join_link_data.team_role == TeamRole::Admin && !access.can(Permission::TeamUpdate) {
+        return access_error(TeamAccessError::Permission(
+            PermissionError::PermissionDenied,
+        ));
+    }
+
+    let join_link = match create_team_join_link(
+        &team_id,
+        access.user_id(),
+        &join_link_data.team_role,
+        join_link_data.requires_approval,
+        join_link_data.max_uses,
+        join_link_data.expires_at,
+    )
+    .await
+    {
+        Ok(join_link) => join_link,
+        Err(err) => {
+            println!("Error creating join link: {:?}", err);
+            return join_link_error(err);
+        }
+    };
+
+    record_audit_event(
+        AuditEntry::new(AuditAction::JoinLinkCreated)
+            .actor(access.user_id())
+            .team(&team_id)
+            .target("join_link", join_link.id.as_deref().unwrap_or_default())
+            .client(&client)
+            .details(serde_json::json!({
+                "teamRole": join_link_data.team_role.to_string(),
+                "requiresApproval": join_link_data.requires_approval,
+                "maxUses": join_link_data.max_uses,
+            })),
+    )
+    .await;
+
+    status::Custom(
+        Status::Created,
+        serde_json::to_value(JoinLinksResponse::success(
+            serde_json::to_value(join_link).unwrap(),
+            Some("Join link created successfully".to_string()),
+        ))
+        .unwrap(),
+    )
+}
+
+#[delete("/<team_id>/join-links/<join_link_id>")]
+pub async fn revoke_join_link(
+    access: Result<TeamAccess<JoinLinkManage>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    join_link_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match revoke_team_join_link(&team_id, &join_link_id).await {
+        Ok(join_link) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinLinkRevoked)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("join_link", &join_link_id)
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(JoinLinksResponse::success(
+                    serde_json::to_value(join_link).unwrap(),
+                    Some("Join link revoked successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error revoking join link: {:?}", err);
+            join_link_error(err)
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use crate::api::client::ClientInfo;
+use crate::api::team_access::TeamAccess;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::join_request::JoinRequestError;
+use crate::models::permission::{JoinRequestDecide, PermissionError, TeamAccessError};
+use crate::models::team::TeamError;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::join_requests::{
+    approve_join_request as approve_team_join_request, deny_join_request as deny_team_join_request,
+    notify_requester, pending_join_requests, request_to_join,
+};
+use crate::utils::notifier::SharedNotifier;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ResponseError {
+    Authentication(AuthenticationError),
+    Team(TeamError),
+    Permission(PermissionError),
+    JoinRequest(JoinRequestError),
+}
+
+impl From<AuthenticationError> for ResponseError {
+    fn from(error: AuthenticationError) -> Self {
+        ResponseError::Authentication(error)
+    }
+}
+
+impl From<TeamError> for ResponseError {
+    fn from(error: TeamError) -> Self {
+        ResponseError::Team(error)
+    }
+}
+
+impl From<JoinRequestError> for ResponseError {
+    fn from(error: JoinRequestError) -> Self {
+        ResponseError::JoinRequest(error)
+    }
+}
+
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JoinRequestsResponse {
+    pub error: Option<ResponseError>,
+    pub message: Option<String>,
+    pub data: Option<Value>,
+}
+
+impl JoinRequestsResponse {
+    pub fn success(data: Value, message: Option<String>) -> Self {
+        Self {
+            error: None,
+            message,
+            data: Some(data),
+        }
+    }
+
+    pub fn error(error: impl Into<ResponseError>, message: String) -> Self {
+        Self {
+            error: Some(error.into()),
+            message: Some(message),
+            data: None,
+        }
+    }
+}
+
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(JoinRequestsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
+fn join_request_error(err: JoinRequestError) -> status::Custom<Value> {
+    let status = match err {
+        JoinRequestError::JoinRequestNotFound | JoinRequestError::TeamNotDiscoverable => {
+            Status::NotFound
+        }
+        JoinRequestError::JoinRequestAlreadyDecided
+        | JoinRequestError::JoinRequestAlreadyPending
+        | JoinRequestError::RequesterAlreadyMember => Status::Conflict,
+        JoinRequestError::MessageTooLong => Status::BadRequest,
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(JoinRequestsResponse::error(err, message)).unwrap(),
+    )
+}
+
+#[get("/<team_id>/join-requests")]
+pub async fn get_join_requests(
+    access: Result<TeamAccess<JoinRequestDecide>, TeamAccessError>,
+    team_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
+
+    match pending_join_requests(&team_id).await {
+        Ok(join_requests) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(JoinRequestsResponse::success(
+                serde_json::to_value(join_requests).unwrap(),
+                None,
+            ))
+            .unwrap(),
+        ),
+        Err(err) => {
+            println!("Error finding join requests: {:?}", err);
+            join_request_error(JoinRequestError::JoinRequestNotFound)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateJoinRequest {
+    pub message: Option<String>,
+}
+
+/// Asks to join a discoverable team, with an optional note for whoever
+/// reviews the request.
+#[post("/<team_id>/join-requests", data = "<join_request_data>")]
+pub async fn create_join_request(
+    token: RawToken,
+    client: ClientInfo,
+    team_id: String,
+    join_request_data: Option<Json<CreateJoinRequest>>,
+) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsWrite).await {
+        Ok(token) => token,
+        Err(err) => {
+            println!("Error validating token: {:?}", err);
+            return status::Custom(
+                token_error_status(&err),
+                serde_json::to_value(JoinRequestsResponse::error(err.clone(), err.to_string()))
+                    .unwrap(),
+            );
+        }
+    };
+
+    let join_request_data = join_request_data.map(Json::into_inner).unwrap_or_default();
+    match request_to_join(
+        &team_id,
+        &token_value.user_id,
+        join_request_data.message.as_deref(),
+    )
+    .await
+    {
+        Ok(join_request) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinRequestCreated)
+                    .user(&token_value.user_id)
+                    .team(&team_id)
+                    .target(
+                        "join_request",
+                        join_request.id.as_deref().unwrap_or_default(),
+                    )
+                    .client(&client),
+            )
+            .await;
+            status::Custom(
+                Status::Created,
+                serde_json::to_value(JoinRequestsResponse::success(
+                    serde_json::to_value(join_request).unwrap(),
+                    Some("Join request sent to the team for approval".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error requesting to join team: {:?}", err);
+            join_request_error(err)
+        }
+    }
+}
+
+#[post("/<team_id>/join-requests/<join_request_id>/approve")]
+pub async fn approve_join_request(
+    access: Result<TeamAccess<JoinRequestDecide>, TeamAccessError>,
+    notifier: &State<SharedNotifier>,
+    client: ClientInfo,
+    team_id: String,
+    join_request_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match approve_team_join_request(&team_id, &join_request_id, access.user_id()).await {
+        Ok((join_request, team_user)) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinRequestApproved)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("join_request", &join_request_id)
+                    .client(&client)
+                    .details(serde_json::json!({
+                        "userId": join_request.user_id,
+                        "teamRole": team_user.team_role.as_ref().map(|role| role.to_string()),
+                    })),
+            )
+            .await;
+            notify_requester(&join_request, notifier.inner().as_ref()).await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(JoinRequestsResponse::success(
+                    serde_json::to_value(team_user).unwrap(),
+                    Some("Join request approved successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error approving join request: {:?}", err);
+            join_request_error(err)
+        }
+    }
+}
+
+#[post("/<team_id>/join-requests/<join_request_id>/deny")]
+pub async fn deny_join_request(
+    access: Result<TeamAccess<JoinRequestDecide>, TeamAccessError>,
+    notifier: &State<SharedNotifier>,
+    client: ClientInfo,
+    team_id: String,
+    join_request_id: String,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    match deny_team_join_request(&team_id, &join_request_id, access.user_id()).await {
+        Ok(join_request) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::JoinRequestDenied)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("join_request", &join_request_id)
+                    .client(&client)
+                    .details(serde_json::json!({ "userId": join_request.user_id })),
+            )
+            .await;
+            notify_requester(&join_request, notifier.inner().as_ref()).await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(JoinRequestsResponse::success(
+                    serde_json::to_value(join_request).unwrap(),
+                    Some("Join request denied successfully".to_string()),
+                ))
+                .unwrap(),
+            )
+        }
+        Err(err) => {
+            println!("Error denying join request: {:?}", err);
+            join_request_error(err)
+        }
+    }
+}
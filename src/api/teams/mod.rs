@@ -1,3 +1,5 @@
 pub mod invitations;
+pub mod join_links;
+pub mod join_requests;
 pub mod teams;
 pub mod users;
@@ -1,15 +1,21 @@
-use crate::api::token::{validate_token, RawToken};
+use crate::api::team_access::TeamAccess;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
 use crate::database::values::DatabaseValue;
-use crate::models::activity::Activity;
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
 use crate::models::capability::Capability;
 use crate::models::invitation::{Invitation, InvitationError};
+use crate::models::permission::{PermissionError, TeamAccessError, TeamRead};
 use crate::models::team::{Team, TeamError};
 use crate::models::user::{User, UserError};
 use crate::models::user_skill::UserSkill;
+use crate::utils::team_hierarchy::{
+    activities_of_teams, child_teams, descendant_team_ids, members_of_teams,
+};
+use crate::utils::teams::{discoverable_teams, user_teams};
 use crate::{
-    find_all_unarchived_resources_where_fields, find_one_unarchived_resource_where_fields,
-    join_all_resources_where_fields_on,
+    find_all_unarchived_resources_where_fields, find_one_resource_where_fields,
+    find_one_unarchived_resource_where_fields,
 };
 use futures::future::try_join_all;
 use rocket::http::Status;
@@ -25,6 +31,7 @@ pub enum ResponseError {
     Invitation(InvitationError),
     Team(TeamError),
     User(UserError),
+    Permission(PermissionError),
 }
 
 impl From<AuthenticationError> for ResponseError {
@@ -51,6 +58,16 @@ impl From<UserError> for ResponseError {
     }
 }
 
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TeamsResponse {
     pub error: Option<ResponseError>,
@@ -76,19 +93,22 @@ impl TeamsResponse {
     }
 }
 
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
 #[get("/")]
 pub async fn get_teams(token: RawToken) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
@@ -110,54 +130,20 @@ pub async fn get_teams(token: RawToken) -> status::Custom<Value> {
         }
     };
 
-    let owned_teams_params = vec![("owner_id", &token_value.user_id)];
-    let owned_teams =
-        match find_all_unarchived_resources_where_fields!(Team, owned_teams_params).await {
-            Ok(teams) => teams,
-            Err(err) => {
-                println!("Error finding teams: {:?}", err);
-                return status::Custom(
-                    Status::NotFound,
-                    serde_json::to_value(TeamsResponse::error(
-                        TeamError::TeamNotFound,
-                        TeamError::TeamNotFound.to_string(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        };
-
-    let invitations_params = vec![("user_id", &token_value.user_id)];
-    let invitations =
-        match find_all_unarchived_resources_where_fields!(Invitation, invitations_params).await {
-            Ok(invitations) => invitations,
-            Err(err) => {
-                println!("Error finding invitations: {:?}", err);
-                return status::Custom(
-                    Status::NotFound,
-                    serde_json::to_value(TeamsResponse::error(
-                        InvitationError::InvitationNotFound,
-                        InvitationError::InvitationNotFound.to_string(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        };
-
-    let team_futures = invitations
-        .iter()
-        .map(|invitation| async move {
-            let team_id = invitation.team_id.clone().unwrap();
-            let team_id_param = vec![("id", &team_id)];
-            find_one_unarchived_resource_where_fields!(Team, team_id_param).await
-        })
-        .collect::<Vec<_>>();
-
-    let invited_teams = try_join_all(team_futures).await.unwrap_or_default();
-
-    let mut teams = Vec::new();
-    teams.extend(owned_teams);
-    teams.extend(invited_teams);
+    let teams = match user_teams(&token_value.user_id).await {
+        Ok(teams) => teams,
+        Err(err) => {
+            println!("Error finding teams: {:?}", err);
+            return status::Custom(
+                Status::NotFound,
+                serde_json::to_value(TeamsResponse::error(
+                    TeamError::TeamNotFound,
+                    TeamError::TeamNotFound.to_string(),
+                ))
+                .unwrap(),
+            );
+        }
+    };
 
     status::Custom(
         Status::Ok,
@@ -169,109 +155,119 @@ pub async fn get_teams(token: RawToken) -> status::Custom<Value> {
     )
 }
 
+/// A team as listed in the directory.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct InvitationResponse {
-    pub invitation: Invitation,
-    pub user: User,
-    pub accepted: bool,
-    pub rejected: bool,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TeamResponse {
-    pub team: Team,
-    pub invitations: Vec<InvitationResponse>,
-    pub capabilities: HashMap<String, Vec<Capability>>,
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryTeam {
+    pub id: Option<String>,
+    pub team_name: Option<String>,
+    pub team_description: Option<String>,
 }
 
-#[get("/<team_id>")]
-pub async fn get_team(token: RawToken, team_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
+/// Searches discoverable teams in the caller's organizations by name and
+/// description. Any of them can be asked to join through
+/// `POST /<team_id>/join-requests`.
+#[get("/discover?<q>")]
+pub async fn get_discoverable_teams(token: RawToken, q: Option<String>) -> status::Custom<Value> {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::TeamsRead).await {
         Ok(token) => token,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
+                token_error_status(&err),
+                serde_json::to_value(TeamsResponse::error(err.clone(), err.to_string())).unwrap(),
+            );
+        }
+    };
+
+    let teams = match discoverable_teams(&token_value.user_id, q.as_deref()).await {
+        Ok(teams) => teams,
+        Err(err) => {
+            println!("Error finding discoverable teams: {:?}", err);
+            return status::Custom(
+                Status::InternalServerError,
                 serde_json::to_value(TeamsResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
+                    TeamError::TeamNotFound,
+                    TeamError::TeamNotFound.to_string(),
                 ))
                 .unwrap(),
             );
         }
     };
 
-    let invitation_params = vec![("user_id", &token_value.user_id), ("team_id", &team_id)];
-    let invitation =
-        match find_one_unarchived_resource_where_fields!(Invitation, invitation_params).await {
-            Ok(invitation) => Some(invitation),
-            Err(err) => {
-                println!("Error finding invitation: {:?}", err);
-                None
-            }
-        };
+    let directory = teams
+        .into_iter()
+        .map(|team| DirectoryTeam {
+            id: team.id,
+            team_name: team.team_name,
+            team_description: team.team_description,
+        })
+        .collect::<Vec<DirectoryTeam>>();
+    status::Custom(
+        Status::Ok,
+        serde_json::to_value(TeamsResponse::success(
+            serde_json::to_value(directory).unwrap(),
+            None,
+        ))
+        .unwrap(),
+    )
+}
 
-    let owned_team_params = vec![("owner_id", &token_value.user_id), ("id", &team_id)];
-    let owned_team = match find_one_unarchived_resource_where_fields!(Team, owned_team_params).await
-    {
-        Ok(team) => Some(team),
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            None
-        }
-    };
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvitationResponse {
+    pub invitation: Invitation,
+    pub user: User,
+    pub accepted: bool,
+    pub rejected: bool,
+}
 
-    let mut team: Option<Team> = None;
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamResponse {
+    pub team: Team,
+    pub sub_teams: Vec<Team>,
+    pub invitations: Vec<InvitationResponse>,
+    pub capabilities: HashMap<String, Vec<Capability>>,
+}
 
-    if invitation.is_some() {
-        let team_id_param = vec![("id", &team_id)];
-        team = match find_one_unarchived_resource_where_fields!(Team, team_id_param).await {
-            Ok(team) => Some(team),
-            Err(err) => {
-                println!("Error finding team: {:?}", err);
-                None
-            }
-        };
-    } else if owned_team.is_some() {
-        team = owned_team;
+/// The team with its capabilities. With `?rollup=true` the capabilities
+/// also cover the members of every team below it.
+#[get("/<team_id>?<rollup>")]
+pub async fn get_team(
+    access: Result<TeamAccess<TeamRead>, TeamAccessError>,
+    team_id: String,
+    rollup: Option<bool>,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
     }
 
-    match team {
-        Some(team) => match get_team_response(team.id.unwrap()).await {
-            Ok(team_response) => status::Custom(
-                Status::Ok,
-                serde_json::to_value(TeamsResponse::success(
-                    serde_json::to_value(team_response).unwrap(),
-                    Some("Team fetched successfully".to_string()),
-                ))
-                .unwrap(),
-            ),
-            Err(err) => {
-                println!("Error getting team response: {:?}", err);
-                return status::Custom(
-                    Status::InternalServerError,
-                    serde_json::to_value(TeamsResponse::error(
-                        TeamError::TeamNotFound,
-                        TeamError::TeamNotFound.to_string(),
-                    ))
-                    .unwrap(),
-                );
-            }
-        },
-        None => status::Custom(
-            Status::NotFound,
-            serde_json::to_value(TeamsResponse::error(
-                TeamError::TeamNotFound,
-                TeamError::TeamNotFound.to_string(),
+    match get_team_response(team_id, rollup.unwrap_or(false)).await {
+        Ok(team_response) => status::Custom(
+            Status::Ok,
+            serde_json::to_value(TeamsResponse::success(
+                serde_json::to_value(team_response).unwrap(),
+                Some("Team fetched successfully".to_string()),
             ))
             .unwrap(),
         ),
+        Err(err) => {
+            println!("Error getting team response: {:?}", err);
+            status::Custom(
+                Status::InternalServerError,
+                serde_json::to_value(TeamsResponse::error(
+                    TeamError::TeamNotFound,
+                    TeamError::TeamNotFound.to_string(),
+                ))
+                .unwrap(),
+            )
+        }
     }
 }
 
-async fn get_team_response(team_id: String) -> Result<TeamResponse, Error> {
+async fn get_team_response(team_id: String, rollup: bool) -> Result<TeamResponse, Error> {
     let team_params = vec![("id", &team_id)];
-    let team = match find_one_unarchived_resource_where_fields!(Team, team_params).await {
+    let team = match find_one_resource_where_fields!(Team, team_params).await {
         Ok(team) => team,
         Err(err) => {
             println!("Error finding team: {:?}", err);
@@ -306,20 +302,23 @@ async fn get_team_response(team_id: String) -> Result<TeamResponse, Error> {
     });
     let invitations_response = try_join_all(invitation_responses).await.unwrap();
 
-    let activity_params = vec![("team_id", &team_id)];
-    let activities =
-        match find_all_unarchived_resources_where_fields!(Activity, activity_params).await {
-            Ok(activities) => activities,
-            Err(err) => {
-                println!("Error finding activities: {:?}", err);
-                return Err(err);
-            }
-        };
+    let sub_teams = child_teams(&team_id).await?;
+    let team_ids = if rollup {
+        descendant_team_ids(&team_id).await?
+    } else {
+        vec![team_id.clone()]
+    };
+    let activities = match activities_of_teams(&team_ids).await {
+        Ok(activities) => activities,
+        Err(err) => {
+            println!("Error finding activities: {:?}", err);
+            return Err(err);
+        }
+    };
 
     let mut capabilities: HashMap<String, Vec<Capability>> = HashMap::new();
 
-    let users_params = vec![("teams.team_id", &team_id)];
-    let users = join_all_resources_where_fields_on!(User, Team, users_params).await?;
+    let users = members_of_teams(&team_ids).await?;
     for user in users {
         let user_id = user.id.clone().unwrap();
         let skills_params = vec![("user_id", DatabaseValue::String(user_id.clone()))];
@@ -340,7 +339,7 @@ async fn get_team_response(team_id: String) -> Result<TeamResponse, Error> {
                 level: skill.skill_level.unwrap(),
                 available: !activities
                     .iter()
-                    .any(|activity| activity.assigned_to.clone().unwrap() == user_id),
+                    .any(|activity| activity.assigned_to.as_deref() == Some(user_id.as_str())),
             };
             capabilities
                 .entry(skill_name)
@@ -351,6 +350,7 @@ async fn get_team_response(team_id: String) -> Result<TeamResponse, Error> {
 
     Ok(TeamResponse {
         team,
+        sub_teams,
         invitations: invitations_response,
         capabilities,
     })
@@ -1,13 +1,16 @@
-use crate::api::token::{validate_token, RawToken};
-use crate::database::values::DatabaseValue;
+use crate::api::client::ClientInfo;
+use crate::api::team_access::TeamAccess;
 use crate::models::authentication::AuthenticationError;
-use crate::models::invitation::{Invitation, InvitationError};
-use crate::models::team::{Team, TeamError};
-use crate::models::user::{User, UserError};
-use crate::{
-    find_all_unarchived_resources_where_fields, find_one_unarchived_resource_where_fields,
-    join_all_resources_where_fields_on,
+use crate::models::invitation::InvitationError;
+use crate::models::permission::{
+    MemberRemove, MemberUpdate, PermissionError, TeamAccessError, TeamLeave, TeamRead,
 };
+use crate::models::team::TeamError;
+use crate::models::team_role::TeamRole;
+use crate::models::team_user::TeamUserError;
+use crate::models::user::UserError;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::teams::{change_team_role, remove_team_member, team_members};
 use rocket::http::Status;
 use rocket::response::status;
 use rocket::serde::json::Json;
@@ -20,6 +23,8 @@ pub enum ResponseError {
     Invitation(InvitationError),
     Team(TeamError),
     User(UserError),
+    Permission(PermissionError),
+    TeamUser(TeamUserError),
 }
 
 impl From<AuthenticationError> for ResponseError {
@@ -46,6 +51,22 @@ impl From<UserError> for ResponseError {
     }
 }
 
+impl From<TeamUserError> for ResponseError {
+    fn from(error: TeamUserError) -> Self {
+        ResponseError::TeamUser(error)
+    }
+}
+
+impl From<TeamAccessError> for ResponseError {
+    fn from(error: TeamAccessError) -> Self {
+        match error {
+            TeamAccessError::Authentication(error) => ResponseError::Authentication(error),
+            TeamAccessError::Team(error) => ResponseError::Team(error),
+            TeamAccessError::Permission(error) => ResponseError::Permission(error),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UsersResponse {
     pub error: Option<ResponseError>,
@@ -71,29 +92,26 @@ impl UsersResponse {
     }
 }
 
+fn access_error(err: TeamAccessError) -> status::Custom<Value> {
+    status::Custom(
+        err.status(),
+        serde_json::to_value(UsersResponse::error(err.clone(), err.to_string())).unwrap(),
+    )
+}
+
 #[get("/<team_id>/users")]
-pub async fn get_users(token: RawToken, team_id: String) -> status::Custom<Value> {
-    let token_value = match validate_token(token).await {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error validating token: {:?}", err);
-            return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UsersResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
-            );
-        }
-    };
+pub async fn get_users(
+    access: Result<TeamAccess<TeamRead>, TeamAccessError>,
+    team_id: String,
+) -> status::Custom<Value> {
+    if let Err(err) = access {
+        return access_error(err);
+    }
 
-    let user_id = DatabaseValue::String(token_value.user_id.clone());
-    let find_user_params = vec![("id", &user_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(User, find_user_params).await {
-        Ok(user) => user,
+    let users = match team_members(&team_id).await {
+        Ok(users) => users,
         Err(err) => {
-            println!("Error finding user: {:?}", err);
+            println!("Error finding users: {:?}", err);
             return status::Custom(
                 Status::NotFound,
                 serde_json::to_value(UsersResponse::error(
@@ -105,39 +123,168 @@ pub async fn get_users(token: RawToken, team_id: String) -> status::Custom<Value
         }
     };
 
-    let team_id = DatabaseValue::String(team_id);
-    let find_team_params = vec![("id", &team_id)];
-    let _ = match find_one_unarchived_resource_where_fields!(Team, find_team_params).await {
-        Ok(team) => team,
-        Err(err) => {
-            println!("Error finding team: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(UsersResponse::error(
-                    TeamError::TeamNotFound,
-                    TeamError::TeamNotFound.to_string(),
+    let users_response = UsersResponse::success(serde_json::to_value(users).unwrap(), None);
+    status::Custom(Status::Ok, serde_json::to_value(users_response).unwrap())
+}
+
+fn team_user_error(err: TeamUserError) -> status::Custom<Value> {
+    let status = match err {
+        TeamUserError::TeamUserNotFound => Status::NotFound,
+        TeamUserError::LastTeamAdmin | TeamUserError::TeamOwnerMembership => Status::Conflict,
+        TeamUserError::InvalidAssignee => Status::UnprocessableEntity,
+        _ => Status::InternalServerError,
+    };
+    let message = err.to_string();
+    status::Custom(
+        status,
+        serde_json::to_value(UsersResponse::error(err, message)).unwrap(),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUserRoleRequest {
+    pub team_role: TeamRole,
+}
+
+/// Promotes or demotes a member. The last admin can't be demoted.
+#[put("/<team_id>/users/<user_id>", data = "<role_data>")]
+pub async fn update_user_role(
+    access: Result<TeamAccess<MemberUpdate>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    user_id: String,
+    role_data: Json<UpdateUserRoleRequest>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    let team_role = role_data.into_inner().team_role;
+    match change_team_role(&team_id, &user_id, team_role.clone()).await {
+        Ok(membership) => {
+            record_audit_event(
+                AuditEntry::new(AuditAction::MemberRoleChanged)
+                    .actor(access.user_id())
+                    .team(&team_id)
+                    .target("user", &user_id)
+                    .client(&client)
+                    .details(serde_json::json!({ "teamRole": team_role.to_string() })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(UsersResponse::success(
+                    serde_json::to_value(membership).unwrap(),
+                    Some("Team role updated successfully".to_string()),
                 ))
                 .unwrap(),
-            );
+            )
         }
-    };
-
-    let users_params = vec![("team_id", &team_id)];
-    let users = match join_all_resources_where_fields_on!(User, TeamUser, users_params).await {
-        Ok(users) => users,
         Err(err) => {
-            println!("Error finding users: {:?}", err);
-            return status::Custom(
-                Status::NotFound,
-                serde_json::to_value(UsersResponse::error(
-                    UserError::UserNotFound,
-                    UserError::UserNotFound.to_string(),
+            println!("Error changing team role: {:?}", err);
+            team_user_error(err)
+        }
+    }
+}
+
+/// Where a departing member's open activities should go. Left out, they
+/// are unassigned.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveUserRequest {
+    pub hand_over_to: Option<String>,
+}
+
+async fn end_membership(
+    team_id: &str,
+    user_id: &str,
+    actor_id: &str,
+    action: AuditAction,
+    request: Option<Json<RemoveUserRequest>>,
+    client: &ClientInfo,
+) -> status::Custom<Value> {
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    match remove_team_member(team_id, user_id, request.hand_over_to.as_deref()).await {
+        Ok((membership, handed_over)) => {
+            record_audit_event(
+                AuditEntry::new(action)
+                    .actor(actor_id)
+                    .team(team_id)
+                    .target("user", user_id)
+                    .client(client)
+                    .details(serde_json::json!({
+                        "handOverTo": request.hand_over_to,
+                        "activities": handed_over,
+                    })),
+            )
+            .await;
+            status::Custom(
+                Status::Ok,
+                serde_json::to_value(UsersResponse::success(
+                    serde_json::to_value(membership).unwrap(),
+                    Some("Team membership ended successfully".to_string()),
                 ))
                 .unwrap(),
-            );
+            )
         }
+        Err(err) => {
+            println!("Error removing team member: {:?}", err);
+            team_user_error(err)
+        }
+    }
+}
+
+#[delete("/<team_id>/users/<user_id>", data = "<remove_data>")]
+pub async fn remove_user(
+    access: Result<TeamAccess<MemberRemove>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    user_id: String,
+    remove_data: Option<Json<RemoveUserRequest>>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
     };
 
-    let users_response = UsersResponse::success(serde_json::to_value(users).unwrap(), None);
-    status::Custom(Status::Ok, serde_json::to_value(users_response).unwrap())
+    let action = if user_id == access.user_id() {
+        AuditAction::MemberLeft
+    } else {
+        AuditAction::MemberRemoved
+    };
+    end_membership(
+        &team_id,
+        &user_id,
+        access.user_id(),
+        action,
+        remove_data,
+        &client,
+    )
+    .await
+}
+
+/// Leaves a team. Owners have to transfer the team first.
+#[post("/<team_id>/leave", data = "<leave_data>")]
+pub async fn leave_team(
+    access: Result<TeamAccess<TeamLeave>, TeamAccessError>,
+    client: ClientInfo,
+    team_id: String,
+    leave_data: Option<Json<RemoveUserRequest>>,
+) -> status::Custom<Value> {
+    let access = match access {
+        Ok(access) => access,
+        Err(err) => return access_error(err),
+    };
+
+    end_membership(
+        &team_id,
+        access.user_id(),
+        access.user_id(),
+        AuditAction::MemberLeft,
+        leave_data,
+        &client,
+    )
+    .await
 }
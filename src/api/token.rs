@@ -1,12 +1,18 @@
-use crate::models::authentication::Authentication;
+use crate::find_one_unarchived_resource_where_fields;
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::user::User;
+use crate::utils::api_keys::{authenticate_api_key, is_api_key};
+use crate::utils::denylist::is_session_revoked;
+use crate::utils::jwt::decode_access_token;
 use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
-use crate::{find_one_resource_where_fields, models::authentication::AuthenticationError};
 use rocket::{
+    http::Status,
     request::{FromRequest, Outcome},
     Request,
 };
 use serde::{Deserialize, Serialize};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use time::OffsetDateTime;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(crate = "rocket::serde")]
@@ -20,6 +26,7 @@ pub struct Token {
 #[serde(crate = "rocket::serde", rename_all = "camelCase")]
 pub struct VerifiedToken {
     pub raw_token: Option<String>,
+    pub authentication_id: String,
     #[serde(rename = "ssoToken")]
     pub user_id: String,
 
@@ -28,35 +35,74 @@ pub struct VerifiedToken {
         deserialize_with = "deserialize_offset_date_time"
     )]
     pub expires_at: Option<OffsetDateTime>,
+
+    /// Scopes granted when the request was made with an API key, in which
+    /// case `authentication_id` is the key's id. `None` for sessions.
+    pub scopes: Option<Vec<ApiKeyScope>>,
 }
 
 impl VerifiedToken {
-    pub fn new(raw_token: String, user_id: String, expires_at: Option<OffsetDateTime>) -> Self {
+    pub fn new(
+        raw_token: String,
+        authentication_id: String,
+        user_id: String,
+        expires_at: Option<OffsetDateTime>,
+    ) -> Self {
         Self {
             raw_token: Some(raw_token),
+            authentication_id,
             user_id,
             expires_at,
+            scopes: None,
         }
     }
 
+    pub fn is_api_key(&self) -> bool {
+        self.scopes.is_some()
+    }
+
+    /// Sessions can do anything their user can; API keys only what they
+    /// were granted.
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(&scope))
+    }
+
+    /// Verifies a signed access token locally. The only shared state checked
+    /// is the revoked-session denylist, which is cached in memory.
     pub async fn from_raw(raw_token: RawToken) -> Result<Self, AuthenticationError> {
-        let params = vec![("token", &raw_token.value)];
-        let authentication = match find_one_resource_where_fields!(Authentication, params).await {
-            Ok(authentication) => authentication,
-            Err(_) => return Err(AuthenticationError::InvalidToken),
-        };
-        if authentication.expires_at.is_none()
-            || authentication.expires_at.as_ref().unwrap().to_string()
-                < OffsetDateTime::now_utc().format(&Rfc3339).unwrap()
-        {
+        if is_api_key(&raw_token.value) {
+            return Self::from_api_key(raw_token).await;
+        }
+        let claims = decode_access_token(&raw_token.value)?;
+        if is_session_revoked(&claims.sid).await {
+            return Err(AuthenticationError::InvalidToken);
+        }
+        let expires_at = OffsetDateTime::from_unix_timestamp(claims.exp)
+            .map_err(|_| AuthenticationError::InvalidToken)?;
+        if expires_at <= OffsetDateTime::now_utc() {
             return Err(AuthenticationError::TokenExpired);
         }
         Ok(Self::new(
             raw_token.value,
-            authentication.user_id,
-            Some(authentication.expires_at.unwrap().clone()),
+            claims.sid,
+            claims.sub,
+            Some(expires_at),
         ))
     }
+
+    async fn from_api_key(raw_token: RawToken) -> Result<Self, AuthenticationError> {
+        let api_key = authenticate_api_key(&raw_token.value).await?;
+        let mut verified = Self::new(
+            raw_token.value,
+            api_key.id.unwrap_or_default(),
+            api_key.user_id.unwrap_or_default(),
+            api_key.expires_at,
+        );
+        verified.scopes = Some(api_key.scopes);
+        Ok(verified)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -84,7 +130,7 @@ impl<'r> FromRequest<'r> for RawToken {
     }
 }
 
-pub async fn validate_token(token: RawToken) -> Result<VerifiedToken, AuthenticationError> {
+async fn verify_token(token: RawToken) -> Result<VerifiedToken, AuthenticationError> {
     if token.value.is_empty() {
         println!("Token is empty");
         return Err(AuthenticationError::SessionNotFound);
@@ -94,7 +140,79 @@ pub async fn validate_token(token: RawToken) -> Result<VerifiedToken, Authentica
         Ok(token) => Ok(token),
         Err(err) => {
             println!("Error verifying token: {:?}", err);
-            return Err(AuthenticationError::InvalidToken);
+            Err(AuthenticationError::InvalidToken)
+        }
+    }
+}
+
+/// Accepts session access tokens only. Used by routes that manage the
+/// account itself, which API keys must never reach.
+pub async fn validate_token(token: RawToken) -> Result<VerifiedToken, AuthenticationError> {
+    let verified = verify_token(token).await?;
+    if verified.is_api_key() {
+        println!("API key used on a session-only route");
+        return Err(AuthenticationError::InvalidToken);
+    }
+    Ok(verified)
+}
+
+/// Accepts a session access token, or an API key granted `scope`.
+pub async fn validate_scoped_token(
+    token: RawToken,
+    scope: ApiKeyScope,
+) -> Result<VerifiedToken, AuthenticationError> {
+    let verified = verify_token(token).await?;
+    if !verified.has_scope(scope) {
+        println!("API key is missing scope: {}", scope.as_str());
+        return Err(AuthenticationError::InsufficientScope);
+    }
+    Ok(verified)
+}
+
+/// The status to answer with when a token is rejected.
+pub fn token_error_status(err: &AuthenticationError) -> Status {
+    match err {
+        AuthenticationError::InsufficientScope | AuthenticationError::NotAdmin => Status::Forbidden,
+        _ => Status::Unauthorized,
+    }
+}
+
+/// Request guard for system administrator routes. It only accepts sessions,
+/// never API keys, and checks the flag on every request so a demotion takes
+/// effect immediately. Take it as `Result<AdminToken, AuthenticationError>`
+/// to answer failures with the usual JSON body.
+#[derive(Debug, Clone)]
+pub struct AdminToken {
+    pub token: VerifiedToken,
+}
+
+impl AdminToken {
+    pub fn user_id(&self) -> &str {
+        &self.token.user_id
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminToken {
+    type Error = AuthenticationError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let raw_token = match RawToken::from_request(request).await {
+            Outcome::Success(raw_token) => raw_token,
+            _ => return Outcome::Error((Status::Unauthorized, AuthenticationError::InvalidToken)),
+        };
+        let token = match validate_token(raw_token).await {
+            Ok(token) => token,
+            Err(err) => return Outcome::Error((token_error_status(&err), err)),
+        };
+        let user_params = [("id", &token.user_id)];
+        match find_one_unarchived_resource_where_fields!(User, user_params).await {
+            Ok(user) if user.is_admin => Outcome::Success(AdminToken { token }),
+            Ok(_) => Outcome::Error((Status::Forbidden, AuthenticationError::NotAdmin)),
+            Err(err) => {
+                println!("Error finding admin user: {:?}", err);
+                Outcome::Error((Status::Unauthorized, AuthenticationError::InvalidToken))
+            }
         }
     }
 }
@@ -1,8 +1,8 @@
-use crate::api::token::{validate_token, RawToken};
-use crate::database::values::DatabaseValue;
-use crate::find_all_unarchived_resources_where_fields;
+use crate::api::token::{token_error_status, validate_scoped_token, RawToken};
+use crate::models::api_key::ApiKeyScope;
 use crate::models::authentication::AuthenticationError;
-use crate::models::user::{User, UserError};
+use crate::models::user::UserError;
+use crate::utils::organizations::organization_peers;
 use rocket::http::Status;
 use rocket::response::status;
 use serde::{Deserialize, Serialize};
@@ -51,25 +51,21 @@ impl UsersResponse {
     }
 }
 
+/// Lists the users who share an organization with the caller.
 #[get("/")]
 pub async fn get_users(token: RawToken) -> status::Custom<Value> {
-    let _ = match validate_token(token).await {
+    let token_value = match validate_scoped_token(token, ApiKeyScope::UsersRead).await {
         Ok(token_value) => token_value,
         Err(err) => {
             println!("Error validating token: {:?}", err);
             return status::Custom(
-                Status::Unauthorized,
-                serde_json::to_value(UsersResponse::error(
-                    AuthenticationError::InvalidToken,
-                    AuthenticationError::InvalidToken.to_string(),
-                ))
-                .unwrap(),
+                token_error_status(&err),
+                serde_json::to_value(UsersResponse::error(err.clone(), err.to_string())).unwrap(),
             );
         }
     };
 
-    let users_params: Vec<(&str, &DatabaseValue)> = vec![];
-    match find_all_unarchived_resources_where_fields!(User, users_params).await {
+    match organization_peers(&token_value.user_id).await {
         Ok(users) => status::Custom(
             Status::Ok,
             serde_json::to_value(UsersResponse::success(
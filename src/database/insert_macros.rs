@@ -6,16 +6,17 @@ macro_rules! insert_resource {
         };
         use crate::utils::strings::camel_to_snake_case;
         use pluralizer::pluralize;
-        use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
+        use time::{format_description::well_known::Iso8601, OffsetDateTime};
         use uuid::Uuid;
 
         async {
             let id = Uuid::new_v4().to_string();
             let created_at = OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap();
             let updated_at = created_at.clone();
-            let expires_at = (OffsetDateTime::now_utc() + Duration::days(30))
-                .format(&Iso8601::DEFAULT)
-                .unwrap();
+            let expires_at = (OffsetDateTime::now_utc()
+                + <$resource as DatabaseResource>::lifetime())
+            .format(&Iso8601::DEFAULT)
+            .unwrap();
 
             let resource_name = pluralize(
                 camel_to_snake_case(stringify!($resource).to_string()).as_str(),
@@ -64,21 +65,13 @@ macro_rules! insert_resource {
                     ));
                 }
             }
-            if <$resource as DatabaseResource>::is_expirable() {
-                if let Some(idx) = params
-                    .iter()
-                    .position(|(field, _)| field.contains("expires_at"))
-                {
-                    params[idx] = (
-                        "expires_at".to_string(),
-                        DatabaseValue::DateTime(expires_at),
-                    );
-                } else {
-                    params.push((
-                        "expires_at".to_string(),
-                        DatabaseValue::DateTime(expires_at),
-                    ));
-                }
+            if <$resource as DatabaseResource>::is_expirable()
+                && !params.iter().any(|(field, _)| field == "expires_at")
+            {
+                params.push((
+                    "expires_at".to_string(),
+                    DatabaseValue::DateTime(expires_at),
+                ));
             }
 
             let fields: Vec<String> = params.iter().map(|(field, _)| field.clone()).collect();
@@ -1,4 +1,5 @@
 use sqlx::{postgres::PgRow, Error};
+use time::Duration;
 
 pub trait DatabaseResource {
     fn from_row(row: &PgRow) -> Result<Self, Error>
@@ -10,4 +11,10 @@ pub trait DatabaseResource {
     fn is_updatable() -> bool;
     fn is_creatable() -> bool;
     fn is_expirable() -> bool;
+
+    /// How long a newly inserted expirable resource lives when the caller
+    /// doesn't supply its own `expires_at`.
+    fn lifetime() -> Duration {
+        Duration::days(30)
+    }
 }
@@ -7,13 +7,10 @@ macro_rules! update_resource {
         use crate::find_one_resource_where_fields;
         use crate::utils::strings::camel_to_snake_case;
         use pluralizer::pluralize;
-        use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
+        use time::{format_description::well_known::Iso8601, OffsetDateTime};
 
         async {
             let updated_at = OffsetDateTime::now_utc().format(&Iso8601::DEFAULT).unwrap();
-            let expires_at = (OffsetDateTime::now_utc() + Duration::days(30))
-                .format(&Iso8601::DEFAULT)
-                .unwrap();
 
             let resource_name = pluralize(
                 camel_to_snake_case(stringify!($resource).to_string()).as_str(),
@@ -40,16 +37,6 @@ macro_rules! update_resource {
                     params.push(("updated_at", DatabaseValue::DateTime(updated_at)));
                 }
             }
-            if <$resource as DatabaseResource>::is_expirable() {
-                if let Some(idx) = params
-                    .iter()
-                    .position(|(field, _)| field.contains("expires_at"))
-                {
-                    params[idx] = ("expires_at", DatabaseValue::DateTime(expires_at));
-                } else {
-                    params.push(("expires_at", DatabaseValue::DateTime(expires_at)));
-                }
-            }
 
             let fields = params
                 .iter()
@@ -18,8 +18,17 @@ async fn main() -> anyhow::Result<()> {
         .await?;
     let cors = CorsOptions::default().to_cors().unwrap();
 
+    utils::token_hash::ensure_token_hash_secret();
+    utils::backup_codes::hash_plaintext_backup_codes().await;
+    utils::admin::promote_bootstrap_admins().await;
+    utils::account_deletion::spawn_account_purge();
+    utils::team_archival::spawn_team_purge();
+    utils::data_exports::resume_data_exports().await;
+    utils::data_exports::spawn_data_export_cleanup();
+
     rocket::build()
         .manage(pool)
+        .manage(utils::notifier::notifier_from_env())
         .attach(cors)
         .mount("/api", routes![api::home::index])
         .mount(
@@ -27,8 +36,15 @@ async fn main() -> anyhow::Result<()> {
             routes![
                 api::authentications::login,
                 api::authentications::logout,
+                api::authentications::refresh,
+                api::authentications::recover,
+                api::authentications::forgot_password,
+                api::authentications::reset_password,
+                api::authentications::verify_two_factor,
                 api::authentications::register,
                 api::authentications::unregister,
+                api::oidc::oidc_authorize,
+                api::oidc::oidc_callback,
             ],
         )
         .mount(
@@ -55,6 +71,49 @@ async fn main() -> anyhow::Result<()> {
                 api::my::backup_codes::regenerate_backup_codes,
             ],
         )
+        .mount(
+            "/api/my/api-keys",
+            routes![
+                api::my::api_keys::get_api_keys,
+                api::my::api_keys::create_api_key,
+                api::my::api_keys::delete_api_key,
+            ],
+        )
+        .mount(
+            "/api/my/identities",
+            routes![
+                api::my::identities::get_identities,
+                api::my::identities::link_oidc_identity,
+                api::my::identities::delete_identity,
+            ],
+        )
+        .mount("/api/my/audit", routes![api::my::audit::get_audit_events])
+        .mount(
+            "/api/my/export",
+            routes![
+                api::my::export::get_exports,
+                api::my::export::create_export,
+                api::my::export::get_export,
+                api::my::export::download_export,
+            ],
+        )
+        .mount(
+            "/api/my/sessions",
+            routes![
+                api::my::sessions::get_sessions,
+                api::my::sessions::delete_session,
+                api::my::sessions::delete_other_sessions,
+            ],
+        )
+        .mount(
+            "/api/my/two-factor",
+            routes![
+                api::my::two_factor::get_two_factor,
+                api::my::two_factor::enroll_two_factor,
+                api::my::two_factor::confirm_two_factor,
+                api::my::two_factor::disable_two_factor,
+            ],
+        )
         .mount(
             "/api/my/teams",
             routes![
@@ -62,8 +121,17 @@ async fn main() -> anyhow::Result<()> {
                 api::my::teams::get_team,
                 api::my::teams::create_team,
                 api::my::teams::update_team,
+                api::my::teams::update_team_parent,
                 api::my::teams::delete_team,
+                api::my::teams::restore_team,
                 api::my::teams::create_invitation,
+                api::my::teams::resend_invitation,
+                api::my::teams::revoke_invitation,
+                api::my::teams::offer_team_transfer,
+                api::my::teams::withdraw_team_transfer,
+                api::my::teams::accept_team_transfer,
+                api::my::teams::decline_team_transfer,
+                api::my::teams::get_team_audit_events,
                 api::my::teams::get_team_activities,
                 api::my::teams::get_team_activity,
                 api::my::teams::create_team_activity,
@@ -85,13 +153,28 @@ async fn main() -> anyhow::Result<()> {
                 api::my::invitations::reject_invitation,
             ],
         )
+        .mount(
+            "/api/my/join-requests",
+            routes![api::my::join_requests::get_join_requests],
+        )
         .mount(
             "/api/teams",
             routes![
                 api::teams::teams::get_teams,
+                api::teams::teams::get_discoverable_teams,
                 api::teams::teams::get_team,
                 api::teams::invitations::get_invitations,
+                api::teams::join_links::get_join_links,
+                api::teams::join_links::create_join_link,
+                api::teams::join_links::revoke_join_link,
+                api::teams::join_requests::get_join_requests,
+                api::teams::join_requests::create_join_request,
+                api::teams::join_requests::approve_join_request,
+                api::teams::join_requests::deny_join_request,
                 api::teams::users::get_users,
+                api::teams::users::update_user_role,
+                api::teams::users::remove_user,
+                api::teams::users::leave_team,
             ],
         )
         .mount(
@@ -101,7 +184,37 @@ async fn main() -> anyhow::Result<()> {
                 api::invitations::get_invitation,
             ],
         )
+        .mount(
+            "/api/join-links",
+            routes![api::join_links::get_join_link, api::join_links::join_team],
+        )
         .mount("/api/users", routes![api::users::get_users])
+        .mount(
+            "/api/organizations",
+            routes![
+                api::organizations::get_organizations,
+                api::organizations::create_organization,
+                api::organizations::get_organization,
+                api::organizations::update_organization,
+                api::organizations::get_members,
+                api::organizations::add_organization_member,
+                api::organizations::update_organization_member,
+                api::organizations::remove_member,
+                api::organizations::get_organization_teams,
+            ],
+        )
+        .mount(
+            "/api/admin/users",
+            routes![
+                api::admin::users::get_users,
+                api::admin::users::get_user,
+                api::admin::users::archive_user,
+                api::admin::users::restore_user,
+                api::admin::users::delete_user_sessions,
+                api::admin::users::reset_user_backup_codes,
+                api::admin::users::update_user_admin,
+            ],
+        )
         .mount(
             "/api/my/activities",
             routes![
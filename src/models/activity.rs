@@ -21,6 +21,7 @@ pub enum ActivityError {
     ActivityCreationFailed,
     ActivityUpdateFailed,
     ActivityDeletionFailed,
+    AssigneeNotTeamMember,
 }
 
 impl std::fmt::Display for ActivityError {
@@ -39,6 +40,9 @@ impl std::fmt::Display for ActivityError {
             ActivityError::ActivityCreationFailed => write!(f, "Failed to create activity"),
             ActivityError::ActivityUpdateFailed => write!(f, "Failed to update activity"),
             ActivityError::ActivityDeletionFailed => write!(f, "Failed to delete activity"),
+            ActivityError::AssigneeNotTeamMember => {
+                write!(f, "Activities can only be assigned to team members")
+            }
         }
     }
 }
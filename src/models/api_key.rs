@@ -0,0 +1,187 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::api_keys::parse_scopes;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ApiKeyError {
+    ApiKeyNotFound,
+    ApiKeyCreationFailed,
+    ApiKeyDeletionFailed,
+    InvalidScope,
+}
+
+impl std::fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiKeyError::ApiKeyNotFound => write!(f, "API key not found"),
+            ApiKeyError::ApiKeyCreationFailed => write!(f, "API key creation failed"),
+            ApiKeyError::ApiKeyDeletionFailed => write!(f, "API key deletion failed"),
+            ApiKeyError::InvalidScope => write!(f, "Invalid API key scope"),
+        }
+    }
+}
+
+impl std::error::Error for ApiKeyError {}
+
+/// What an API key is allowed to do. Sessions are not scoped; these only
+/// restrict requests made with an API key.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeyScope {
+    #[serde(rename = "profile:read")]
+    ProfileRead,
+    #[serde(rename = "profile:write")]
+    ProfileWrite,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "skills:read")]
+    SkillsRead,
+    #[serde(rename = "skills:write")]
+    SkillsWrite,
+    #[serde(rename = "teams:read")]
+    TeamsRead,
+    #[serde(rename = "teams:write")]
+    TeamsWrite,
+    #[serde(rename = "activities:read")]
+    ActivitiesRead,
+    #[serde(rename = "activities:write")]
+    ActivitiesWrite,
+    #[serde(rename = "invitations:read")]
+    InvitationsRead,
+    #[serde(rename = "invitations:write")]
+    InvitationsWrite,
+    #[serde(rename = "organizations:read")]
+    OrganizationsRead,
+    #[serde(rename = "organizations:write")]
+    OrganizationsWrite,
+}
+
+impl ApiKeyScope {
+    pub const ALL: [ApiKeyScope; 13] = [
+        ApiKeyScope::ProfileRead,
+        ApiKeyScope::ProfileWrite,
+        ApiKeyScope::UsersRead,
+        ApiKeyScope::SkillsRead,
+        ApiKeyScope::SkillsWrite,
+        ApiKeyScope::TeamsRead,
+        ApiKeyScope::TeamsWrite,
+        ApiKeyScope::ActivitiesRead,
+        ApiKeyScope::ActivitiesWrite,
+        ApiKeyScope::InvitationsRead,
+        ApiKeyScope::InvitationsWrite,
+        ApiKeyScope::OrganizationsRead,
+        ApiKeyScope::OrganizationsWrite,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::ProfileRead => "profile:read",
+            ApiKeyScope::ProfileWrite => "profile:write",
+            ApiKeyScope::UsersRead => "users:read",
+            ApiKeyScope::SkillsRead => "skills:read",
+            ApiKeyScope::SkillsWrite => "skills:write",
+            ApiKeyScope::TeamsRead => "teams:read",
+            ApiKeyScope::TeamsWrite => "teams:write",
+            ApiKeyScope::ActivitiesRead => "activities:read",
+            ApiKeyScope::ActivitiesWrite => "activities:write",
+            ApiKeyScope::InvitationsRead => "invitations:read",
+            ApiKeyScope::InvitationsWrite => "invitations:write",
+            ApiKeyScope::OrganizationsRead => "organizations:read",
+            ApiKeyScope::OrganizationsWrite => "organizations:write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        ApiKeyScope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == value)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKey {
+    pub id: Option<String>,
+    pub user_id: Option<String>,
+    pub name: Option<String>,
+    pub prefix: Option<String>,
+
+    pub scopes: Vec<ApiKeyScope>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub last_used_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl ApiKey {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+}
+
+impl DatabaseResource for ApiKey {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        let scopes: String = row.get("scopes");
+        Ok(ApiKey {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            name: row.get("name"),
+            prefix: row.get("prefix"),
+            scopes: parse_scopes(&scopes).unwrap_or_default(),
+            last_used_at: row.get("last_used_at"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
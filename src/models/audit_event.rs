@@ -0,0 +1,83 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum AuditError {
+    AuditEventsNotFound,
+}
+
+impl std::fmt::Display for AuditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditError::AuditEventsNotFound => write!(f, "Audit events not found"),
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+/// A security-relevant action. `user_id` is the account the event belongs
+/// to, `actor_id` whoever performed it, and `team_id` is set for team
+/// actions. Rows are never updated or deleted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub id: Option<String>,
+    pub actor_id: Option<String>,
+    pub user_id: Option<String>,
+    pub team_id: Option<String>,
+    pub action: String,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Value,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+impl DatabaseResource for AuditEvent {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(AuditEvent {
+            id: row.get("id"),
+            actor_id: row.get("actor_id"),
+            user_id: row.get("user_id"),
+            team_id: row.get("team_id"),
+            action: row.get("action"),
+            target_type: row.get("target_type"),
+            target_id: row.get("target_id"),
+            ip_address: row.get("ip_address"),
+            user_agent: row.get("user_agent"),
+            details: row.get("details"),
+            created_at: row.get("created_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        false
+    }
+
+    fn is_updatable() -> bool {
+        false
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
@@ -1,8 +1,9 @@
 use crate::database::traits::DatabaseResource;
+use crate::utils::sessions::access_token_lifetime;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, Error as SqlxError, Row};
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum AuthenticationError {
@@ -16,6 +17,15 @@ pub enum AuthenticationError {
     TokenExpired,
     RegistrationFailed,
     InvalidRequest,
+    SecondFactorRequired,
+    InvalidSecondFactor,
+    TwoFactorAlreadyEnabled,
+    TwoFactorNotEnabled,
+    RefreshTokenReused,
+    TooManyAttempts,
+    AccountLocked,
+    InsufficientScope,
+    NotAdmin,
 }
 
 impl std::fmt::Display for AuthenticationError {
@@ -31,6 +41,30 @@ impl std::fmt::Display for AuthenticationError {
             AuthenticationError::TokenExpired => write!(f, "Token expired"),
             AuthenticationError::RegistrationFailed => write!(f, "Registration failed"),
             AuthenticationError::InvalidRequest => write!(f, "Invalid request"),
+            AuthenticationError::SecondFactorRequired => write!(f, "Second factor required"),
+            AuthenticationError::InvalidSecondFactor => write!(f, "Invalid second factor"),
+            AuthenticationError::TwoFactorAlreadyEnabled => {
+                write!(f, "Two-factor authentication already enabled")
+            }
+            AuthenticationError::TwoFactorNotEnabled => {
+                write!(f, "Two-factor authentication not enabled")
+            }
+            AuthenticationError::RefreshTokenReused => {
+                write!(f, "Refresh token already used, session revoked")
+            }
+            AuthenticationError::TooManyAttempts => {
+                write!(f, "Too many failed attempts, try again later")
+            }
+            AuthenticationError::AccountLocked => {
+                write!(
+                    f,
+                    "Account temporarily locked after too many failed attempts"
+                )
+            }
+            AuthenticationError::InsufficientScope => {
+                write!(f, "API key is missing the scope required for this request")
+            }
+            AuthenticationError::NotAdmin => write!(f, "Administrator access required"),
         }
     }
 }
@@ -43,6 +77,15 @@ pub struct Authentication {
     pub id: String,
     pub user_id: String,
     pub token: String,
+    pub second_factor_pending: bool,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub last_seen_at: Option<OffsetDateTime>,
 
     #[serde(
         serialize_with = "serialize_offset_date_time",
@@ -75,6 +118,10 @@ impl DatabaseResource for Authentication {
             id: row.get("id"),
             user_id: row.get("user_id"),
             token: row.get("token"),
+            second_factor_pending: row.get("second_factor_pending"),
+            user_agent: row.get("user_agent"),
+            ip_address: row.get("ip_address"),
+            last_seen_at: row.get("last_seen_at"),
             expires_at: row.get("expires_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
@@ -101,6 +148,10 @@ impl DatabaseResource for Authentication {
     fn is_expirable() -> bool {
         true
     }
+
+    fn lifetime() -> Duration {
+        access_token_lifetime()
+    }
 }
 
 fn serialize_offset_date_time<S>(
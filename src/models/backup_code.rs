@@ -34,7 +34,6 @@ impl BackupCodeError {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BackupCode {
     pub id: Option<String>,
-    pub code: Option<String>,
     pub user_id: Option<String>,
 
     #[serde(
@@ -60,7 +59,6 @@ impl DatabaseResource for BackupCode {
     fn from_row(row: &PgRow) -> Result<Self, Error> {
         Ok(Self {
             id: row.get("id"),
-            code: row.get("code"),
             user_id: row.get("user_id"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
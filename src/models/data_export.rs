@@ -0,0 +1,157 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::data_exports::data_export_lifetime;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::{Duration, OffsetDateTime};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum DataExportError {
+    NotFound,
+    CreationFailed,
+    NotReady,
+    Expired,
+}
+
+impl std::fmt::Display for DataExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataExportError::NotFound => write!(f, "Data export not found"),
+            DataExportError::CreationFailed => write!(f, "Data export creation failed"),
+            DataExportError::NotReady => write!(f, "Data export is not ready yet"),
+            DataExportError::Expired => write!(f, "Data export expired"),
+        }
+    }
+}
+
+impl std::error::Error for DataExportError {}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataExportStatus {
+    Pending,
+    Running,
+    Ready,
+    Failed,
+}
+
+impl DataExportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataExportStatus::Pending => "pending",
+            DataExportStatus::Running => "running",
+            DataExportStatus::Ready => "ready",
+            DataExportStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(DataExportStatus::Pending),
+            "running" => Some(DataExportStatus::Running),
+            "ready" => Some(DataExportStatus::Ready),
+            "failed" => Some(DataExportStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// An archive of everything held about a user, built in the background and
+/// kept on disk until `expires_at`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DataExport {
+    pub id: Option<String>,
+    pub user_id: Option<String>,
+    pub status: DataExportStatus,
+
+    #[serde(skip_serializing)]
+    pub file_name: Option<String>,
+    pub size_bytes: Option<i64>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub completed_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl DataExport {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        matches!(
+            self.status,
+            DataExportStatus::Pending | DataExportStatus::Running
+        )
+    }
+}
+
+impl DatabaseResource for DataExport {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        let status: String = row.get("status");
+        Ok(DataExport {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            status: DataExportStatus::parse(&status).unwrap_or(DataExportStatus::Failed),
+            file_name: row.get("file_name"),
+            size_bytes: row.get("size_bytes"),
+            completed_at: row.get("completed_at"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        false
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        true
+    }
+
+    fn lifetime() -> Duration {
+        data_export_lifetime()
+    }
+}
@@ -1,9 +1,10 @@
 use crate::database::traits::DatabaseResource;
 use crate::models::team_role::TeamRole;
+use crate::utils::invitations::invitation_lifetime;
 use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, Error, Row};
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum InvitationError {
@@ -11,6 +12,11 @@ pub enum InvitationError {
     InvitationCreationFailed,
     InvitationUpdateFailed,
     InvitationDeletionFailed,
+    InvitationAlreadyAnswered,
+    InvitationExpired,
+    InvitationRevoked,
+    InvitationAlreadyPending,
+    InviteeAlreadyMember,
 }
 
 impl std::fmt::Display for InvitationError {
@@ -20,16 +26,30 @@ impl std::fmt::Display for InvitationError {
             InvitationError::InvitationCreationFailed => write!(f, "Invitation creation failed"),
             InvitationError::InvitationUpdateFailed => write!(f, "Invitation update failed"),
             InvitationError::InvitationDeletionFailed => write!(f, "Invitation deletion failed"),
+            InvitationError::InvitationAlreadyAnswered => {
+                write!(f, "Invitation has already been accepted or rejected")
+            }
+            InvitationError::InvitationExpired => write!(f, "Invitation has expired"),
+            InvitationError::InvitationRevoked => write!(f, "Invitation has been revoked"),
+            InvitationError::InvitationAlreadyPending => write!(
+                f,
+                "This user already has a pending invitation to the team; resend it instead"
+            ),
+            InvitationError::InviteeAlreadyMember => {
+                write!(f, "This user is already a member of the team")
+            }
         }
     }
 }
 
 impl std::error::Error for InvitationError {}
 
+/// An invitation for `user_id` to join a team, sent by `invited_by`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Invitation {
     pub id: Option<String>,
     pub user_id: Option<String>,
+    pub invited_by: Option<String>,
     pub team_id: Option<String>,
     pub team_role: Option<TeamRole>,
 
@@ -56,6 +76,18 @@ pub struct Invitation {
         deserialize_with = "deserialize_offset_date_time"
     )]
     pub rejected_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub revoked_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
 }
 
 impl Invitation {
@@ -66,6 +98,20 @@ impl Invitation {
     pub fn is_rejected(&self) -> bool {
         self.rejected_at.is_some()
     }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+
+    /// Still waiting for an answer, whether or not it has expired.
+    pub fn is_pending(&self) -> bool {
+        !self.is_accepted() && !self.is_rejected() && !self.is_revoked()
+    }
 }
 
 impl DatabaseResource for Invitation {
@@ -73,12 +119,17 @@ impl DatabaseResource for Invitation {
         Ok(Invitation {
             id: row.get("id"),
             user_id: row.get("user_id"),
+            invited_by: row.get("invited_by"),
             team_id: row.get("team_id"),
-            team_role: row.get("team_role"),
+            team_role: row
+                .get::<Option<String>, _>("team_role")
+                .and_then(|role| role.parse().ok()),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
             accepted_at: row.get("accepted_at"),
             rejected_at: row.get("rejected_at"),
+            revoked_at: row.get("revoked_at"),
+            expires_at: row.get("expires_at"),
         })
     }
 
@@ -99,6 +150,10 @@ impl DatabaseResource for Invitation {
     }
 
     fn is_expirable() -> bool {
-        false
+        true
+    }
+
+    fn lifetime() -> Duration {
+        invitation_lifetime()
     }
 }
@@ -0,0 +1,153 @@
+use crate::database::traits::DatabaseResource;
+use crate::models::team_role::TeamRole;
+use crate::utils::join_links::join_link_lifetime;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::{Duration, OffsetDateTime};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum JoinLinkError {
+    JoinLinkNotFound,
+    JoinLinkCreationFailed,
+    JoinLinkUpdateFailed,
+    JoinLinkExpired,
+    JoinLinkRevoked,
+    JoinLinkExhausted,
+    InvalidMaxUses,
+    InvalidExpiry,
+    AlreadyTeamMember,
+    JoinRequestAlreadyPending,
+}
+
+impl std::fmt::Display for JoinLinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinLinkError::JoinLinkNotFound => write!(f, "Join link not found"),
+            JoinLinkError::JoinLinkCreationFailed => write!(f, "Join link creation failed"),
+            JoinLinkError::JoinLinkUpdateFailed => write!(f, "Join link update failed"),
+            JoinLinkError::JoinLinkExpired => write!(f, "Join link has expired"),
+            JoinLinkError::JoinLinkRevoked => write!(f, "Join link has been revoked"),
+            JoinLinkError::JoinLinkExhausted => {
+                write!(f, "Join link has reached its maximum number of uses")
+            }
+            JoinLinkError::InvalidMaxUses => write!(f, "Maximum uses must be at least 1"),
+            JoinLinkError::InvalidExpiry => write!(f, "Expiry must be in the future"),
+            JoinLinkError::AlreadyTeamMember => {
+                write!(f, "You are already a member of this team")
+            }
+            JoinLinkError::JoinRequestAlreadyPending => {
+                write!(f, "You already have a pending request to join this team")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JoinLinkError {}
+
+/// A shareable code that lets any signed in user join a team with
+/// `team_role`, or ask to when `requires_approval` is set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinLink {
+    pub id: Option<String>,
+    pub team_id: Option<String>,
+    pub created_by: Option<String>,
+    pub code: Option<String>,
+    pub team_role: Option<TeamRole>,
+    pub requires_approval: bool,
+    pub max_uses: Option<i32>,
+    pub use_count: i32,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub revoked_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl JoinLink {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.max_uses
+            .is_some_and(|max_uses| self.use_count >= max_uses)
+    }
+}
+
+impl DatabaseResource for JoinLink {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(JoinLink {
+            id: row.get("id"),
+            team_id: row.get("team_id"),
+            created_by: row.get("created_by"),
+            code: row.get("code"),
+            team_role: row
+                .get::<Option<String>, _>("team_role")
+                .and_then(|role| role.parse().ok()),
+            requires_approval: row.get("requires_approval"),
+            max_uses: row.get("max_uses"),
+            use_count: row.get("use_count"),
+            expires_at: row.get("expires_at"),
+            revoked_at: row.get("revoked_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        true
+    }
+
+    fn lifetime() -> Duration {
+        join_link_lifetime()
+    }
+}
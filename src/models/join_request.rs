@@ -0,0 +1,143 @@
+use crate::database::traits::DatabaseResource;
+use crate::models::team_role::TeamRole;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum JoinRequestError {
+    JoinRequestNotFound,
+    JoinRequestCreationFailed,
+    JoinRequestUpdateFailed,
+    JoinRequestAlreadyDecided,
+    JoinRequestAlreadyPending,
+    RequesterAlreadyMember,
+    TeamNotDiscoverable,
+    MessageTooLong,
+}
+
+impl std::fmt::Display for JoinRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinRequestError::JoinRequestNotFound => write!(f, "Join request not found"),
+            JoinRequestError::JoinRequestCreationFailed => {
+                write!(f, "Join request creation failed")
+            }
+            JoinRequestError::JoinRequestUpdateFailed => write!(f, "Join request update failed"),
+            JoinRequestError::JoinRequestAlreadyDecided => {
+                write!(f, "Join request has already been approved or denied")
+            }
+            JoinRequestError::JoinRequestAlreadyPending => {
+                write!(f, "You already have a pending request to join this team")
+            }
+            JoinRequestError::RequesterAlreadyMember => {
+                write!(f, "The requester is already a member of the team")
+            }
+            JoinRequestError::TeamNotDiscoverable => {
+                write!(f, "Team not found or not open to join requests")
+            }
+            JoinRequestError::MessageTooLong => write!(
+                f,
+                "Message must be at most {} characters",
+                JOIN_REQUEST_MESSAGE_MAX_LENGTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for JoinRequestError {}
+
+pub const JOIN_REQUEST_MESSAGE_MAX_LENGTH: usize = 1000;
+
+/// A user asking to join a team, waiting for an admin or manager to
+/// approve or deny it. Requests come from a join link that requires
+/// approval or straight from the team directory.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinRequest {
+    pub id: Option<String>,
+    pub team_id: Option<String>,
+    pub user_id: Option<String>,
+    pub join_link_id: Option<String>,
+    pub team_role: Option<TeamRole>,
+    pub message: Option<String>,
+    pub decided_by: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub approved_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub denied_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl JoinRequest {
+    pub fn is_pending(&self) -> bool {
+        self.approved_at.is_none() && self.denied_at.is_none()
+    }
+}
+
+impl DatabaseResource for JoinRequest {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(JoinRequest {
+            id: row.get("id"),
+            team_id: row.get("team_id"),
+            user_id: row.get("user_id"),
+            join_link_id: row.get("join_link_id"),
+            team_role: row
+                .get::<Option<String>, _>("team_role")
+                .and_then(|role| role.parse().ok()),
+            message: row.get("message"),
+            decided_by: row.get("decided_by"),
+            approved_at: row.get("approved_at"),
+            denied_at: row.get("denied_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
@@ -1,10 +1,24 @@
 pub mod activity;
+pub mod api_key;
+pub mod audit_event;
 pub mod authentication;
 pub mod backup_code;
 pub mod capability;
+pub mod data_export;
 pub mod invitation;
+pub mod join_link;
+pub mod join_request;
+pub mod oidc_login;
+pub mod organization;
+pub mod organization_role;
+pub mod organization_user;
+pub mod password_reset;
+pub mod permission;
+pub mod refresh_token;
 pub mod team;
 pub mod team_role;
 pub mod team_user;
+pub mod totp_secret;
 pub mod user;
+pub mod user_identity;
 pub mod user_skill;
@@ -0,0 +1,96 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::oidc::oidc_login_lifetime;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::{Duration, OffsetDateTime};
+
+/// A sign-on that was sent to the identity provider and hasn't come back
+/// yet. The state is stored hashed and only used for lookup; `user_id` is set
+/// when an existing user is linking a new identity rather than logging in.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcLogin {
+    pub id: Option<String>,
+
+    #[serde(skip_serializing)]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing)]
+    pub code_verifier: Option<String>,
+
+    pub user_id: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl OidcLogin {
+    pub fn is_used(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+}
+
+impl DatabaseResource for OidcLogin {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(OidcLogin {
+            id: row.get("id"),
+            nonce: row.get("nonce"),
+            code_verifier: row.get("code_verifier"),
+            user_id: row.get("user_id"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        true
+    }
+
+    fn lifetime() -> Duration {
+        oidc_login_lifetime()
+    }
+}
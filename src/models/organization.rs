@@ -0,0 +1,132 @@
+use crate::database::traits::DatabaseResource;
+use crate::models::authentication::AuthenticationError;
+use crate::models::permission::PermissionError;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum OrganizationError {
+    OrganizationNotFound,
+    OrganizationCreationFailed,
+    OrganizationUpdateFailed,
+    OrganizationRequired,
+    NotOrganizationMember,
+    AlreadyOrganizationMember,
+    LastOrganizationOwner,
+    MemberOwnsTeams,
+}
+
+impl std::fmt::Display for OrganizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrganizationError::OrganizationNotFound => write!(f, "Organization not found"),
+            OrganizationError::OrganizationCreationFailed => {
+                write!(f, "Organization creation failed")
+            }
+            OrganizationError::OrganizationUpdateFailed => write!(f, "Organization update failed"),
+            OrganizationError::OrganizationRequired => write!(
+                f,
+                "You belong to several organizations or none; pick the organization for the team"
+            ),
+            OrganizationError::NotOrganizationMember => {
+                write!(f, "User is not a member of the organization")
+            }
+            OrganizationError::AlreadyOrganizationMember => {
+                write!(f, "User is already a member of the organization")
+            }
+            OrganizationError::LastOrganizationOwner => {
+                write!(f, "An organization must keep at least one owner")
+            }
+            OrganizationError::MemberOwnsTeams => write!(
+                f,
+                "The user owns teams in this organization; transfer them first"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OrganizationError {}
+
+/// Why an `OrganizationAccess` guard turned a request away.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum OrganizationAccessError {
+    Authentication(AuthenticationError),
+    Organization(OrganizationError),
+    Permission(PermissionError),
+}
+
+impl std::fmt::Display for OrganizationAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrganizationAccessError::Authentication(err) => err.fmt(f),
+            OrganizationAccessError::Organization(err) => err.fmt(f),
+            OrganizationAccessError::Permission(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OrganizationAccessError {}
+
+/// A group of users and the teams they form, such as a department or a
+/// customer. People only see users and teams that share an organization
+/// with them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Organization {
+    pub id: Option<String>,
+    pub organization_name: Option<String>,
+    pub organization_description: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl DatabaseResource for Organization {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(Organization {
+            id: row.get("id"),
+            organization_name: row.get("organization_name"),
+            organization_description: row.get("organization_description"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
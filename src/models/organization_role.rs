@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A user's standing in an organization. Owners and admins manage its
+/// members; only owners can hand out or take away ownership.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum OrganizationRole {
+    Owner,
+    Admin,
+    Member,
+}
+
+impl OrganizationRole {
+    pub fn is_admin(&self) -> bool {
+        matches!(self, OrganizationRole::Owner | OrganizationRole::Admin)
+    }
+}
+
+impl std::fmt::Display for OrganizationRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrganizationRole::Owner => write!(f, "owner"),
+            OrganizationRole::Admin => write!(f, "admin"),
+            OrganizationRole::Member => write!(f, "member"),
+        }
+    }
+}
+
+impl FromStr for OrganizationRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "owner" => Ok(OrganizationRole::Owner),
+            "admin" => Ok(OrganizationRole::Admin),
+            "member" => Ok(OrganizationRole::Member),
+            _ => Err("Invalid organization role".to_string()),
+        }
+    }
+}
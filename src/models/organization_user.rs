@@ -0,0 +1,70 @@
+use crate::database::traits::DatabaseResource;
+use crate::models::organization_role::OrganizationRole;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+/// A user's membership of an organization.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganizationUser {
+    pub id: Option<String>,
+    pub organization_id: Option<String>,
+    pub user_id: Option<String>,
+    pub organization_role: Option<OrganizationRole>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl DatabaseResource for OrganizationUser {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(OrganizationUser {
+            id: row.get("id"),
+            organization_id: row.get("organization_id"),
+            user_id: row.get("user_id"),
+            organization_role: row
+                .get::<Option<String>, _>("organization_role")
+                .and_then(|role| role.parse().ok()),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
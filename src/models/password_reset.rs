@@ -0,0 +1,108 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::password_resets::password_reset_lifetime;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::{Duration, OffsetDateTime};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PasswordResetError {
+    TokenNotValid,
+    TokenExpired,
+    TokenAlreadyUsed,
+    ResetFailed,
+}
+
+impl std::fmt::Display for PasswordResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordResetError::TokenNotValid => write!(f, "Reset token not valid"),
+            PasswordResetError::TokenExpired => write!(f, "Reset token expired"),
+            PasswordResetError::TokenAlreadyUsed => write!(f, "Reset token already used"),
+            PasswordResetError::ResetFailed => write!(f, "Password reset failed"),
+        }
+    }
+}
+
+impl std::error::Error for PasswordResetError {}
+
+/// A single-use token for setting a new password without a session. Only the
+/// token's hash is stored, and a used token is kept with `archived_at` set.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordReset {
+    pub id: Option<String>,
+    pub user_id: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl PasswordReset {
+    pub fn is_used(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+}
+
+impl DatabaseResource for PasswordReset {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(PasswordReset {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        true
+    }
+
+    fn lifetime() -> Duration {
+        password_reset_lifetime()
+    }
+}
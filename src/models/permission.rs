@@ -0,0 +1,217 @@
+use crate::models::api_key::ApiKeyScope;
+use crate::models::authentication::AuthenticationError;
+use crate::models::team::TeamError;
+use crate::models::team_role::TeamRole;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum PermissionError {
+    NotTeamMember,
+    PermissionDenied,
+}
+
+impl std::fmt::Display for PermissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionError::NotTeamMember => write!(f, "You are not a member of this team"),
+            PermissionError::PermissionDenied => {
+                write!(f, "Your team role does not allow this action")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PermissionError {}
+
+/// Why a `TeamAccess` guard turned a request away.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum TeamAccessError {
+    Authentication(AuthenticationError),
+    Team(TeamError),
+    Permission(PermissionError),
+}
+
+impl std::fmt::Display for TeamAccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TeamAccessError::Authentication(err) => err.fmt(f),
+            TeamAccessError::Team(err) => err.fmt(f),
+            TeamAccessError::Permission(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TeamAccessError {}
+
+/// Actions on a team and its activities. The team owner holds every
+/// permission; everyone else gets what their team role grants.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    #[serde(rename = "team.read")]
+    TeamRead,
+    #[serde(rename = "team.update")]
+    TeamUpdate,
+    #[serde(rename = "team.delete")]
+    TeamDelete,
+    #[serde(rename = "team.invite")]
+    TeamInvite,
+    #[serde(rename = "team.audit")]
+    TeamAudit,
+    #[serde(rename = "team.leave")]
+    TeamLeave,
+    #[serde(rename = "team.transfer")]
+    TeamTransfer,
+    #[serde(rename = "team.take_ownership")]
+    TeamTakeOwnership,
+    #[serde(rename = "invitation.revoke")]
+    InvitationRevoke,
+    #[serde(rename = "join_link.manage")]
+    JoinLinkManage,
+    #[serde(rename = "join_request.decide")]
+    JoinRequestDecide,
+    #[serde(rename = "member.update")]
+    MemberUpdate,
+    #[serde(rename = "member.remove")]
+    MemberRemove,
+    #[serde(rename = "activity.read")]
+    ActivityRead,
+    #[serde(rename = "activity.create")]
+    ActivityCreate,
+    #[serde(rename = "activity.update")]
+    ActivityUpdate,
+    #[serde(rename = "activity.delete")]
+    ActivityDelete,
+    #[serde(rename = "activity.assign")]
+    ActivityAssign,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::TeamRead => "team.read",
+            Permission::TeamUpdate => "team.update",
+            Permission::TeamDelete => "team.delete",
+            Permission::TeamInvite => "team.invite",
+            Permission::TeamAudit => "team.audit",
+            Permission::TeamLeave => "team.leave",
+            Permission::TeamTransfer => "team.transfer",
+            Permission::TeamTakeOwnership => "team.take_ownership",
+            Permission::InvitationRevoke => "invitation.revoke",
+            Permission::JoinLinkManage => "join_link.manage",
+            Permission::JoinRequestDecide => "join_request.decide",
+            Permission::MemberUpdate => "member.update",
+            Permission::MemberRemove => "member.remove",
+            Permission::ActivityRead => "activity.read",
+            Permission::ActivityCreate => "activity.create",
+            Permission::ActivityUpdate => "activity.update",
+            Permission::ActivityDelete => "activity.delete",
+            Permission::ActivityAssign => "activity.assign",
+        }
+    }
+
+    /// Team roles granted this permission. An empty list means only the
+    /// owner may do it.
+    pub fn roles(&self) -> &'static [TeamRole] {
+        match self {
+            Permission::TeamRead | Permission::TeamLeave | Permission::ActivityRead => {
+                &[TeamRole::Admin, TeamRole::Manager, TeamRole::Member]
+            }
+            Permission::TeamInvite
+            | Permission::JoinRequestDecide
+            | Permission::ActivityCreate
+            | Permission::ActivityUpdate
+            | Permission::ActivityAssign => &[TeamRole::Admin, TeamRole::Manager],
+            Permission::TeamUpdate
+            | Permission::TeamAudit
+            | Permission::InvitationRevoke
+            | Permission::JoinLinkManage
+            | Permission::MemberUpdate
+            | Permission::MemberRemove
+            | Permission::TeamTakeOwnership
+            | Permission::ActivityDelete => &[TeamRole::Admin],
+            Permission::TeamDelete | Permission::TeamTransfer => &[],
+        }
+    }
+
+    pub fn allows(&self, role: &TeamRole) -> bool {
+        self.roles().contains(role)
+    }
+
+    /// Whether the permission can still be used once the team is archived.
+    /// Archived teams are read-only; deletion stays open so the owner can
+    /// restore them.
+    pub fn applies_to_archived(&self) -> bool {
+        matches!(
+            self,
+            Permission::TeamRead
+                | Permission::TeamAudit
+                | Permission::TeamDelete
+                | Permission::ActivityRead
+        )
+    }
+
+    /// The scope an API key needs on top of the role. `None` keeps the
+    /// action to sessions.
+    pub fn scope(&self) -> Option<ApiKeyScope> {
+        match self {
+            Permission::TeamRead => Some(ApiKeyScope::TeamsRead),
+            Permission::TeamUpdate
+            | Permission::TeamDelete
+            | Permission::TeamInvite
+            | Permission::TeamLeave
+            | Permission::InvitationRevoke
+            | Permission::JoinLinkManage
+            | Permission::JoinRequestDecide
+            | Permission::MemberUpdate
+            | Permission::MemberRemove => Some(ApiKeyScope::TeamsWrite),
+            Permission::TeamAudit | Permission::TeamTransfer | Permission::TeamTakeOwnership => {
+                None
+            }
+            Permission::ActivityRead => Some(ApiKeyScope::ActivitiesRead),
+            Permission::ActivityCreate
+            | Permission::ActivityUpdate
+            | Permission::ActivityDelete
+            | Permission::ActivityAssign => Some(ApiKeyScope::ActivitiesWrite),
+        }
+    }
+}
+
+/// Names a `Permission` at the type level so routes can ask for it through
+/// the `TeamAccess` guard, e.g. `TeamAccess<TeamUpdate>`.
+pub trait TeamPermission: Send + Sync + 'static {
+    const PERMISSION: Permission;
+}
+
+macro_rules! team_permissions {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[derive(Debug, Clone, Copy)]
+            pub struct $name;
+
+            impl TeamPermission for $name {
+                const PERMISSION: Permission = Permission::$name;
+            }
+        )*
+    };
+}
+
+team_permissions!(
+    TeamRead,
+    TeamUpdate,
+    TeamDelete,
+    TeamInvite,
+    TeamAudit,
+    TeamLeave,
+    TeamTransfer,
+    TeamTakeOwnership,
+    InvitationRevoke,
+    JoinLinkManage,
+    JoinRequestDecide,
+    MemberUpdate,
+    MemberRemove,
+    ActivityRead,
+    ActivityCreate,
+    ActivityUpdate,
+    ActivityDelete,
+    ActivityAssign,
+);
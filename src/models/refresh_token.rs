@@ -0,0 +1,89 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::sessions::refresh_token_lifetime;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::{Duration, OffsetDateTime};
+
+/// A single-use token that trades for a new access token. Used tokens are
+/// kept with `archived_at` set so that replaying one can be detected.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshToken {
+    pub id: Option<String>,
+    pub authentication_id: Option<String>,
+    pub token: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub expires_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl RefreshToken {
+    pub fn is_used(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at <= OffsetDateTime::now_utc())
+    }
+}
+
+impl DatabaseResource for RefreshToken {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(RefreshToken {
+            id: row.get("id"),
+            authentication_id: row.get("authentication_id"),
+            token: row.get("token"),
+            expires_at: row.get("expires_at"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        true
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        true
+    }
+
+    fn lifetime() -> Duration {
+        refresh_token_lifetime()
+    }
+}
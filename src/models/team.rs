@@ -4,12 +4,19 @@ use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, Error, Row};
 use time::OffsetDateTime;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TeamError {
     TeamNotFound,
     TeamCreationFailed,
     TeamUpdateFailed,
     TeamDeletionFailed,
+    InvalidOwner,
+    NomineeNotAdmin,
+    NoOwnershipOffer,
+    ParentTeamNotFound,
+    TeamHierarchyCycle,
+    TeamArchived,
+    TeamNotArchived,
 }
 
 impl std::fmt::Display for TeamError {
@@ -19,6 +26,19 @@ impl std::fmt::Display for TeamError {
             TeamError::TeamCreationFailed => write!(f, "Team creation failed"),
             TeamError::TeamUpdateFailed => write!(f, "Team update failed"),
             TeamError::TeamDeletionFailed => write!(f, "Team deletion failed"),
+            TeamError::InvalidOwner => write!(f, "New owner must be a member of the team"),
+            TeamError::NomineeNotAdmin => {
+                write!(f, "Ownership can only be offered to another team admin")
+            }
+            TeamError::NoOwnershipOffer => {
+                write!(f, "There is no pending ownership transfer for you")
+            }
+            TeamError::ParentTeamNotFound => write!(f, "Parent team not found"),
+            TeamError::TeamHierarchyCycle => {
+                write!(f, "A team can't sit under itself or one of its sub-teams")
+            }
+            TeamError::TeamArchived => write!(f, "Team is archived and can't be changed"),
+            TeamError::TeamNotArchived => write!(f, "Team is not archived"),
         }
     }
 }
@@ -30,8 +50,23 @@ impl std::error::Error for TeamError {}
 pub struct Team {
     pub id: Option<String>,
     pub owner_id: Option<String>,
+    pub organization_id: Option<String>,
+    /// The team this one sits under, if any.
+    pub parent_team_id: Option<String>,
+    /// Roles held on the parent team also count on this one.
+    pub inherits_permissions: bool,
     pub team_name: Option<String>,
     pub team_description: Option<String>,
+    /// Listed in the team directory, where anyone can ask to join.
+    pub is_discoverable: bool,
+    /// The admin the owner has offered the team to, until they accept.
+    pub pending_owner_id: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub ownership_offered_at: Option<OffsetDateTime>,
 
     #[serde(
         serialize_with = "serialize_offset_date_time",
@@ -50,6 +85,13 @@ pub struct Team {
         deserialize_with = "deserialize_offset_date_time"
     )]
     pub archived_at: Option<OffsetDateTime>,
+
+    /// When an archived team is deleted for good.
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub purge_after: Option<OffsetDateTime>,
 }
 
 impl DatabaseResource for Team {
@@ -57,11 +99,18 @@ impl DatabaseResource for Team {
         Ok(Team {
             id: row.get("id"),
             owner_id: row.get("owner_id"),
+            organization_id: row.get("organization_id"),
+            parent_team_id: row.get("parent_team_id"),
+            inherits_permissions: row.get("inherits_permissions"),
             team_name: row.get("team_name"),
             team_description: row.get("team_description"),
+            is_discoverable: row.get("is_discoverable"),
+            pending_owner_id: row.get("pending_owner_id"),
+            ownership_offered_at: row.get("ownership_offered_at"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
             archived_at: row.get("archived_at"),
+            purge_after: row.get("purge_after"),
         })
     }
 
@@ -1,12 +1,19 @@
 use crate::database::traits::DatabaseResource;
+use crate::models::team_role::TeamRole;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
 use rocket::serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum TeamUserError {
     TeamUserNotFound,
     TeamUserCreationFailed,
     TeamUserDeletionFailed,
+    TeamUserUpdateFailed,
+    LastTeamAdmin,
+    TeamOwnerMembership,
+    InvalidAssignee,
 }
 
 impl std::fmt::Display for TeamUserError {
@@ -15,38 +22,81 @@ impl std::fmt::Display for TeamUserError {
             TeamUserError::TeamUserNotFound => write!(f, "Team user not found"),
             TeamUserError::TeamUserCreationFailed => write!(f, "Team user creation failed"),
             TeamUserError::TeamUserDeletionFailed => write!(f, "Team user deletion failed"),
+            TeamUserError::TeamUserUpdateFailed => write!(f, "Team user update failed"),
+            TeamUserError::LastTeamAdmin => {
+                write!(f, "A team must keep at least one admin")
+            }
+            TeamUserError::TeamOwnerMembership => write!(
+                f,
+                "The team owner can't be demoted, removed or leave; transfer ownership first"
+            ),
+            TeamUserError::InvalidAssignee => {
+                write!(f, "Activities can only be handed to another team member")
+            }
         }
     }
 }
 
+/// A user's membership of a team. The owner doesn't need one; everyone else
+/// gets one when they accept an invitation or join through a link.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct TeamUser {
+    pub id: Option<String>,
     pub team_id: Option<String>,
     pub user_id: Option<String>,
+    pub team_role: Option<TeamRole>,
+    pub join_link_id: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
 }
 
 impl DatabaseResource for TeamUser {
     fn from_row(row: &PgRow) -> Result<Self, Error> {
         Ok(TeamUser {
+            id: row.get("id"),
             team_id: row.get("team_id"),
             user_id: row.get("user_id"),
+            team_role: row
+                .get::<Option<String>, _>("team_role")
+                .and_then(|role| role.parse().ok()),
+            join_link_id: row.get("join_link_id"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
         })
     }
 
     fn has_id() -> bool {
-        false
+        true
     }
 
     fn is_archivable() -> bool {
-        false
+        true
     }
 
     fn is_updatable() -> bool {
-        false
+        true
     }
 
     fn is_creatable() -> bool {
-        false
+        true
     }
 
     fn is_expirable() -> bool {
@@ -4,12 +4,14 @@ use rocket::serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgRow, Error, Row};
 use time::OffsetDateTime;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum UserError {
     UserNotFound,
     UserCreationFailed,
     UserUpdateFailed,
     UserDeletionFailed,
+    OwnsTeams,
+    LastTeamAdmin,
 }
 
 impl std::fmt::Display for UserError {
@@ -19,6 +21,14 @@ impl std::fmt::Display for UserError {
             UserError::UserCreationFailed => write!(f, "User creation failed"),
             UserError::UserUpdateFailed => write!(f, "User update failed"),
             UserError::UserDeletionFailed => write!(f, "User deletion failed"),
+            UserError::OwnsTeams => write!(
+                f,
+                "User still owns teams, transfer or archive them before deleting the account"
+            ),
+            UserError::LastTeamAdmin => write!(
+                f,
+                "User is the last admin of a team, make someone else admin before deleting the account"
+            ),
         }
     }
 }
@@ -32,6 +42,15 @@ pub struct User {
     pub first_name: Option<String>,
     pub last_name: Option<String>,
     pub username: Option<String>,
+    pub email: Option<String>,
+
+    /// System administrators can manage every account through
+    /// `/api/admin/users`.
+    #[serde(default)]
+    pub is_admin: bool,
+
+    #[serde(skip)]
+    pub password_hash: Option<String>,
 
     #[serde(
         serialize_with = "serialize_offset_date_time",
@@ -50,6 +69,14 @@ pub struct User {
         deserialize_with = "deserialize_offset_date_time"
     )]
     pub archived_at: Option<OffsetDateTime>,
+
+    /// Set when the user deleted their account. It stays archived and can be
+    /// restored until then, after which it is purged for good.
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub purge_after: Option<OffsetDateTime>,
 }
 
 impl DatabaseResource for User {
@@ -59,9 +86,13 @@ impl DatabaseResource for User {
             first_name: row.get("first_name"),
             last_name: row.get("last_name"),
             username: row.get("username"),
+            email: row.get("email"),
+            is_admin: row.get("is_admin"),
+            password_hash: row.get("password_hash"),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
             archived_at: row.get("archived_at"),
+            purge_after: row.get("purge_after"),
         })
     }
 
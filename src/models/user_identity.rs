@@ -0,0 +1,103 @@
+use crate::database::traits::DatabaseResource;
+use crate::utils::time::{deserialize_offset_date_time, serialize_offset_date_time};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Error, Row};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum OidcError {
+    NotConfigured,
+    DiscoveryFailed,
+    InvalidState,
+    StateExpired,
+    TokenExchangeFailed,
+    InvalidIdToken,
+    IdentityAlreadyLinked,
+    IdentityNotFound,
+    IdentityCreationFailed,
+}
+
+impl std::fmt::Display for OidcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OidcError::NotConfigured => write!(f, "Single sign-on is not configured"),
+            OidcError::DiscoveryFailed => write!(f, "Identity provider discovery failed"),
+            OidcError::InvalidState => write!(f, "Invalid sign-on state"),
+            OidcError::StateExpired => write!(f, "Sign-on attempt expired"),
+            OidcError::TokenExchangeFailed => write!(f, "Authorization code exchange failed"),
+            OidcError::InvalidIdToken => write!(f, "Invalid ID token"),
+            OidcError::IdentityAlreadyLinked => {
+                write!(f, "Identity is already linked to another user")
+            }
+            OidcError::IdentityNotFound => write!(f, "Identity not found"),
+            OidcError::IdentityCreationFailed => write!(f, "Identity creation failed"),
+        }
+    }
+}
+
+impl std::error::Error for OidcError {}
+
+/// An account at an external identity provider, keyed by the provider's
+/// issuer and the subject it assigned the user.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    pub id: Option<String>,
+    pub user_id: Option<String>,
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    pub email: Option<String>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub created_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub updated_at: Option<OffsetDateTime>,
+
+    #[serde(
+        serialize_with = "serialize_offset_date_time",
+        deserialize_with = "deserialize_offset_date_time"
+    )]
+    pub archived_at: Option<OffsetDateTime>,
+}
+
+impl DatabaseResource for UserIdentity {
+    fn from_row(row: &PgRow) -> Result<Self, Error> {
+        Ok(UserIdentity {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            issuer: row.get("issuer"),
+            subject: row.get("subject"),
+            email: row.get("email"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            archived_at: row.get("archived_at"),
+        })
+    }
+
+    fn has_id() -> bool {
+        true
+    }
+
+    fn is_archivable() -> bool {
+        false
+    }
+
+    fn is_updatable() -> bool {
+        true
+    }
+
+    fn is_creatable() -> bool {
+        true
+    }
+
+    fn is_expirable() -> bool {
+        false
+    }
+}
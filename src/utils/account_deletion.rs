@@ -0,0 +1,205 @@
+use sqlx::{Postgres, Row, Transaction};
+use time::{Duration, OffsetDateTime};
+
+use crate::database::connection::get_connection;
+use crate::models::user::UserError;
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::jobs::{interval_from_env, spawn_periodic};
+
+const DEFAULT_GRACE_PERIOD_DAYS: i64 = 30;
+const DEFAULT_PURGE_INTERVAL_MINUTES: u64 = 60;
+
+/// How long a deleted account can still be restored, from
+/// `ACCOUNT_DELETION_GRACE_DAYS`.
+pub fn account_deletion_grace_period() -> Duration {
+    Duration::days(
+        std::env::var("ACCOUNT_DELETION_GRACE_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|days: &i64| *days >= 0)
+            .unwrap_or(DEFAULT_GRACE_PERIOD_DAYS),
+    )
+}
+
+/// Active teams where the user is the only admin left, by name. Deleting
+/// the account would leave them without one.
+const SOLE_ADMIN_TEAMS: &str = "SELECT teams.team_name FROM teams_users JOIN teams ON teams.id = teams_users.team_id WHERE teams_users.user_id = $1 AND teams_users.team_role = 'admin' AND teams_users.archived_at IS NULL AND teams.archived_at IS NULL AND NOT EXISTS (SELECT 1 FROM teams_users others JOIN users ON users.id = others.user_id WHERE others.team_id = teams.id AND others.user_id <> $1 AND others.team_role = 'admin' AND others.archived_at IS NULL AND users.archived_at IS NULL) AND NOT EXISTS (SELECT 1 FROM users WHERE users.id = teams.owner_id AND users.id <> $1 AND users.archived_at IS NULL) ORDER BY teams.team_name ASC";
+
+/// Names of the active teams the user is the last admin of.
+pub async fn sole_admin_teams(user_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    let pool = get_connection().await;
+    sqlx::query_scalar::<_, String>(SOLE_ADMIN_TEAMS)
+        .bind(user_id)
+        .fetch_all(&pool)
+        .await
+}
+
+/// Soft deletes an account: unassigns its open activities, drops its skills,
+/// team memberships and second factors, revokes its pending invitations, and
+/// archives the user until the grace period ends. Owned teams must already
+/// have been dealt with, and deletion is refused while the user is the last
+/// admin of a team. Sessions and API keys are left to the caller.
+pub async fn schedule_account_deletion(user_id: &str) -> Result<OffsetDateTime, UserError> {
+    let purge_after = OffsetDateTime::now_utc() + account_deletion_grace_period();
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting account deletion: {:?}", err);
+        UserError::UserDeletionFailed
+    })?;
+    match sqlx::query_scalar::<_, String>(SOLE_ADMIN_TEAMS)
+        .bind(user_id)
+        .fetch_all(&mut *tx)
+        .await
+    {
+        Ok(teams) if teams.is_empty() => {}
+        Ok(_) => return Err(UserError::LastTeamAdmin),
+        Err(err) => {
+            println!("Error finding teams the user is last admin of: {:?}", err);
+            return Err(UserError::UserDeletionFailed);
+        }
+    }
+    let statements = [
+        "UPDATE activities SET assigned_to = NULL, updated_at = CURRENT_TIMESTAMP WHERE assigned_to = $1 AND ended_at IS NULL",
+        "DELETE FROM user_skills WHERE user_id = $1",
+        "DELETE FROM teams_users WHERE user_id = $1",
+        "DELETE FROM join_requests WHERE user_id = $1 AND approved_at IS NULL AND denied_at IS NULL",
+        "UPDATE invitations SET revoked_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND accepted_at IS NULL AND rejected_at IS NULL AND revoked_at IS NULL",
+        "DELETE FROM backup_codes WHERE user_id = $1",
+        "DELETE FROM totp_secrets WHERE user_id = $1",
+    ];
+    for statement in statements {
+        execute(&mut tx, statement, user_id).await?;
+    }
+    if let Err(err) = sqlx::query(
+        "UPDATE users SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP, purge_after = $2 WHERE id = $1",
+    )
+    .bind(user_id)
+    .bind(purge_after)
+    .execute(&mut *tx)
+    .await
+    {
+        println!("Error archiving user: {:?}", err);
+        return Err(UserError::UserDeletionFailed);
+    }
+    tx.commit().await.map_err(|err| {
+        println!("Error committing account deletion: {:?}", err);
+        UserError::UserDeletionFailed
+    })?;
+    Ok(purge_after)
+}
+
+async fn execute(
+    tx: &mut Transaction<'_, Postgres>,
+    statement: &str,
+    user_id: &str,
+) -> Result<(), UserError> {
+    match sqlx::query(statement)
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            println!("Error running `{}`: {:?}", statement, err);
+            Err(UserError::UserDeletionFailed)
+        }
+    }
+}
+
+/// Permanently removes one account whose grace period is over, along with
+/// everything still pointing at it. Teams it owns were archived when the
+/// account was deleted and go with it; if it somehow owns an active team the
+/// purge is skipped.
+async fn purge_account(user_id: &str) -> Result<(), UserError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting account purge: {:?}", err);
+        UserError::UserDeletionFailed
+    })?;
+    let active_teams: i64 = match sqlx::query(
+        "SELECT COUNT(*) AS count FROM teams WHERE owner_id = $1 AND archived_at IS NULL",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(row) => row.get("count"),
+        Err(err) => {
+            println!("Error counting owned teams: {:?}", err);
+            return Err(UserError::UserDeletionFailed);
+        }
+    };
+    if active_teams > 0 {
+        println!("Not purging user {} who owns active teams", user_id);
+        return Err(UserError::OwnsTeams);
+    }
+
+    let statements = [
+        "DELETE FROM activities WHERE team_id IN (SELECT id FROM teams WHERE owner_id = $1)",
+        "DELETE FROM invitations WHERE team_id IN (SELECT id FROM teams WHERE owner_id = $1)",
+        "DELETE FROM join_requests WHERE team_id IN (SELECT id FROM teams WHERE owner_id = $1)",
+        "DELETE FROM teams_users WHERE team_id IN (SELECT id FROM teams WHERE owner_id = $1)",
+        "DELETE FROM join_links WHERE team_id IN (SELECT id FROM teams WHERE owner_id = $1)",
+        "DELETE FROM teams WHERE owner_id = $1",
+        "UPDATE activities SET assigned_to = NULL, updated_at = CURRENT_TIMESTAMP WHERE assigned_to = $1",
+        "DELETE FROM invitations WHERE user_id = $1",
+        "DELETE FROM join_requests WHERE user_id = $1",
+        "DELETE FROM user_skills WHERE user_id = $1",
+        "DELETE FROM teams_users WHERE user_id = $1",
+        "DELETE FROM organizations_users WHERE user_id = $1",
+        "DELETE FROM backup_codes WHERE user_id = $1",
+        "DELETE FROM totp_secrets WHERE user_id = $1",
+        "DELETE FROM authentications WHERE user_id = $1",
+        "DELETE FROM users WHERE id = $1",
+    ];
+    for statement in statements {
+        execute(&mut tx, statement, user_id).await?;
+    }
+    tx.commit().await.map_err(|err| {
+        println!("Error committing account purge: {:?}", err);
+        UserError::UserDeletionFailed
+    })
+}
+
+/// Purges every deleted account whose grace period has passed.
+pub async fn purge_expired_accounts() {
+    let pool = get_connection().await;
+    let user_ids = match sqlx::query(
+        "SELECT id FROM users WHERE archived_at IS NOT NULL AND purge_after <= CURRENT_TIMESTAMP",
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .map(|row| row.get::<String, _>("id"))
+            .collect::<Vec<String>>(),
+        Err(err) => {
+            println!("Error finding accounts to purge: {:?}", err);
+            return;
+        }
+    };
+    for user_id in user_ids {
+        match purge_account(&user_id).await {
+            Ok(_) => {
+                record_audit_event(
+                    AuditEntry::new(AuditAction::AccountPurged).target("user", &user_id),
+                )
+                .await
+            }
+            Err(err) => println!("Error purging user {}: {:?}", user_id, err),
+        }
+    }
+}
+
+/// Runs [`purge_expired_accounts`] in the background every
+/// `ACCOUNT_PURGE_INTERVAL_MINUTES`.
+pub fn spawn_account_purge() {
+    spawn_periodic(
+        interval_from_env(
+            "ACCOUNT_PURGE_INTERVAL_MINUTES",
+            DEFAULT_PURGE_INTERVAL_MINUTES,
+        ),
+        purge_expired_accounts,
+    );
+}
@@ -0,0 +1,119 @@
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::user::{User, UserError};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// Turns a search term into an `ILIKE` pattern matching it anywhere,
+/// escaping the wildcards so they are matched literally.
+pub fn search_pattern(query: &str) -> String {
+    let escaped = query
+        .trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Searches users by username, name or email. Archived users are only
+/// returned when `archived` is set, and then only archived ones.
+pub async fn search_users(
+    query: Option<&str>,
+    archived: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<Vec<User>, UserError> {
+    let pool = get_connection().await;
+    let pattern = query
+        .filter(|query| !query.trim().is_empty())
+        .map(search_pattern);
+    let limit = limit
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    let offset = offset.filter(|offset| *offset > 0).unwrap_or(0);
+    let sql = "SELECT * FROM users WHERE ($1::VARCHAR IS NULL OR username ILIKE $1 OR first_name ILIKE $1 OR last_name ILIKE $1 OR email ILIKE $1) AND (archived_at IS NOT NULL) = $2 ORDER BY username LIMIT $3 OFFSET $4";
+    match sqlx::query(sql)
+        .bind(pattern)
+        .bind(archived)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .map(User::from_row)
+            .collect::<Result<Vec<User>, _>>()
+            .map_err(|err| {
+                println!("Error reading users: {:?}", err);
+                UserError::UserNotFound
+            }),
+        Err(err) => {
+            println!("Error searching users: {:?}", err);
+            Err(UserError::UserNotFound)
+        }
+    }
+}
+
+pub async fn set_admin(user_id: &str, is_admin: bool) -> Result<User, UserError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE users SET is_admin = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(user_id)
+    .bind(is_admin)
+    .fetch_one(&pool)
+    .await
+    {
+        Ok(row) => User::from_row(&row).map_err(|_| UserError::UserUpdateFailed),
+        Err(err) => {
+            println!("Error updating admin flag: {:?}", err);
+            Err(UserError::UserUpdateFailed)
+        }
+    }
+}
+
+/// Promotes the users listed in `ADMIN_USERNAMES` (comma separated), which
+/// is how the first administrator gets created. Safe to run on every
+/// startup.
+pub async fn promote_bootstrap_admins() {
+    let usernames = std::env::var("ADMIN_USERNAMES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|username| !username.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    if usernames.is_empty() {
+        return;
+    }
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE users SET is_admin = TRUE, updated_at = CURRENT_TIMESTAMP WHERE username = ANY($1) AND NOT is_admin",
+    )
+    .bind(&usernames)
+    .execute(&pool)
+    .await
+    {
+        Ok(result) if result.rows_affected() > 0 => {
+            println!("Promoted {} bootstrap admin(s)", result.rows_affected())
+        }
+        Ok(_) => (),
+        Err(err) => println!("Error promoting bootstrap admins: {:?}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_pattern() {
+        assert_eq!(search_pattern("alice"), "%alice%");
+        assert_eq!(search_pattern("  bob "), "%bob%");
+        assert_eq!(search_pattern("50%_off"), "%50\\%\\_off%");
+        assert_eq!(search_pattern("a\\b"), "%a\\\\b%");
+    }
+}
@@ -0,0 +1,133 @@
+use rand::distr::Alphanumeric;
+use rand::{rng, Rng};
+
+use crate::database::connection::get_connection;
+use crate::database::values::DatabaseValue;
+use crate::find_one_unarchived_resource_where_fields;
+use crate::models::api_key::{ApiKey, ApiKeyScope};
+use crate::models::authentication::AuthenticationError;
+use crate::utils::token_hash::hash_token;
+
+/// Every API key starts with this, which is how the token guard tells them
+/// apart from session access tokens.
+pub const API_KEY_PREFIX: &str = "cap_";
+
+const API_KEY_SECRET_LENGTH: usize = 40;
+const API_KEY_DISPLAY_PREFIX_LENGTH: usize = 8;
+
+pub fn is_api_key(value: &str) -> bool {
+    value.starts_with(API_KEY_PREFIX)
+}
+
+/// Generates a new key and returns it along with the short prefix kept in
+/// plaintext so the owner can tell their keys apart.
+pub fn generate_api_key() -> (String, String) {
+    let secret = rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_SECRET_LENGTH)
+        .map(char::from)
+        .collect::<String>();
+    let key = format!("{}{}", API_KEY_PREFIX, secret);
+    let prefix = key[..API_KEY_PREFIX.len() + API_KEY_DISPLAY_PREFIX_LENGTH].to_string();
+    (key, prefix)
+}
+
+/// Parses a comma separated list of scopes, failing on any unknown scope.
+pub fn parse_scopes(value: &str) -> Option<Vec<ApiKeyScope>> {
+    let mut scopes = Vec::new();
+    for name in value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let scope = ApiKeyScope::parse(name)?;
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    Some(scopes)
+}
+
+pub fn join_scopes(scopes: &[ApiKeyScope]) -> String {
+    scopes
+        .iter()
+        .map(ApiKeyScope::as_str)
+        .collect::<Vec<&str>>()
+        .join(",")
+}
+
+/// Looks up an unrevoked, unexpired API key by its hash and records that it
+/// was used.
+pub async fn authenticate_api_key(value: &str) -> Result<ApiKey, AuthenticationError> {
+    let token = DatabaseValue::String(hash_token(value));
+    let key_params = [("token", &token)];
+    let api_key = match find_one_unarchived_resource_where_fields!(ApiKey, key_params).await {
+        Ok(api_key) => api_key,
+        Err(_) => return Err(AuthenticationError::InvalidToken),
+    };
+    if api_key.is_expired() {
+        return Err(AuthenticationError::TokenExpired);
+    }
+    touch_api_key(api_key.id.as_deref().unwrap_or_default()).await;
+    Ok(api_key)
+}
+
+/// Updates `last_used_at`, at most once a minute per key so busy scripts
+/// don't turn every request into a write.
+async fn touch_api_key(api_key_id: &str) {
+    let pool = get_connection().await;
+    if let Err(err) = sqlx::query(
+        "UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < CURRENT_TIMESTAMP - INTERVAL '1 minute')",
+    )
+    .bind(api_key_id)
+    .execute(&pool)
+    .await
+    {
+        println!("Error recording API key use: {:?}", err);
+    }
+}
+
+/// Revokes every API key belonging to a user.
+pub async fn revoke_user_api_keys(user_id: &str) -> Result<(), AuthenticationError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE api_keys SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND archived_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            println!("Error revoking API keys: {:?}", err);
+            Err(AuthenticationError::SessionDeletionFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scopes() {
+        assert_eq!(parse_scopes(""), Some(vec![]));
+        assert_eq!(
+            parse_scopes("skills:read, teams:write,skills:read"),
+            Some(vec![ApiKeyScope::SkillsRead, ApiKeyScope::TeamsWrite])
+        );
+        assert_eq!(parse_scopes("skills:read,admin"), None);
+        assert_eq!(
+            join_scopes(&[ApiKeyScope::SkillsRead, ApiKeyScope::ActivitiesWrite]),
+            "skills:read,activities:write"
+        );
+    }
+
+    #[test]
+    fn test_generate_api_key() {
+        let (key, prefix) = generate_api_key();
+        assert!(is_api_key(&key));
+        assert!(key.starts_with(&prefix));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + API_KEY_SECRET_LENGTH);
+    }
+}
@@ -0,0 +1,320 @@
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::api::client::ClientInfo;
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::audit_event::{AuditError, AuditEvent};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Registered,
+    Unregistered,
+    AccountRestored,
+    AccountPurged,
+    LoginSucceeded,
+    LoginFailed,
+    Logout,
+    SecondFactorSucceeded,
+    SecondFactorFailed,
+    RefreshTokenReused,
+    AccountRecovered,
+    RecoveryFailed,
+    PasswordChanged,
+    PasswordChangeFailed,
+    PasswordResetRequested,
+    PasswordReset,
+    BackupCodesRegenerated,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    SessionRevoked,
+    OtherSessionsRevoked,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    IdentityLinked,
+    IdentityUnlinked,
+    DataExportRequested,
+    DataExportDownloaded,
+    TeamCreated,
+    TeamUpdated,
+    TeamDeleted,
+    TeamArchived,
+    TeamRestored,
+    TeamOwnershipTransferred,
+    TeamOwnershipOffered,
+    TeamOwnershipOfferCancelled,
+    TeamParentChanged,
+    MemberRoleChanged,
+    MemberRemoved,
+    MemberLeft,
+    InvitationCreated,
+    InvitationAccepted,
+    InvitationRejected,
+    InvitationRevoked,
+    InvitationResent,
+    JoinLinkCreated,
+    JoinLinkRevoked,
+    JoinLinkUsed,
+    JoinRequestCreated,
+    JoinRequestApproved,
+    JoinRequestDenied,
+    OrganizationCreated,
+    OrganizationUpdated,
+    OrganizationMemberAdded,
+    OrganizationMemberRoleChanged,
+    OrganizationMemberRemoved,
+    UserArchived,
+    UserRestored,
+    UserSessionsRevoked,
+    UserBackupCodesReset,
+    AdminGranted,
+    AdminRevoked,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Registered => "user.registered",
+            AuditAction::Unregistered => "user.unregistered",
+            AuditAction::AccountRestored => "user.restored",
+            AuditAction::AccountPurged => "user.purged",
+            AuditAction::LoginSucceeded => "auth.login_succeeded",
+            AuditAction::LoginFailed => "auth.login_failed",
+            AuditAction::Logout => "auth.logout",
+            AuditAction::SecondFactorSucceeded => "auth.second_factor_succeeded",
+            AuditAction::SecondFactorFailed => "auth.second_factor_failed",
+            AuditAction::RefreshTokenReused => "auth.refresh_token_reused",
+            AuditAction::AccountRecovered => "auth.account_recovered",
+            AuditAction::RecoveryFailed => "auth.recovery_failed",
+            AuditAction::PasswordChanged => "auth.password_changed",
+            AuditAction::PasswordChangeFailed => "auth.password_change_failed",
+            AuditAction::PasswordResetRequested => "auth.password_reset_requested",
+            AuditAction::PasswordReset => "auth.password_reset",
+            AuditAction::BackupCodesRegenerated => "auth.backup_codes_regenerated",
+            AuditAction::TwoFactorEnabled => "auth.two_factor_enabled",
+            AuditAction::TwoFactorDisabled => "auth.two_factor_disabled",
+            AuditAction::SessionRevoked => "auth.session_revoked",
+            AuditAction::OtherSessionsRevoked => "auth.other_sessions_revoked",
+            AuditAction::ApiKeyCreated => "auth.api_key_created",
+            AuditAction::ApiKeyRevoked => "auth.api_key_revoked",
+            AuditAction::IdentityLinked => "auth.identity_linked",
+            AuditAction::IdentityUnlinked => "auth.identity_unlinked",
+            AuditAction::DataExportRequested => "user.data_export_requested",
+            AuditAction::DataExportDownloaded => "user.data_export_downloaded",
+            AuditAction::TeamCreated => "team.created",
+            AuditAction::TeamUpdated => "team.updated",
+            AuditAction::TeamDeleted => "team.deleted",
+            AuditAction::TeamArchived => "team.archived",
+            AuditAction::TeamRestored => "team.restored",
+            AuditAction::TeamOwnershipTransferred => "team.ownership_transferred",
+            AuditAction::TeamOwnershipOffered => "team.ownership_offered",
+            AuditAction::TeamOwnershipOfferCancelled => "team.ownership_offer_cancelled",
+            AuditAction::TeamParentChanged => "team.parent_changed",
+            AuditAction::MemberRoleChanged => "team.member_role_changed",
+            AuditAction::MemberRemoved => "team.member_removed",
+            AuditAction::MemberLeft => "team.member_left",
+            AuditAction::InvitationCreated => "invitation.created",
+            AuditAction::InvitationAccepted => "invitation.accepted",
+            AuditAction::InvitationRejected => "invitation.rejected",
+            AuditAction::InvitationRevoked => "invitation.revoked",
+            AuditAction::InvitationResent => "invitation.resent",
+            AuditAction::JoinLinkCreated => "join_link.created",
+            AuditAction::JoinLinkRevoked => "join_link.revoked",
+            AuditAction::JoinLinkUsed => "join_link.used",
+            AuditAction::JoinRequestCreated => "join_request.created",
+            AuditAction::JoinRequestApproved => "join_request.approved",
+            AuditAction::JoinRequestDenied => "join_request.denied",
+            AuditAction::OrganizationCreated => "organization.created",
+            AuditAction::OrganizationUpdated => "organization.updated",
+            AuditAction::OrganizationMemberAdded => "organization.member_added",
+            AuditAction::OrganizationMemberRoleChanged => "organization.member_role_changed",
+            AuditAction::OrganizationMemberRemoved => "organization.member_removed",
+            AuditAction::UserArchived => "admin.user_archived",
+            AuditAction::UserRestored => "admin.user_restored",
+            AuditAction::UserSessionsRevoked => "admin.user_sessions_revoked",
+            AuditAction::UserBackupCodesReset => "admin.user_backup_codes_reset",
+            AuditAction::AdminGranted => "admin.admin_granted",
+            AuditAction::AdminRevoked => "admin.admin_revoked",
+        }
+    }
+}
+
+/// An audit event waiting to be written. Start from [`AuditEntry::new`] and
+/// fill in whatever applies.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub action: AuditAction,
+    pub actor_id: Option<String>,
+    pub user_id: Option<String>,
+    pub team_id: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub details: Value,
+}
+
+impl AuditEntry {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            actor_id: None,
+            user_id: None,
+            team_id: None,
+            target_type: None,
+            target_id: None,
+            ip_address: None,
+            user_agent: None,
+            details: serde_json::json!({}),
+        }
+    }
+
+    /// The user acting on their own account, which is most events.
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.actor_id = Some(user_id.to_string());
+        self.user_id = Some(user_id.to_string());
+        self
+    }
+
+    pub fn actor(mut self, actor_id: &str) -> Self {
+        self.actor_id = Some(actor_id.to_string());
+        self
+    }
+
+    pub fn team(mut self, team_id: &str) -> Self {
+        self.team_id = Some(team_id.to_string());
+        self
+    }
+
+    pub fn target(mut self, target_type: &str, target_id: &str) -> Self {
+        self.target_type = Some(target_type.to_string());
+        self.target_id = Some(target_id.to_string());
+        self
+    }
+
+    pub fn client(mut self, client: &ClientInfo) -> Self {
+        self.ip_address = client.ip_address.clone();
+        self.user_agent = client.user_agent.clone();
+        self
+    }
+
+    pub fn details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+}
+
+/// Writes an audit event. Failures are logged rather than returned so that
+/// auditing never breaks the request being audited.
+pub async fn record_audit_event(entry: AuditEntry) {
+    let pool = get_connection().await;
+    let query = "INSERT INTO audit_events (id, actor_id, user_id, team_id, action, target_type, target_id, ip_address, user_agent, details) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)";
+    if let Err(err) = sqlx::query(query)
+        .bind(Uuid::new_v4().to_string())
+        .bind(&entry.actor_id)
+        .bind(&entry.user_id)
+        .bind(&entry.team_id)
+        .bind(entry.action.as_str())
+        .bind(&entry.target_type)
+        .bind(&entry.target_id)
+        .bind(&entry.ip_address)
+        .bind(&entry.user_agent)
+        .bind(&entry.details)
+        .execute(&pool)
+        .await
+    {
+        println!(
+            "Error recording audit event {}: {:?}",
+            entry.action.as_str(),
+            err
+        );
+    }
+}
+
+/// Clamps a requested page size to something sensible.
+pub fn page_size(limit: Option<i64>) -> i64 {
+    limit
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE)
+}
+
+async fn find_audit_events(
+    column: &str,
+    value: &str,
+    limit: Option<i64>,
+    before: Option<&str>,
+) -> Result<Vec<AuditEvent>, AuditError> {
+    let pool = get_connection().await;
+    // Paging is keyed on the last event of the previous page, so events
+    // written in the meantime don't shift later pages.
+    let query = format!(
+        "SELECT * FROM audit_events WHERE {column} = $1 AND ($2::VARCHAR IS NULL OR (created_at, id) < (SELECT created_at, id FROM audit_events WHERE id = $2)) ORDER BY created_at DESC, id DESC LIMIT $3"
+    );
+    match sqlx::query(&query)
+        .bind(value)
+        .bind(before)
+        .bind(page_size(limit))
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .map(AuditEvent::from_row)
+            .collect::<Result<Vec<AuditEvent>, _>>()
+            .map_err(|err| {
+                println!("Error reading audit events: {:?}", err);
+                AuditError::AuditEventsNotFound
+            }),
+        Err(err) => {
+            println!("Error finding audit events: {:?}", err);
+            Err(AuditError::AuditEventsNotFound)
+        }
+    }
+}
+
+/// Events on a user's own account, newest first.
+pub async fn user_audit_events(
+    user_id: &str,
+    limit: Option<i64>,
+    before: Option<&str>,
+) -> Result<Vec<AuditEvent>, AuditError> {
+    find_audit_events("user_id", user_id, limit, before).await
+}
+
+/// Events on a team, newest first.
+pub async fn team_audit_events(
+    team_id: &str,
+    limit: Option<i64>,
+    before: Option<&str>,
+) -> Result<Vec<AuditEvent>, AuditError> {
+    find_audit_events("team_id", team_id, limit, before).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_size() {
+        assert_eq!(page_size(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(0)), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(-5)), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(10)), 10);
+        assert_eq!(page_size(Some(10_000)), MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_entry_user_sets_actor_and_account() {
+        let entry = AuditEntry::new(AuditAction::LoginSucceeded)
+            .user("user-1")
+            .target("session", "session-1");
+        assert_eq!(entry.actor_id.as_deref(), Some("user-1"));
+        assert_eq!(entry.user_id.as_deref(), Some("user-1"));
+        assert_eq!(entry.target_type.as_deref(), Some("session"));
+        assert_eq!(entry.action.as_str(), "auth.login_succeeded");
+    }
+}
@@ -1,27 +1,30 @@
-use rand::{rng, Rng};
-use sha2::{Digest, Sha256};
-use time::OffsetDateTime;
+use rand::rngs::OsRng;
+use rand::TryRngCore;
+use sqlx::Row;
 
-use crate::find_all_resources_where_fields;
-use crate::models::backup_code::BackupCode;
+use crate::database::connection::get_connection;
+use crate::database::values::DatabaseValue;
+use crate::models::backup_code::{BackupCode, BackupCodeError};
+use crate::utils::token_hash::{hash_token, is_token_hash};
+use crate::{find_all_resources_where_fields, find_one_resource_where_fields, insert_resource};
 
+const CODE_BYTES: usize = 7;
+
+/// A backup code: 14 hex characters, every one of them drawn from the OS
+/// random number generator.
 fn generate_code() -> String {
-    let mut rng = rng();
-    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
-    let code = format!("{:06}", rng.random_range(0..1000000));
-    let hash = Sha256::digest(format!("{:?}{}", timestamp, code).as_bytes()).to_vec();
-    hash[..7]
-        .iter()
-        .map(|b| format!("{:02x}", b))
-        .collect::<Vec<String>>()
-        .join("")
+    let mut bytes = [0u8; CODE_BYTES];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .expect("Error reading OS randomness");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
 
 pub async fn generate_backup_code() -> String {
     let backup_code = generate_code();
     match find_all_resources_where_fields!(
         BackupCode,
-        vec![("code", DatabaseValue::String(backup_code.clone()))]
+        [("code", DatabaseValue::String(hash_token(&backup_code)))]
     )
     .await
     {
@@ -47,3 +50,98 @@ pub async fn generate_backup_codes() -> Vec<String> {
     }
     codes
 }
+
+/// Stores a user's backup codes. Only their keyed hashes are written, so the
+/// plaintext codes must be shown to the user before this returns.
+pub async fn store_backup_codes(user_id: &str, codes: &[String]) -> Result<(), BackupCodeError> {
+    for code in codes {
+        let backup_code_params = vec![
+            ("code", DatabaseValue::String(hash_token(code))),
+            ("user_id", DatabaseValue::String(user_id.to_string())),
+        ];
+        if let Err(err) = insert_resource!(BackupCode, backup_code_params).await {
+            println!("Error inserting backup code: {:?}", err);
+            return Err(BackupCodeError::CodeCreationFailed);
+        }
+    }
+    Ok(())
+}
+
+/// Throws away all of a user's backup codes, used or not, and issues a new
+/// set. Returns the plaintext codes, which are not stored.
+pub async fn reset_backup_codes(user_id: &str) -> Result<Vec<String>, BackupCodeError> {
+    let pool = get_connection().await;
+    if let Err(err) = sqlx::query("DELETE FROM backup_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&pool)
+        .await
+    {
+        println!("Error deleting backup codes: {:?}", err);
+        return Err(BackupCodeError::CodeDeletionFailed);
+    }
+    let codes = generate_backup_codes().await;
+    store_backup_codes(user_id, &codes).await?;
+    Ok(codes)
+}
+
+/// Replaces backup codes stored in plaintext before hashing was introduced
+/// with their hashes. Safe to run on every startup.
+pub async fn hash_plaintext_backup_codes() {
+    let pool = get_connection().await;
+    let rows = match sqlx::query("SELECT id, code FROM backup_codes WHERE LENGTH(code) <> 64")
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("Error finding plaintext backup codes: {:?}", err);
+            return;
+        }
+    };
+    for row in rows {
+        let id: String = row.get("id");
+        let code: String = row.get("code");
+        if is_token_hash(&code) {
+            continue;
+        }
+        if let Err(err) = sqlx::query("UPDATE backup_codes SET code = $2 WHERE id = $1")
+            .bind(&id)
+            .bind(hash_token(&code))
+            .execute(&pool)
+            .await
+        {
+            println!("Error hashing backup code {}: {:?}", id, err);
+        }
+    }
+}
+
+/// Marks one of a user's backup codes as used by setting its `archived_at`.
+/// The update only matches unarchived rows, so two concurrent redemptions of
+/// the same code cannot both succeed.
+pub async fn redeem_backup_code(user_id: &str, code: &str) -> Result<(), BackupCodeError> {
+    let code_params = [
+        ("user_id", DatabaseValue::String(user_id.to_string())),
+        ("code", DatabaseValue::String(hash_token(code.trim()))),
+    ];
+    let backup_code = match find_one_resource_where_fields!(BackupCode, code_params).await {
+        Ok(backup_code) => backup_code,
+        Err(err) => {
+            println!("Error finding backup code: {:?}", err);
+            return Err(BackupCodeError::CodeNotValid);
+        }
+    };
+    if backup_code.archived_at.is_some() {
+        return Err(BackupCodeError::CodeAlreadyUsed);
+    }
+
+    let pool = get_connection().await;
+    let query = "UPDATE backup_codes SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND archived_at IS NULL";
+    match sqlx::query(query).bind(backup_code.id).execute(&pool).await {
+        Ok(result) if result.rows_affected() == 1 => Ok(()),
+        Ok(_) => Err(BackupCodeError::CodeAlreadyUsed),
+        Err(err) => {
+            println!("Error redeeming backup code: {:?}", err);
+            Err(BackupCodeError::CodeVerificationFailed)
+        }
+    }
+}
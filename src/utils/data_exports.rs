@@ -0,0 +1,323 @@
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+
+use serde_json::Value;
+use sqlx::Row;
+use time::{format_description::well_known::Iso8601, Duration, OffsetDateTime};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::database::connection::get_connection;
+use crate::database::values::DatabaseValue;
+use crate::models::activity::Activity;
+use crate::models::data_export::{DataExport, DataExportStatus};
+use crate::models::team::Team;
+use crate::models::user::User;
+use crate::models::user_skill::UserSkill;
+use crate::utils::jobs::{interval_from_env, spawn_job, spawn_periodic};
+use crate::{find_all_resources_where_fields, find_one_resource_where_fields, update_resource};
+
+const DEFAULT_EXPORT_TTL_HOURS: i64 = 24;
+const DEFAULT_EXPORT_DIR: &str = "exports";
+const DEFAULT_CLEANUP_INTERVAL_MINUTES: u64 = 60;
+
+/// How long a finished export can be downloaded, from `EXPORT_TTL_HOURS`.
+pub fn data_export_lifetime() -> Duration {
+    Duration::hours(
+        std::env::var("EXPORT_TTL_HOURS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|hours: &i64| *hours > 0)
+            .unwrap_or(DEFAULT_EXPORT_TTL_HOURS),
+    )
+}
+
+/// Where archives are written, from `EXPORT_DIR`.
+pub fn export_dir() -> PathBuf {
+    PathBuf::from(std::env::var("EXPORT_DIR").unwrap_or_else(|_| DEFAULT_EXPORT_DIR.to_string()))
+}
+
+pub fn export_path(file_name: &str) -> PathBuf {
+    export_dir().join(file_name)
+}
+
+/// Where a ready export can be fetched from by its owner.
+pub fn download_url(export_id: &str) -> String {
+    format!("/api/my/export/{}/download", export_id)
+}
+
+/// Runs a query wrapped in `json_agg`, for data that has no model of its own.
+async fn json_rows(query: &str, user_id: &str) -> Result<Value, String> {
+    let pool = get_connection().await;
+    let wrapped = format!(
+        "SELECT COALESCE(json_agg(row_to_json(export_rows)), '[]'::json) AS data FROM ({}) export_rows",
+        query
+    );
+    match sqlx::query(&wrapped).bind(user_id).fetch_one(&pool).await {
+        Ok(row) => Ok(row.get("data")),
+        Err(err) => Err(format!("{:?}", err)),
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|err| err.to_string())
+}
+
+/// Collects everything held about a user, one document per archive entry.
+async fn collect_user_data(user_id: &str) -> Result<Vec<(&'static str, Value)>, String> {
+    let user_params = [("id", user_id.to_string())];
+    let user = find_one_resource_where_fields!(User, user_params)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let skills_params = [("user_id", DatabaseValue::String(user_id.to_string()))];
+    let skills = find_all_resources_where_fields!(UserSkill, skills_params)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let activities_params = [("assigned_to", DatabaseValue::String(user_id.to_string()))];
+    let activities = find_all_resources_where_fields!(Activity, activities_params)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let owned_teams_params = [("owner_id", DatabaseValue::String(user_id.to_string()))];
+    let owned_teams = find_all_resources_where_fields!(Team, owned_teams_params)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let memberships = json_rows(
+        "SELECT teams_users.team_id, teams.team_name, teams_users.team_role, teams_users.created_at, teams_users.archived_at FROM teams_users JOIN teams ON teams.id = teams_users.team_id WHERE teams_users.user_id = $1 ORDER BY teams_users.created_at",
+        user_id,
+    )
+    .await?;
+    let organization_memberships = json_rows(
+        "SELECT organizations_users.organization_id, organizations.organization_name, organizations_users.organization_role, organizations_users.created_at, organizations_users.archived_at FROM organizations_users JOIN organizations ON organizations.id = organizations_users.organization_id WHERE organizations_users.user_id = $1 ORDER BY organizations_users.created_at",
+        user_id,
+    )
+    .await?;
+    let invitations_received = json_rows(
+        "SELECT id, team_id, invited_by, team_role, accepted_at, rejected_at, revoked_at, expires_at, created_at, archived_at FROM invitations WHERE user_id = $1 ORDER BY created_at",
+        user_id,
+    )
+    .await?;
+    let invitations_sent = json_rows(
+        "SELECT id, team_id, user_id, team_role, accepted_at, rejected_at, revoked_at, expires_at, created_at, archived_at FROM invitations WHERE invited_by = $1 ORDER BY created_at",
+        user_id,
+    )
+    .await?;
+    let join_requests = json_rows(
+        "SELECT id, team_id, join_link_id, team_role, message, approved_at, denied_at, created_at, archived_at FROM join_requests WHERE user_id = $1 ORDER BY created_at",
+        user_id,
+    )
+    .await?;
+    let audit_events = json_rows(
+        "SELECT action, actor_id, team_id, target_type, target_id, ip_address, user_agent, details, created_at FROM audit_events WHERE user_id = $1 ORDER BY created_at",
+        user_id,
+    )
+    .await?;
+
+    Ok(vec![
+        ("profile.json", to_json(&user)?),
+        ("skills.json", to_json(&skills)?),
+        ("organization_memberships.json", organization_memberships),
+        ("team_memberships.json", memberships),
+        ("owned_teams.json", to_json(&owned_teams)?),
+        ("invitations_received.json", invitations_received),
+        ("invitations_sent.json", invitations_sent),
+        ("join_requests.json", join_requests),
+        ("activities.json", to_json(&activities)?),
+        ("security_events.json", audit_events),
+    ])
+}
+
+/// Packs the documents into a zip archive with a small manifest.
+pub fn build_archive(
+    user_id: &str,
+    documents: &[(&str, Value)],
+    generated_at: OffsetDateTime,
+) -> Result<Vec<u8>, String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let manifest = serde_json::json!({
+        "userId": user_id,
+        "generatedAt": generated_at.format(&Iso8601::DEFAULT).map_err(|err| err.to_string())?,
+        "files": documents.iter().map(|(name, _)| *name).collect::<Vec<&str>>(),
+    });
+    let entries = std::iter::once(("manifest.json", &manifest))
+        .chain(documents.iter().map(|(name, document)| (*name, document)));
+    for (name, document) in entries {
+        zip.start_file(name, options)
+            .map_err(|err| err.to_string())?;
+        let contents = serde_json::to_vec_pretty(document).map_err(|err| err.to_string())?;
+        zip.write_all(&contents).map_err(|err| err.to_string())?;
+    }
+    let cursor = zip.finish().map_err(|err| err.to_string())?;
+    Ok(cursor.into_inner())
+}
+
+async fn set_status(export_id: &str, status: DataExportStatus) {
+    let status_params = vec![("status", DatabaseValue::String(status.as_str().to_string()))];
+    let export_id = export_id.to_string();
+    if let Err(err) = update_resource!(DataExport, export_id, status_params).await {
+        println!("Error updating data export {}: {:?}", export_id, err);
+    }
+}
+
+async fn run_export(export_id: String, user_id: String) {
+    set_status(&export_id, DataExportStatus::Running).await;
+
+    let generated_at = OffsetDateTime::now_utc();
+    let archive = match collect_user_data(&user_id).await {
+        Ok(documents) => build_archive(&user_id, &documents, generated_at),
+        Err(err) => Err(err),
+    };
+    let archive = match archive {
+        Ok(archive) => archive,
+        Err(err) => {
+            println!("Error building data export {}: {}", export_id, err);
+            set_status(&export_id, DataExportStatus::Failed).await;
+            return;
+        }
+    };
+
+    let file_name = format!("{}.zip", export_id);
+    if let Err(err) = rocket::tokio::fs::create_dir_all(export_dir()).await {
+        println!("Error creating export directory: {:?}", err);
+    }
+    if let Err(err) = rocket::tokio::fs::write(export_path(&file_name), &archive).await {
+        println!("Error writing data export {}: {:?}", export_id, err);
+        set_status(&export_id, DataExportStatus::Failed).await;
+        return;
+    }
+
+    let completed_at = OffsetDateTime::now_utc();
+    let ready_params = vec![
+        (
+            "status",
+            DatabaseValue::String(DataExportStatus::Ready.as_str().to_string()),
+        ),
+        ("file_name", DatabaseValue::String(file_name)),
+        (
+            "size_bytes",
+            DatabaseValue::Int64(archive.len().to_string()),
+        ),
+        (
+            "completed_at",
+            DatabaseValue::DateTime(completed_at.format(&Iso8601::DEFAULT).unwrap()),
+        ),
+        (
+            "expires_at",
+            DatabaseValue::DateTime(
+                (completed_at + data_export_lifetime())
+                    .format(&Iso8601::DEFAULT)
+                    .unwrap(),
+            ),
+        ),
+    ];
+    if let Err(err) = update_resource!(DataExport, export_id, ready_params).await {
+        println!("Error finishing data export: {:?}", err);
+    }
+}
+
+/// Builds an export in the background.
+pub fn spawn_export(export: &DataExport) {
+    spawn_job(run_export(
+        export.id.clone().unwrap_or_default(),
+        export.user_id.clone().unwrap_or_default(),
+    ));
+}
+
+/// Restarts exports that were interrupted by a shutdown.
+pub async fn resume_data_exports() {
+    let pool = get_connection().await;
+    let rows = match sqlx::query(
+        "SELECT id, user_id FROM data_exports WHERE status IN ('pending', 'running') AND expires_at > CURRENT_TIMESTAMP",
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("Error finding interrupted data exports: {:?}", err);
+            return;
+        }
+    };
+    for row in rows {
+        spawn_job(run_export(row.get("id"), row.get("user_id")));
+    }
+}
+
+/// Deletes expired exports and their archives.
+pub async fn remove_expired_data_exports() {
+    let pool = get_connection().await;
+    let rows = match sqlx::query(
+        "DELETE FROM data_exports WHERE expires_at <= CURRENT_TIMESTAMP RETURNING file_name",
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("Error removing expired data exports: {:?}", err);
+            return;
+        }
+    };
+    for row in rows {
+        if let Some(file_name) = row.get::<Option<String>, _>("file_name") {
+            if let Err(err) = rocket::tokio::fs::remove_file(export_path(&file_name)).await {
+                println!("Error removing export archive {}: {:?}", file_name, err);
+            }
+        }
+    }
+}
+
+/// Runs [`remove_expired_data_exports`] every
+/// `EXPORT_CLEANUP_INTERVAL_MINUTES`.
+pub fn spawn_data_export_cleanup() {
+    spawn_periodic(
+        interval_from_env(
+            "EXPORT_CLEANUP_INTERVAL_MINUTES",
+            DEFAULT_CLEANUP_INTERVAL_MINUTES,
+        ),
+        remove_expired_data_exports,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    #[test]
+    fn test_build_archive() {
+        let documents = vec![
+            ("profile.json", serde_json::json!({ "username": "alice" })),
+            ("skills.json", serde_json::json!([])),
+        ];
+        let archive = build_archive("user-1", &documents, OffsetDateTime::UNIX_EPOCH).unwrap();
+
+        let mut zip = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut names = zip.file_names().map(str::to_string).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["manifest.json", "profile.json", "skills.json"]);
+
+        let mut profile = String::new();
+        zip.by_name("profile.json")
+            .unwrap()
+            .read_to_string(&mut profile)
+            .unwrap();
+        let profile: Value = serde_json::from_str(&profile).unwrap();
+        assert_eq!(profile["username"], "alice");
+
+        let mut manifest = String::new();
+        zip.by_name("manifest.json")
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        let manifest: Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["userId"], "user-1");
+        assert_eq!(manifest["files"][1], "skills.json");
+    }
+}
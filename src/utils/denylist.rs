@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration as StdDuration, Instant};
+
+use sqlx::Row;
+use time::OffsetDateTime;
+
+use crate::database::connection::get_connection;
+
+const DEFAULT_REFRESH_SECONDS: u64 = 10;
+
+/// Session ids whose access tokens must be rejected even though their
+/// signatures are still valid. Entries only need to outlive the longest
+/// access token, so the list stays small.
+#[derive(Default)]
+struct Denylist {
+    session_ids: HashSet<String>,
+    loaded_at: Option<Instant>,
+}
+
+fn denylist() -> &'static RwLock<Denylist> {
+    static DENYLIST: OnceLock<RwLock<Denylist>> = OnceLock::new();
+    DENYLIST.get_or_init(|| RwLock::new(Denylist::default()))
+}
+
+/// How often the in-memory copy is reloaded from Postgres, from
+/// `SESSION_DENYLIST_REFRESH_SECONDS`. This bounds how long a session revoked
+/// on another instance stays usable here.
+fn refresh_interval() -> StdDuration {
+    StdDuration::from_secs(
+        std::env::var("SESSION_DENYLIST_REFRESH_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_REFRESH_SECONDS),
+    )
+}
+
+async fn reload() {
+    let pool = get_connection().await;
+    if let Err(err) =
+        sqlx::query("DELETE FROM revoked_sessions WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(&pool)
+            .await
+    {
+        println!("Error pruning revoked sessions: {:?}", err);
+    }
+    match sqlx::query("SELECT session_id FROM revoked_sessions")
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(rows) => {
+            let session_ids = rows
+                .iter()
+                .map(|row| row.get::<String, _>("session_id"))
+                .collect::<HashSet<String>>();
+            let mut denylist = denylist().write().unwrap();
+            denylist.session_ids = session_ids;
+            denylist.loaded_at = Some(Instant::now());
+        }
+        Err(err) => println!("Error loading revoked sessions: {:?}", err),
+    }
+}
+
+pub async fn is_session_revoked(session_id: &str) -> bool {
+    let stale = denylist()
+        .read()
+        .unwrap()
+        .loaded_at
+        .is_none_or(|loaded_at| loaded_at.elapsed() >= refresh_interval());
+    if stale {
+        reload().await;
+    }
+    denylist().read().unwrap().session_ids.contains(session_id)
+}
+
+/// Adds sessions to the denylist until `expires_at`, after which their access
+/// tokens would be rejected as expired anyway.
+pub async fn deny_sessions(session_ids: &[String], expires_at: OffsetDateTime) {
+    if session_ids.is_empty() {
+        return;
+    }
+    {
+        let mut denylist = denylist().write().unwrap();
+        denylist.session_ids.extend(session_ids.iter().cloned());
+    }
+    let pool = get_connection().await;
+    if let Err(err) = sqlx::query(
+        "INSERT INTO revoked_sessions (session_id, expires_at) SELECT UNNEST($1::VARCHAR[]), $2 ON CONFLICT (session_id) DO NOTHING",
+    )
+    .bind(session_ids)
+    .bind(expires_at)
+    .execute(&pool)
+    .await
+    {
+        println!("Error recording revoked sessions: {:?}", err);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::invitation::{Invitation, InvitationError};
+use crate::models::team_role::TeamRole;
+use crate::models::team_user::TeamUser;
+use crate::utils::teams::is_team_member;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+const DEFAULT_INVITATION_TTL_DAYS: i64 = 7;
+
+/// How long an invitation can be accepted, from `INVITATION_TTL_DAYS`.
+pub fn invitation_lifetime() -> Duration {
+    Duration::days(
+        std::env::var("INVITATION_TTL_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|days: &i64| *days > 0)
+            .unwrap_or(DEFAULT_INVITATION_TTL_DAYS),
+    )
+}
+
+/// Why an invitation can no longer be answered, if it can't.
+pub fn answerable(invitation: &Invitation) -> Result<(), InvitationError> {
+    if invitation.is_revoked() {
+        Err(InvitationError::InvitationRevoked)
+    } else if !invitation.is_pending() {
+        Err(InvitationError::InvitationAlreadyAnswered)
+    } else if invitation.is_expired() {
+        Err(InvitationError::InvitationExpired)
+    } else {
+        Ok(())
+    }
+}
+
+/// Invites `user_id` to the team on behalf of `invited_by`. A user can only
+/// have one pending invitation per team; an expired one is revoked to make
+/// way for the new one.
+pub async fn create_invitation(
+    team_id: &str,
+    user_id: &str,
+    invited_by: &str,
+    team_role: &TeamRole,
+) -> Result<Invitation, InvitationError> {
+    if is_team_member(team_id, user_id).await {
+        return Err(InvitationError::InviteeAlreadyMember);
+    }
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting invitation: {:?}", err);
+        InvitationError::InvitationCreationFailed
+    })?;
+
+    let pending = sqlx::query(
+        "SELECT * FROM invitations WHERE team_id = $1 AND user_id = $2 AND accepted_at IS NULL AND rejected_at IS NULL AND revoked_at IS NULL AND archived_at IS NULL FOR UPDATE",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| {
+        println!("Error finding pending invitation: {:?}", err);
+        InvitationError::InvitationCreationFailed
+    })?;
+    if let Some(row) = pending {
+        let pending =
+            Invitation::from_row(&row).map_err(|_| InvitationError::InvitationCreationFailed)?;
+        if !pending.is_expired() {
+            return Err(InvitationError::InvitationAlreadyPending);
+        }
+        if let Err(err) = sqlx::query(
+            "UPDATE invitations SET revoked_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(&pending.id)
+        .execute(&mut *tx)
+        .await
+        {
+            println!("Error revoking expired invitation: {:?}", err);
+            return Err(InvitationError::InvitationCreationFailed);
+        }
+    }
+
+    let invitation = match sqlx::query(
+        "INSERT INTO invitations (id, team_id, user_id, invited_by, team_role, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(team_id)
+    .bind(user_id)
+    .bind(invited_by)
+    .bind(team_role.to_string())
+    .bind(OffsetDateTime::now_utc() + invitation_lifetime())
+    .fetch_one(&mut *tx)
+    .await
+    {
+        Ok(row) => {
+            Invitation::from_row(&row).map_err(|_| InvitationError::InvitationCreationFailed)?
+        }
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            return Err(InvitationError::InvitationAlreadyPending)
+        }
+        Err(err) => {
+            println!("Error creating invitation: {:?}", err);
+            return Err(InvitationError::InvitationCreationFailed);
+        }
+    };
+
+    tx.commit().await.map_err(|err| {
+        println!("Error committing invitation: {:?}", err);
+        InvitationError::InvitationCreationFailed
+    })?;
+    Ok(invitation)
+}
+
+async fn find_team_invitation(
+    team_id: &str,
+    invitation_id: &str,
+) -> Result<Invitation, InvitationError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "SELECT * FROM invitations WHERE id = $1 AND team_id = $2 AND archived_at IS NULL",
+    )
+    .bind(invitation_id)
+    .bind(team_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => {
+            Invitation::from_row(&row).map_err(|_| InvitationError::InvitationNotFound)
+        }
+        Ok(None) => Err(InvitationError::InvitationNotFound),
+        Err(err) => {
+            println!("Error finding invitation: {:?}", err);
+            Err(InvitationError::InvitationNotFound)
+        }
+    }
+}
+
+/// Runs an update that only matches the invitation while it is pending,
+/// so an answer that lands in between wins.
+async fn update_pending_invitation(
+    query: &str,
+    invitation_id: &str,
+    expires_at: Option<OffsetDateTime>,
+) -> Result<Invitation, InvitationError> {
+    let pool = get_connection().await;
+    let mut query = sqlx::query(query).bind(invitation_id);
+    if let Some(expires_at) = expires_at {
+        query = query.bind(expires_at);
+    }
+    match query.fetch_optional(&pool).await {
+        Ok(Some(row)) => {
+            Invitation::from_row(&row).map_err(|_| InvitationError::InvitationUpdateFailed)
+        }
+        Ok(None) => Err(InvitationError::InvitationAlreadyAnswered),
+        Err(err) => {
+            println!("Error updating invitation: {:?}", err);
+            Err(InvitationError::InvitationUpdateFailed)
+        }
+    }
+}
+
+/// Gives a pending invitation a fresh expiry, including one that has
+/// already expired.
+pub async fn resend_invitation(
+    team_id: &str,
+    invitation_id: &str,
+) -> Result<Invitation, InvitationError> {
+    let invitation = find_team_invitation(team_id, invitation_id).await?;
+    if invitation.is_revoked() {
+        return Err(InvitationError::InvitationRevoked);
+    }
+    if !invitation.is_pending() {
+        return Err(InvitationError::InvitationAlreadyAnswered);
+    }
+    update_pending_invitation(
+        "UPDATE invitations SET expires_at = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND accepted_at IS NULL AND rejected_at IS NULL AND revoked_at IS NULL RETURNING *",
+        invitation_id,
+        Some(OffsetDateTime::now_utc() + invitation_lifetime()),
+    )
+    .await
+}
+
+/// Withdraws a pending invitation so it can no longer be accepted.
+pub async fn revoke_invitation(
+    team_id: &str,
+    invitation_id: &str,
+) -> Result<Invitation, InvitationError> {
+    let invitation = find_team_invitation(team_id, invitation_id).await?;
+    if invitation.is_revoked() {
+        return Err(InvitationError::InvitationRevoked);
+    }
+    if !invitation.is_pending() {
+        return Err(InvitationError::InvitationAlreadyAnswered);
+    }
+    update_pending_invitation(
+        "UPDATE invitations SET revoked_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND accepted_at IS NULL AND rejected_at IS NULL AND revoked_at IS NULL RETURNING *",
+        invitation_id,
+        None,
+    )
+    .await
+}
+
+/// Accepts a pending invitation and makes the invitee a member of the team
+/// with the invited role. Both happen in one transaction, so an accepted
+/// invitation always has a membership behind it.
+pub async fn accept_invitation(
+    invitation_id: &str,
+    user_id: &str,
+) -> Result<(Invitation, TeamUser), InvitationError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting invitation acceptance: {:?}", err);
+        InvitationError::InvitationUpdateFailed
+    })?;
+
+    let invitation = match sqlx::query(
+        "SELECT invitations.* FROM invitations JOIN teams ON teams.id = invitations.team_id WHERE invitations.id = $1 AND invitations.user_id = $2 AND invitations.archived_at IS NULL AND teams.archived_at IS NULL AND teams.organization_id IN (SELECT organization_id FROM organizations_users WHERE user_id = $2 AND archived_at IS NULL) FOR UPDATE OF invitations",
+    )
+    .bind(invitation_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(row)) => {
+            Invitation::from_row(&row).map_err(|_| InvitationError::InvitationUpdateFailed)?
+        }
+        Ok(None) => return Err(InvitationError::InvitationNotFound),
+        Err(err) => {
+            println!("Error finding invitation: {:?}", err);
+            return Err(InvitationError::InvitationUpdateFailed);
+        }
+    };
+    answerable(&invitation)?;
+    // Accepting must never change the role of someone already on the team.
+    if is_team_member(invitation.team_id.as_deref().unwrap_or_default(), user_id).await {
+        return Err(InvitationError::InviteeAlreadyMember);
+    }
+
+    let invitation = sqlx::query(
+        "UPDATE invitations SET accepted_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(invitation_id)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| Invitation::from_row(&row))
+    .map_err(|err| {
+        println!("Error accepting invitation: {:?}", err);
+        InvitationError::InvitationUpdateFailed
+    })?;
+
+    let team_role = invitation.team_role.clone().unwrap_or(TeamRole::Member);
+    let team_user = match sqlx::query(
+        "INSERT INTO teams_users (id, team_id, user_id, team_role) VALUES ($1, $2, $3, $4) ON CONFLICT (team_id, user_id) DO UPDATE SET team_role = EXCLUDED.team_role, join_link_id = NULL, archived_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE teams_users.archived_at IS NOT NULL RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(&invitation.team_id)
+    .bind(user_id)
+    .bind(team_role.to_string())
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(row)) => {
+            TeamUser::from_row(&row).map_err(|_| InvitationError::InvitationUpdateFailed)?
+        }
+        Ok(None) => return Err(InvitationError::InviteeAlreadyMember),
+        Err(err) => {
+            println!("Error creating team membership: {:?}", err);
+            return Err(InvitationError::InvitationUpdateFailed);
+        }
+    };
+
+    tx.commit().await.map_err(|err| {
+        println!("Error committing invitation acceptance: {:?}", err);
+        InvitationError::InvitationUpdateFailed
+    })?;
+    Ok((invitation, team_user))
+}
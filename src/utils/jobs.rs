@@ -0,0 +1,35 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Reads a job interval in minutes from the environment.
+pub fn interval_from_env(name: &str, default_minutes: u64) -> Duration {
+    Duration::from_secs(
+        60 * std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|minutes: &u64| *minutes > 0)
+            .unwrap_or(default_minutes),
+    )
+}
+
+/// Runs `job` in the background straight away and then every `interval`.
+pub fn spawn_periodic<F, Fut>(interval: Duration, job: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    rocket::tokio::spawn(async move {
+        loop {
+            job().await;
+            rocket::tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+/// Runs a one-off job in the background.
+pub fn spawn_job<Fut>(job: Fut)
+where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    rocket::tokio::spawn(job);
+}
@@ -0,0 +1,304 @@
+use rand::distr::Alphanumeric;
+use rand::{rng, Rng};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::join_link::{JoinLink, JoinLinkError};
+use crate::models::join_request::JoinRequest;
+use crate::models::team_role::TeamRole;
+use crate::models::team_user::TeamUser;
+use crate::models::user::User;
+use crate::utils::organizations::is_in_team_organization;
+use crate::utils::teams::is_team_member;
+
+const JOIN_LINK_CODE_LENGTH: usize = 24;
+const DEFAULT_JOIN_LINK_TTL_DAYS: i64 = 7;
+
+/// How long a join link works when no expiry is given, from
+/// `JOIN_LINK_TTL_DAYS`.
+pub fn join_link_lifetime() -> Duration {
+    Duration::days(
+        std::env::var("JOIN_LINK_TTL_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|days: &i64| *days > 0)
+            .unwrap_or(DEFAULT_JOIN_LINK_TTL_DAYS),
+    )
+}
+
+pub fn generate_join_link_code() -> String {
+    rng()
+        .sample_iter(&Alphanumeric)
+        .take(JOIN_LINK_CODE_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Why a join link can no longer be used, if it can't.
+pub fn usable(join_link: &JoinLink) -> Result<(), JoinLinkError> {
+    if join_link.is_revoked() {
+        Err(JoinLinkError::JoinLinkRevoked)
+    } else if join_link.is_expired() {
+        Err(JoinLinkError::JoinLinkExpired)
+    } else if join_link.is_exhausted() {
+        Err(JoinLinkError::JoinLinkExhausted)
+    } else {
+        Ok(())
+    }
+}
+
+/// What happened when someone used a join link.
+#[derive(Debug)]
+pub enum JoinOutcome {
+    Joined(TeamUser),
+    Requested(JoinRequest),
+}
+
+pub async fn create_join_link(
+    team_id: &str,
+    created_by: &str,
+    team_role: &TeamRole,
+    requires_approval: bool,
+    max_uses: Option<i32>,
+    expires_at: Option<OffsetDateTime>,
+) -> Result<JoinLink, JoinLinkError> {
+    if max_uses.is_some_and(|max_uses| max_uses < 1) {
+        return Err(JoinLinkError::InvalidMaxUses);
+    }
+    if expires_at.is_some_and(|expires_at| expires_at <= OffsetDateTime::now_utc()) {
+        return Err(JoinLinkError::InvalidExpiry);
+    }
+    let pool = get_connection().await;
+    match sqlx::query(
+        "INSERT INTO join_links (id, team_id, created_by, code, team_role, requires_approval, max_uses, expires_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(team_id)
+    .bind(created_by)
+    .bind(generate_join_link_code())
+    .bind(team_role.to_string())
+    .bind(requires_approval)
+    .bind(max_uses)
+    .bind(expires_at.unwrap_or_else(|| OffsetDateTime::now_utc() + join_link_lifetime()))
+    .fetch_one(&pool)
+    .await
+    {
+        Ok(row) => JoinLink::from_row(&row).map_err(|_| JoinLinkError::JoinLinkCreationFailed),
+        Err(err) => {
+            println!("Error creating join link: {:?}", err);
+            Err(JoinLinkError::JoinLinkCreationFailed)
+        }
+    }
+}
+
+pub async fn team_join_links(team_id: &str) -> Result<Vec<JoinLink>, sqlx::Error> {
+    let pool = get_connection().await;
+    let rows = sqlx::query(
+        "SELECT * FROM join_links WHERE team_id = $1 AND archived_at IS NULL ORDER BY created_at ASC",
+    )
+    .bind(team_id)
+    .fetch_all(&pool)
+    .await?;
+    rows.iter().map(JoinLink::from_row).collect()
+}
+
+/// Everyone who became a member through the link, including those who have
+/// since left.
+pub async fn join_link_members(join_link_id: &str) -> Result<Vec<User>, sqlx::Error> {
+    let pool = get_connection().await;
+    let rows = sqlx::query(
+        "SELECT users.* FROM users JOIN teams_users ON teams_users.user_id = users.id WHERE teams_users.join_link_id = $1 ORDER BY teams_users.created_at ASC",
+    )
+    .bind(join_link_id)
+    .fetch_all(&pool)
+    .await?;
+    rows.iter().map(User::from_row).collect()
+}
+
+/// Finds a link by its code, as long as it and its team are still around.
+pub async fn find_join_link(code: &str) -> Result<JoinLink, JoinLinkError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "SELECT join_links.* FROM join_links JOIN teams ON teams.id = join_links.team_id WHERE join_links.code = $1 AND join_links.archived_at IS NULL AND teams.archived_at IS NULL",
+    )
+    .bind(code)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => JoinLink::from_row(&row).map_err(|_| JoinLinkError::JoinLinkNotFound),
+        Ok(None) => Err(JoinLinkError::JoinLinkNotFound),
+        Err(err) => {
+            println!("Error finding join link: {:?}", err);
+            Err(JoinLinkError::JoinLinkNotFound)
+        }
+    }
+}
+
+pub async fn revoke_join_link(
+    team_id: &str,
+    join_link_id: &str,
+) -> Result<JoinLink, JoinLinkError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE join_links SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP), updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND team_id = $2 AND archived_at IS NULL RETURNING *",
+    )
+    .bind(join_link_id)
+    .bind(team_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => JoinLink::from_row(&row).map_err(|_| JoinLinkError::JoinLinkUpdateFailed),
+        Ok(None) => Err(JoinLinkError::JoinLinkNotFound),
+        Err(err) => {
+            println!("Error revoking join link: {:?}", err);
+            Err(JoinLinkError::JoinLinkUpdateFailed)
+        }
+    }
+}
+
+/// Uses a join link for `user_id`: they become a member straight away, or
+/// file a join request when the link requires approval. Either way it
+/// counts as one use of the link.
+pub async fn use_join_link(code: &str, user_id: &str) -> Result<JoinOutcome, JoinLinkError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting join: {:?}", err);
+        JoinLinkError::JoinLinkUpdateFailed
+    })?;
+
+    let join_link = match sqlx::query(
+        "SELECT join_links.* FROM join_links JOIN teams ON teams.id = join_links.team_id WHERE join_links.code = $1 AND join_links.archived_at IS NULL AND teams.archived_at IS NULL FOR UPDATE OF join_links",
+    )
+    .bind(code)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(row)) => {
+            JoinLink::from_row(&row).map_err(|_| JoinLinkError::JoinLinkUpdateFailed)?
+        }
+        Ok(None) => return Err(JoinLinkError::JoinLinkNotFound),
+        Err(err) => {
+            println!("Error finding join link: {:?}", err);
+            return Err(JoinLinkError::JoinLinkUpdateFailed);
+        }
+    };
+    usable(&join_link)?;
+    let team_id = join_link.team_id.clone().unwrap_or_default();
+    // Links only work inside the team's organization; to anyone else the
+    // link doesn't exist.
+    if !is_in_team_organization(&team_id, user_id).await {
+        return Err(JoinLinkError::JoinLinkNotFound);
+    }
+    if is_team_member(&team_id, user_id).await {
+        return Err(JoinLinkError::AlreadyTeamMember);
+    }
+
+    let team_role = join_link.team_role.clone().unwrap_or(TeamRole::Member);
+    let outcome = if join_link.requires_approval {
+        match sqlx::query(
+            "INSERT INTO join_requests (id, team_id, user_id, join_link_id, team_role) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&team_id)
+        .bind(user_id)
+        .bind(&join_link.id)
+        .bind(team_role.to_string())
+        .fetch_one(&mut *tx)
+        .await
+        {
+            Ok(row) => JoinOutcome::Requested(
+                JoinRequest::from_row(&row).map_err(|_| JoinLinkError::JoinLinkUpdateFailed)?,
+            ),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                return Err(JoinLinkError::JoinRequestAlreadyPending)
+            }
+            Err(err) => {
+                println!("Error creating join request: {:?}", err);
+                return Err(JoinLinkError::JoinLinkUpdateFailed);
+            }
+        }
+    } else {
+        let team_user = sqlx::query(
+            "INSERT INTO teams_users (id, team_id, user_id, team_role, join_link_id) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (team_id, user_id) DO UPDATE SET team_role = EXCLUDED.team_role, join_link_id = EXCLUDED.join_link_id, archived_at = NULL, updated_at = CURRENT_TIMESTAMP RETURNING *",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&team_id)
+        .bind(user_id)
+        .bind(team_role.to_string())
+        .bind(&join_link.id)
+        .fetch_one(&mut *tx)
+        .await
+        .and_then(|row| TeamUser::from_row(&row))
+        .map_err(|err| {
+            println!("Error creating team membership: {:?}", err);
+            JoinLinkError::JoinLinkUpdateFailed
+        })?;
+        JoinOutcome::Joined(team_user)
+    };
+
+    if let Err(err) = sqlx::query(
+        "UPDATE join_links SET use_count = use_count + 1, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+    )
+    .bind(&join_link.id)
+    .execute(&mut *tx)
+    .await
+    {
+        println!("Error counting join link use: {:?}", err);
+        return Err(JoinLinkError::JoinLinkUpdateFailed);
+    }
+
+    tx.commit().await.map_err(|err| {
+        println!("Error committing join: {:?}", err);
+        JoinLinkError::JoinLinkUpdateFailed
+    })?;
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usable() {
+        let mut join_link = JoinLink {
+            id: None,
+            team_id: None,
+            created_by: None,
+            code: Some(generate_join_link_code()),
+            team_role: Some(TeamRole::Member),
+            requires_approval: false,
+            max_uses: Some(2),
+            use_count: 1,
+            expires_at: Some(OffsetDateTime::now_utc() + Duration::days(1)),
+            revoked_at: None,
+            created_at: None,
+            updated_at: None,
+            archived_at: None,
+        };
+        assert_eq!(
+            join_link.code.as_ref().unwrap().len(),
+            JOIN_LINK_CODE_LENGTH
+        );
+        assert!(usable(&join_link).is_ok());
+
+        join_link.use_count = 2;
+        assert!(matches!(
+            usable(&join_link),
+            Err(JoinLinkError::JoinLinkExhausted)
+        ));
+
+        join_link.expires_at = Some(OffsetDateTime::now_utc() - Duration::days(1));
+        assert!(matches!(
+            usable(&join_link),
+            Err(JoinLinkError::JoinLinkExpired)
+        ));
+
+        join_link.revoked_at = Some(OffsetDateTime::now_utc());
+        assert!(matches!(
+            usable(&join_link),
+            Err(JoinLinkError::JoinLinkRevoked)
+        ));
+    }
+}
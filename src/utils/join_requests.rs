@@ -0,0 +1,250 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::join_request::{JoinRequest, JoinRequestError, JOIN_REQUEST_MESSAGE_MAX_LENGTH};
+use crate::models::team::Team;
+use crate::models::team_role::TeamRole;
+use crate::models::team_user::TeamUser;
+use crate::models::user::User;
+use crate::utils::notifier::{Notification, Notifier};
+use crate::utils::teams::is_team_member;
+
+/// Asks to join a discoverable team as a member. Teams that aren't
+/// discoverable look the same as ones that don't exist.
+pub async fn request_to_join(
+    team_id: &str,
+    user_id: &str,
+    message: Option<&str>,
+) -> Result<JoinRequest, JoinRequestError> {
+    let message = message.map(str::trim).filter(|message| !message.is_empty());
+    if message.is_some_and(|message| message.chars().count() > JOIN_REQUEST_MESSAGE_MAX_LENGTH) {
+        return Err(JoinRequestError::MessageTooLong);
+    }
+    if is_team_member(team_id, user_id).await {
+        return Err(JoinRequestError::RequesterAlreadyMember);
+    }
+
+    let pool = get_connection().await;
+    match sqlx::query(
+        "INSERT INTO join_requests (id, team_id, user_id, team_role, message) SELECT $1, id, $3, $4, $5 FROM teams WHERE id = $2 AND is_discoverable AND archived_at IS NULL AND organization_id IN (SELECT organization_id FROM organizations_users WHERE user_id = $3 AND archived_at IS NULL) RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(team_id)
+    .bind(user_id)
+    .bind(TeamRole::Member.to_string())
+    .bind(message)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => {
+            JoinRequest::from_row(&row).map_err(|_| JoinRequestError::JoinRequestCreationFailed)
+        }
+        Ok(None) => Err(JoinRequestError::TeamNotDiscoverable),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            Err(JoinRequestError::JoinRequestAlreadyPending)
+        }
+        Err(err) => {
+            println!("Error creating join request: {:?}", err);
+            Err(JoinRequestError::JoinRequestCreationFailed)
+        }
+    }
+}
+
+/// Every request the user has made, newest first.
+pub async fn user_join_requests(user_id: &str) -> Result<Vec<JoinRequest>, sqlx::Error> {
+    let pool = get_connection().await;
+    let rows = sqlx::query(
+        "SELECT * FROM join_requests WHERE user_id = $1 AND archived_at IS NULL ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(&pool)
+    .await?;
+    rows.iter().map(JoinRequest::from_row).collect()
+}
+
+/// Requests still waiting for a decision, oldest first.
+pub async fn pending_join_requests(team_id: &str) -> Result<Vec<JoinRequest>, sqlx::Error> {
+    let pool = get_connection().await;
+    let rows = sqlx::query(
+        "SELECT * FROM join_requests WHERE team_id = $1 AND approved_at IS NULL AND denied_at IS NULL AND archived_at IS NULL ORDER BY created_at ASC",
+    )
+    .bind(team_id)
+    .fetch_all(&pool)
+    .await?;
+    rows.iter().map(JoinRequest::from_row).collect()
+}
+
+async fn lock_pending_request(
+    tx: &mut Transaction<'_, Postgres>,
+    team_id: &str,
+    join_request_id: &str,
+) -> Result<JoinRequest, JoinRequestError> {
+    let join_request = match sqlx::query(
+        "SELECT join_requests.* FROM join_requests JOIN teams ON teams.id = join_requests.team_id WHERE join_requests.id = $1 AND join_requests.team_id = $2 AND join_requests.archived_at IS NULL AND teams.archived_at IS NULL FOR UPDATE OF join_requests",
+    )
+    .bind(join_request_id)
+    .bind(team_id)
+    .fetch_optional(&mut **tx)
+    .await
+    {
+        Ok(Some(row)) => JoinRequest::from_row(&row)
+            .map_err(|_| JoinRequestError::JoinRequestUpdateFailed)?,
+        Ok(None) => return Err(JoinRequestError::JoinRequestNotFound),
+        Err(err) => {
+            println!("Error finding join request: {:?}", err);
+            return Err(JoinRequestError::JoinRequestUpdateFailed);
+        }
+    };
+    if !join_request.is_pending() {
+        return Err(JoinRequestError::JoinRequestAlreadyDecided);
+    }
+    Ok(join_request)
+}
+
+async fn decide(
+    tx: &mut Transaction<'_, Postgres>,
+    query: &str,
+    join_request_id: &str,
+    decided_by: &str,
+) -> Result<JoinRequest, JoinRequestError> {
+    sqlx::query(query)
+        .bind(join_request_id)
+        .bind(decided_by)
+        .fetch_one(&mut **tx)
+        .await
+        .and_then(|row| JoinRequest::from_row(&row))
+        .map_err(|err| {
+            println!("Error deciding join request: {:?}", err);
+            JoinRequestError::JoinRequestUpdateFailed
+        })
+}
+
+/// Approves a pending request and makes the requester a member with the
+/// requested role, in one transaction.
+pub async fn approve_join_request(
+    team_id: &str,
+    join_request_id: &str,
+    decided_by: &str,
+) -> Result<(JoinRequest, TeamUser), JoinRequestError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting join request approval: {:?}", err);
+        JoinRequestError::JoinRequestUpdateFailed
+    })?;
+
+    let join_request = lock_pending_request(&mut tx, team_id, join_request_id).await?;
+    let user_id = join_request.user_id.clone().unwrap_or_default();
+    if is_team_member(team_id, &user_id).await {
+        return Err(JoinRequestError::RequesterAlreadyMember);
+    }
+
+    let join_request = decide(
+        &mut tx,
+        "UPDATE join_requests SET approved_at = CURRENT_TIMESTAMP, decided_by = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+        join_request_id,
+        decided_by,
+    )
+    .await?;
+
+    let team_role = join_request.team_role.clone().unwrap_or(TeamRole::Member);
+    let team_user = sqlx::query(
+        "INSERT INTO teams_users (id, team_id, user_id, team_role, join_link_id) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (team_id, user_id) DO UPDATE SET team_role = EXCLUDED.team_role, join_link_id = EXCLUDED.join_link_id, archived_at = NULL, updated_at = CURRENT_TIMESTAMP RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(team_id)
+    .bind(&user_id)
+    .bind(team_role.to_string())
+    .bind(&join_request.join_link_id)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| TeamUser::from_row(&row))
+    .map_err(|err| {
+        println!("Error creating team membership: {:?}", err);
+        JoinRequestError::JoinRequestUpdateFailed
+    })?;
+
+    tx.commit().await.map_err(|err| {
+        println!("Error committing join request approval: {:?}", err);
+        JoinRequestError::JoinRequestUpdateFailed
+    })?;
+    Ok((join_request, team_user))
+}
+
+pub async fn deny_join_request(
+    team_id: &str,
+    join_request_id: &str,
+    decided_by: &str,
+) -> Result<JoinRequest, JoinRequestError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting join request denial: {:?}", err);
+        JoinRequestError::JoinRequestUpdateFailed
+    })?;
+
+    lock_pending_request(&mut tx, team_id, join_request_id).await?;
+    let join_request = decide(
+        &mut tx,
+        "UPDATE join_requests SET denied_at = CURRENT_TIMESTAMP, decided_by = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+        join_request_id,
+        decided_by,
+    )
+    .await?;
+
+    tx.commit().await.map_err(|err| {
+        println!("Error committing join request denial: {:?}", err);
+        JoinRequestError::JoinRequestUpdateFailed
+    })?;
+    Ok(join_request)
+}
+
+fn decision_message(team_name: &str, approved: bool) -> String {
+    if approved {
+        format!(
+            "Your request to join {} has been approved. You can find the team under your teams.",
+            team_name
+        )
+    } else {
+        format!("Your request to join {} has been declined.", team_name)
+    }
+}
+
+/// Lets the requester know how their request was decided. Delivery
+/// problems are logged rather than undoing the decision.
+pub async fn notify_requester(join_request: &JoinRequest, notifier: &dyn Notifier) {
+    let pool = get_connection().await;
+    let user = sqlx::query("SELECT * FROM users WHERE id = $1")
+        .bind(&join_request.user_id)
+        .fetch_one(&pool)
+        .await
+        .and_then(|row| User::from_row(&row));
+    let team = sqlx::query("SELECT * FROM teams WHERE id = $1")
+        .bind(&join_request.team_id)
+        .fetch_one(&pool)
+        .await
+        .and_then(|row| Team::from_row(&row));
+    let (user, team) = match (user, team) {
+        (Ok(user), Ok(team)) => (user, team),
+        (Err(err), _) | (_, Err(err)) => {
+            println!("Error preparing join request notification: {:?}", err);
+            return;
+        }
+    };
+
+    let approved = join_request.approved_at.is_some();
+    let team_name = team.team_name.unwrap_or_default();
+    let notification = Notification {
+        username: user.username.unwrap_or_default(),
+        email: user.email,
+        subject: if approved {
+            format!("You have joined {}", team_name)
+        } else {
+            format!("Your request to join {} was declined", team_name)
+        },
+        body: decision_message(&team_name, approved),
+    };
+    if let Err(err) = notifier.send(&notification).await {
+        println!("Error sending join request notification: {:?}", err);
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rand::{rng, Rng};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::models::authentication::AuthenticationError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AccessClaims {
+    pub sub: String,
+    pub sid: String,
+    pub jti: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Signing keys by `kid`. New tokens are signed with the active key, while
+/// any key still in the ring verifies, so a key can be rotated out by first
+/// adding its replacement, switching `JWT_ACTIVE_KID`, and removing the old
+/// key once the last tokens it signed have expired.
+#[derive(Debug, Clone)]
+pub struct Keyring {
+    active_kid: String,
+    keys: HashMap<String, Vec<u8>>,
+}
+
+impl Keyring {
+    /// Parses `kid:secret` pairs separated by commas. The active key defaults
+    /// to the first one listed.
+    pub fn parse(keys: &str, active_kid: Option<&str>) -> Option<Self> {
+        let mut parsed = HashMap::new();
+        let mut first_kid = None;
+        for entry in keys
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (kid, secret) = entry.split_once(':')?;
+            if kid.is_empty() || secret.is_empty() {
+                return None;
+            }
+            first_kid.get_or_insert_with(|| kid.to_string());
+            parsed.insert(kid.to_string(), secret.as_bytes().to_vec());
+        }
+        let active_kid = active_kid.map(str::to_string).or(first_kid)?;
+        if !parsed.contains_key(&active_kid) {
+            return None;
+        }
+        Some(Self {
+            active_kid,
+            keys: parsed,
+        })
+    }
+
+    /// Reads the keyring from `JWT_KEYS` and `JWT_ACTIVE_KID`. Without them a
+    /// random key is generated, which means tokens don't survive a restart
+    /// and aren't accepted by other instances.
+    fn from_env() -> Self {
+        let keys = std::env::var("JWT_KEYS").unwrap_or_default();
+        let active_kid = std::env::var("JWT_ACTIVE_KID").ok();
+        match Self::parse(&keys, active_kid.as_deref()) {
+            Some(keyring) => keyring,
+            None => {
+                println!("JWT_KEYS is missing or invalid, using a temporary signing key");
+                let mut secret = [0u8; 32];
+                rng().fill(&mut secret[..]);
+                Self {
+                    active_kid: "ephemeral".to_string(),
+                    keys: HashMap::from([("ephemeral".to_string(), secret.to_vec())]),
+                }
+            }
+        }
+    }
+
+    pub fn sign(&self, claims: &AccessClaims) -> Result<String, AuthenticationError> {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(self.active_kid.clone());
+        let key = EncodingKey::from_secret(&self.keys[&self.active_kid]);
+        encode(&header, claims, &key).map_err(|err| {
+            println!("Error signing access token: {:?}", err);
+            AuthenticationError::SessionCreationFailed
+        })
+    }
+
+    pub fn verify(&self, token: &str) -> Result<AccessClaims, AuthenticationError> {
+        let header = decode_header(token).map_err(|_| AuthenticationError::InvalidToken)?;
+        let secret = header
+            .kid
+            .as_ref()
+            .and_then(|kid| self.keys.get(kid))
+            .ok_or(AuthenticationError::InvalidToken)?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        match decode::<AccessClaims>(token, &DecodingKey::from_secret(secret), &validation) {
+            Ok(data) => Ok(data.claims),
+            Err(err) => match err.kind() {
+                jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
+                    Err(AuthenticationError::TokenExpired)
+                }
+                _ => Err(AuthenticationError::InvalidToken),
+            },
+        }
+    }
+}
+
+pub fn keyring() -> &'static Keyring {
+    static KEYRING: OnceLock<Keyring> = OnceLock::new();
+    KEYRING.get_or_init(Keyring::from_env)
+}
+
+pub fn encode_access_token(
+    user_id: &str,
+    session_id: &str,
+    jti: &str,
+    expires_at: OffsetDateTime,
+) -> Result<String, AuthenticationError> {
+    keyring().sign(&AccessClaims {
+        sub: user_id.to_string(),
+        sid: session_id.to_string(),
+        jti: jti.to_string(),
+        iat: OffsetDateTime::now_utc().unix_timestamp(),
+        exp: expires_at.unix_timestamp(),
+    })
+}
+
+pub fn decode_access_token(token: &str) -> Result<AccessClaims, AuthenticationError> {
+    keyring().verify(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    fn claims(expires_at: OffsetDateTime) -> AccessClaims {
+        AccessClaims {
+            sub: "user".to_string(),
+            sid: "session".to_string(),
+            jti: "jti".to_string(),
+            iat: OffsetDateTime::now_utc().unix_timestamp(),
+            exp: expires_at.unix_timestamp(),
+        }
+    }
+
+    #[test]
+    fn test_parse_keyring() {
+        assert!(Keyring::parse("", None).is_none());
+        assert!(Keyring::parse("one", None).is_none());
+        assert!(Keyring::parse("one:secret", Some("two")).is_none());
+        let keyring = Keyring::parse("one:secret, two:other", None).unwrap();
+        assert_eq!(keyring.active_kid, "one");
+        assert_eq!(keyring.keys.len(), 2);
+    }
+
+    #[test]
+    fn test_rotated_keys_still_verify() {
+        let old = Keyring::parse("one:secret", None).unwrap();
+        let rotated = Keyring::parse("one:secret,two:other", Some("two")).unwrap();
+        let retired = Keyring::parse("two:other", None).unwrap();
+        let claims = claims(OffsetDateTime::now_utc() + Duration::minutes(5));
+
+        let token = old.sign(&claims).unwrap();
+        assert_eq!(rotated.verify(&token).unwrap(), claims);
+        assert!(matches!(
+            retired.verify(&token),
+            Err(AuthenticationError::InvalidToken)
+        ));
+
+        let token = rotated.sign(&claims).unwrap();
+        assert_eq!(retired.verify(&token).unwrap(), claims);
+    }
+
+    #[test]
+    fn test_expired_token_is_rejected() {
+        let keyring = Keyring::parse("one:secret", None).unwrap();
+        let token = keyring
+            .sign(&claims(OffsetDateTime::now_utc() - Duration::minutes(1)))
+            .unwrap();
+        assert!(matches!(
+            keyring.verify(&token),
+            Err(AuthenticationError::TokenExpired)
+        ));
+    }
+}
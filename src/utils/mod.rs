@@ -1,4 +1,26 @@
+pub mod account_deletion;
+pub mod admin;
+pub mod api_keys;
+pub mod audit;
 pub mod backup_codes;
+pub mod data_exports;
+pub mod denylist;
+pub mod invitations;
+pub mod jobs;
+pub mod join_links;
+pub mod join_requests;
+pub mod jwt;
+pub mod notifier;
+pub mod oidc;
+pub mod organizations;
+pub mod password_resets;
 pub mod passwords;
+pub mod sessions;
 pub mod strings;
+pub mod team_archival;
+pub mod team_hierarchy;
+pub mod teams;
+pub mod throttle;
 pub mod time;
+pub mod token_hash;
+pub mod totp;
@@ -0,0 +1,167 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum NotifierError {
+    MissingRecipient,
+    DeliveryFailed,
+}
+
+impl std::fmt::Display for NotifierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotifierError::MissingRecipient => write!(f, "No address to deliver to"),
+            NotifierError::DeliveryFailed => write!(f, "Message delivery failed"),
+        }
+    }
+}
+
+impl std::error::Error for NotifierError {}
+
+/// An outbound message for a user. `email` is only needed by transports that
+/// actually send mail.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub username: String,
+    pub email: Option<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+impl Notification {
+    fn render(&self) -> String {
+        format!(
+            "To: {} <{}>\nSubject: {}\n\n{}\n",
+            self.username,
+            self.email.clone().unwrap_or_default(),
+            self.subject,
+            self.body
+        )
+    }
+}
+
+#[rocket::async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifierError>;
+}
+
+/// The notifier handed to routes through Rocket's managed state.
+pub type SharedNotifier = Arc<dyn Notifier>;
+
+pub struct StdoutNotifier;
+
+#[rocket::async_trait]
+impl Notifier for StdoutNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        println!("{}", notification.render());
+        Ok(())
+    }
+}
+
+/// Appends every message to a file, for local development and tests.
+pub struct FileNotifier {
+    path: String,
+}
+
+impl FileNotifier {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[rocket::async_trait]
+impl Notifier for FileNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| {
+                println!("Error opening notification file: {:?}", err);
+                NotifierError::DeliveryFailed
+            })?;
+        writeln!(file, "{}", notification.render()).map_err(|err| {
+            println!("Error writing notification file: {:?}", err);
+            NotifierError::DeliveryFailed
+        })
+    }
+}
+
+pub struct SmtpNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpNotifier {
+    /// Configured from `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`,
+    /// `SMTP_PASSWORD` and `SMTP_FROM`. Connections use STARTTLS.
+    pub fn from_env() -> Result<Self, String> {
+        let host = std::env::var("SMTP_HOST").map_err(|_| "SMTP_HOST must be set")?;
+        let from = std::env::var("SMTP_FROM")
+            .map_err(|_| "SMTP_FROM must be set")?
+            .parse::<Mailbox>()
+            .map_err(|err| format!("Invalid SMTP_FROM: {}", err))?;
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+            .map_err(|err| format!("Invalid SMTP_HOST: {}", err))?;
+        if let Some(port) = std::env::var("SMTP_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+        {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (
+            std::env::var("SMTP_USERNAME"),
+            std::env::var("SMTP_PASSWORD"),
+        ) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl Notifier for SmtpNotifier {
+    async fn send(&self, notification: &Notification) -> Result<(), NotifierError> {
+        let to = notification
+            .email
+            .as_ref()
+            .and_then(|email| email.parse::<Mailbox>().ok())
+            .ok_or(NotifierError::MissingRecipient)?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(notification.subject.clone())
+            .body(notification.body.clone())
+            .map_err(|err| {
+                println!("Error building message: {:?}", err);
+                NotifierError::DeliveryFailed
+            })?;
+        match self.transport.send(message).await {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                println!("Error sending message: {:?}", err);
+                Err(NotifierError::DeliveryFailed)
+            }
+        }
+    }
+}
+
+/// Picks the notifier named by `NOTIFIER`: `smtp`, `file` (written to
+/// `NOTIFIER_FILE`, default `notifications.log`) or `stdout`, the default.
+pub fn notifier_from_env() -> SharedNotifier {
+    match std::env::var("NOTIFIER").unwrap_or_default().as_str() {
+        "smtp" => Arc::new(SmtpNotifier::from_env().expect("Invalid SMTP configuration")),
+        "file" => Arc::new(FileNotifier::new(
+            std::env::var("NOTIFIER_FILE").unwrap_or("notifications.log".to_string()),
+        )),
+        _ => Arc::new(StdoutNotifier),
+    }
+}
@@ -0,0 +1,371 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::organization::{Organization, OrganizationError};
+use crate::models::organization_role::OrganizationRole;
+use crate::models::organization_user::OrganizationUser;
+use crate::models::team::Team;
+use crate::models::user::User;
+
+/// The user's role in the organization, if they are an active member of it.
+pub async fn organization_role(organization_id: &str, user_id: &str) -> Option<OrganizationRole> {
+    let pool = get_connection().await;
+    let query = "SELECT organizations_users.organization_role FROM organizations_users JOIN users ON users.id = organizations_users.user_id WHERE organizations_users.organization_id = $1 AND organizations_users.user_id = $2 AND organizations_users.archived_at IS NULL AND users.archived_at IS NULL";
+    match sqlx::query_scalar::<_, String>(query)
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+    {
+        Ok(role) => role.and_then(|role| role.parse().ok()),
+        Err(err) => {
+            println!("Error finding organization role: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Whether the user belongs to the organization the team is part of.
+pub async fn is_in_team_organization(team_id: &str, user_id: &str) -> bool {
+    let pool = get_connection().await;
+    let query = "SELECT EXISTS (SELECT 1 FROM teams JOIN organizations_users ON organizations_users.organization_id = teams.organization_id WHERE teams.id = $1 AND organizations_users.user_id = $2 AND organizations_users.archived_at IS NULL)";
+    match sqlx::query_scalar::<_, bool>(query)
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+    {
+        Ok(is_member) => is_member,
+        Err(err) => {
+            println!("Error checking organization membership: {:?}", err);
+            false
+        }
+    }
+}
+
+/// Active organizations the user belongs to.
+pub async fn user_organizations(user_id: &str) -> Result<Vec<Organization>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM organizations WHERE archived_at IS NULL AND id IN (SELECT organization_id FROM organizations_users WHERE user_id = $1 AND archived_at IS NULL) ORDER BY organization_name ASC";
+    let rows = sqlx::query(query).bind(user_id).fetch_all(&pool).await?;
+    rows.iter().map(Organization::from_row).collect()
+}
+
+/// Active users who share at least one organization with `user_id`,
+/// including the user themselves.
+pub async fn organization_peers(user_id: &str) -> Result<Vec<User>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM users WHERE archived_at IS NULL AND id IN (SELECT user_id FROM organizations_users WHERE archived_at IS NULL AND organization_id IN (SELECT organizations_users.organization_id FROM organizations_users JOIN organizations ON organizations.id = organizations_users.organization_id WHERE organizations_users.user_id = $1 AND organizations_users.archived_at IS NULL AND organizations.archived_at IS NULL)) ORDER BY created_at ASC";
+    let rows = sqlx::query(query).bind(user_id).fetch_all(&pool).await?;
+    rows.iter().map(User::from_row).collect()
+}
+
+/// The organization's active members along with their memberships.
+pub async fn organization_members(
+    organization_id: &str,
+) -> Result<Vec<(User, OrganizationUser)>, sqlx::Error> {
+    let pool = get_connection().await;
+    let memberships = sqlx::query(
+        "SELECT organizations_users.* FROM organizations_users JOIN users ON users.id = organizations_users.user_id WHERE organizations_users.organization_id = $1 AND organizations_users.archived_at IS NULL AND users.archived_at IS NULL ORDER BY organizations_users.created_at ASC",
+    )
+    .bind(organization_id)
+    .fetch_all(&pool)
+    .await?
+    .iter()
+    .map(OrganizationUser::from_row)
+    .collect::<Result<Vec<OrganizationUser>, sqlx::Error>>()?;
+    let users = sqlx::query(
+        "SELECT users.* FROM users JOIN organizations_users ON organizations_users.user_id = users.id WHERE organizations_users.organization_id = $1 AND organizations_users.archived_at IS NULL AND users.archived_at IS NULL",
+    )
+    .bind(organization_id)
+    .fetch_all(&pool)
+    .await?
+    .iter()
+    .map(User::from_row)
+    .collect::<Result<Vec<User>, sqlx::Error>>()?;
+    Ok(memberships
+        .into_iter()
+        .filter_map(|membership| {
+            let user = users
+                .iter()
+                .find(|user| user.id == membership.user_id)?
+                .clone();
+            Some((user, membership))
+        })
+        .collect())
+}
+
+/// Active teams in the organization.
+pub async fn organization_teams(organization_id: &str) -> Result<Vec<Team>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM teams WHERE organization_id = $1 AND archived_at IS NULL ORDER BY team_name ASC";
+    let rows = sqlx::query(query)
+        .bind(organization_id)
+        .fetch_all(&pool)
+        .await?;
+    rows.iter().map(Team::from_row).collect()
+}
+
+async fn upsert_membership(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: &str,
+    user_id: &str,
+    organization_role: &OrganizationRole,
+) -> Result<OrganizationUser, sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO organizations_users (id, organization_id, user_id, organization_role) VALUES ($1, $2, $3, $4) ON CONFLICT (organization_id, user_id) DO UPDATE SET organization_role = EXCLUDED.organization_role, archived_at = NULL, updated_at = CURRENT_TIMESTAMP RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(organization_id)
+    .bind(user_id)
+    .bind(organization_role.to_string())
+    .fetch_one(&mut **tx)
+    .await
+    .and_then(|row| OrganizationUser::from_row(&row))
+}
+
+/// Creates an organization with `owner_id` as its first owner.
+pub async fn create_organization(
+    organization_name: &str,
+    organization_description: Option<&str>,
+    owner_id: &str,
+) -> Result<Organization, OrganizationError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting organization creation: {:?}", err);
+        OrganizationError::OrganizationCreationFailed
+    })?;
+    let organization = sqlx::query(
+        "INSERT INTO organizations (id, organization_name, organization_description) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(organization_name)
+    .bind(organization_description)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| Organization::from_row(&row))
+    .map_err(|err| {
+        println!("Error creating organization: {:?}", err);
+        OrganizationError::OrganizationCreationFailed
+    })?;
+    upsert_membership(
+        &mut tx,
+        organization.id.as_deref().unwrap_or_default(),
+        owner_id,
+        &OrganizationRole::Owner,
+    )
+    .await
+    .map_err(|err| {
+        println!("Error adding organization owner: {:?}", err);
+        OrganizationError::OrganizationCreationFailed
+    })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing organization creation: {:?}", err);
+        OrganizationError::OrganizationCreationFailed
+    })?;
+    Ok(organization)
+}
+
+/// Adds an existing user to the organization.
+pub async fn add_organization_member(
+    organization_id: &str,
+    user_id: &str,
+    organization_role: &OrganizationRole,
+) -> Result<OrganizationUser, OrganizationError> {
+    if organization_role_of(organization_id, user_id)
+        .await
+        .is_some()
+    {
+        return Err(OrganizationError::AlreadyOrganizationMember);
+    }
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting organization membership: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    let membership = upsert_membership(&mut tx, organization_id, user_id, organization_role)
+        .await
+        .map_err(|err| {
+            println!("Error adding organization member: {:?}", err);
+            OrganizationError::OrganizationUpdateFailed
+        })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing organization membership: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    Ok(membership)
+}
+
+/// Like [`organization_role`], but also finds memberships of archived
+/// accounts, so they can't be added a second time.
+async fn organization_role_of(organization_id: &str, user_id: &str) -> Option<OrganizationRole> {
+    let pool = get_connection().await;
+    let query = "SELECT organization_role FROM organizations_users WHERE organization_id = $1 AND user_id = $2 AND archived_at IS NULL";
+    sqlx::query_scalar::<_, String>(query)
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|role| role.parse().ok())
+}
+
+/// Locks and returns the member's active membership.
+async fn lock_organization_membership(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: &str,
+    user_id: &str,
+) -> Result<OrganizationUser, OrganizationError> {
+    match sqlx::query(
+        "SELECT * FROM organizations_users WHERE organization_id = $1 AND user_id = $2 AND archived_at IS NULL FOR UPDATE",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await
+    {
+        Ok(Some(row)) => OrganizationUser::from_row(&row)
+            .map_err(|_| OrganizationError::OrganizationUpdateFailed),
+        Ok(None) => Err(OrganizationError::NotOrganizationMember),
+        Err(err) => {
+            println!("Error finding organization member: {:?}", err);
+            Err(OrganizationError::OrganizationUpdateFailed)
+        }
+    }
+}
+
+/// Locks the organization's owners and says whether any would remain
+/// without `user_id`.
+async fn has_other_owner(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: &str,
+    user_id: &str,
+) -> Result<bool, OrganizationError> {
+    match sqlx::query_scalar::<_, String>(
+        "SELECT organizations_users.user_id FROM organizations_users JOIN users ON users.id = organizations_users.user_id WHERE organizations_users.organization_id = $1 AND organizations_users.organization_role = $2 AND organizations_users.archived_at IS NULL AND users.archived_at IS NULL FOR UPDATE OF organizations_users",
+    )
+    .bind(organization_id)
+    .bind(OrganizationRole::Owner.to_string())
+    .fetch_all(&mut **tx)
+    .await
+    {
+        Ok(owners) => Ok(owners.iter().any(|owner| owner != user_id)),
+        Err(err) => {
+            println!("Error counting organization owners: {:?}", err);
+            Err(OrganizationError::OrganizationUpdateFailed)
+        }
+    }
+}
+
+/// Changes a member's role. Demoting the last owner is refused.
+pub async fn change_organization_role(
+    organization_id: &str,
+    user_id: &str,
+    organization_role: &OrganizationRole,
+) -> Result<OrganizationUser, OrganizationError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting organization role change: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    let membership = lock_organization_membership(&mut tx, organization_id, user_id).await?;
+    if membership.organization_role == Some(OrganizationRole::Owner)
+        && *organization_role != OrganizationRole::Owner
+        && !has_other_owner(&mut tx, organization_id, user_id).await?
+    {
+        return Err(OrganizationError::LastOrganizationOwner);
+    }
+    let membership = upsert_membership(&mut tx, organization_id, user_id, organization_role)
+        .await
+        .map_err(|err| {
+            println!("Error changing organization role: {:?}", err);
+            OrganizationError::OrganizationUpdateFailed
+        })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing organization role change: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    Ok(membership)
+}
+
+/// Takes a member out of the organization, along with their memberships of
+/// its teams; their open activities there are unassigned. Owners of teams
+/// in the organization have to hand those over first, and the last owner
+/// can't leave.
+pub async fn remove_organization_member(
+    organization_id: &str,
+    user_id: &str,
+) -> Result<OrganizationUser, OrganizationError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting organization member removal: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    let membership = lock_organization_membership(&mut tx, organization_id, user_id).await?;
+    if membership.organization_role == Some(OrganizationRole::Owner)
+        && !has_other_owner(&mut tx, organization_id, user_id).await?
+    {
+        return Err(OrganizationError::LastOrganizationOwner);
+    }
+    let owns_teams = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM teams WHERE organization_id = $1 AND owner_id = $2 AND archived_at IS NULL)",
+    )
+    .bind(organization_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or(true);
+    if owns_teams {
+        return Err(OrganizationError::MemberOwnsTeams);
+    }
+
+    let statements = [
+        "UPDATE activities SET assigned_to = NULL, updated_at = CURRENT_TIMESTAMP WHERE assigned_to = $2 AND ended_at IS NULL AND archived_at IS NULL AND team_id IN (SELECT id FROM teams WHERE organization_id = $1)",
+        "UPDATE teams_users SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE user_id = $2 AND archived_at IS NULL AND team_id IN (SELECT id FROM teams WHERE organization_id = $1)",
+    ];
+    for statement in statements {
+        if let Err(err) = sqlx::query(statement)
+            .bind(organization_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await
+        {
+            println!("Error running `{}`: {:?}", statement, err);
+            return Err(OrganizationError::OrganizationUpdateFailed);
+        }
+    }
+    let membership = sqlx::query(
+        "UPDATE organizations_users SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(&membership.id)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| OrganizationUser::from_row(&row))
+    .map_err(|err| {
+        println!("Error removing organization member: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing organization member removal: {:?}", err);
+        OrganizationError::OrganizationUpdateFailed
+    })?;
+    Ok(membership)
+}
+
+/// Puts a newly created account into the organization named by
+/// `DEFAULT_ORGANIZATION_ID`, if one is configured. Single-tenant
+/// deployments use this so everyone keeps seeing everyone.
+pub async fn join_default_organization(user_id: &str) {
+    let organization_id = match std::env::var("DEFAULT_ORGANIZATION_ID") {
+        Ok(organization_id) if !organization_id.is_empty() => organization_id,
+        _ => return,
+    };
+    if let Err(err) =
+        add_organization_member(&organization_id, user_id, &OrganizationRole::Member).await
+    {
+        println!("Error joining default organization: {:?}", err);
+    }
+}
@@ -0,0 +1,123 @@
+use time::format_description::well_known::Iso8601;
+use time::{Duration, OffsetDateTime};
+
+use crate::database::connection::get_connection;
+use crate::database::values::DatabaseValue;
+use crate::models::password_reset::{PasswordReset, PasswordResetError};
+use crate::models::user::User;
+use crate::utils::notifier::{Notification, Notifier};
+use crate::utils::sessions::generate_token;
+use crate::utils::token_hash::hash_token;
+use crate::{find_one_resource_where_fields, insert_resource};
+
+const DEFAULT_PASSWORD_RESET_TTL_MINUTES: i64 = 30;
+
+/// Lifetime of a password reset token, from `PASSWORD_RESET_TTL_MINUTES`.
+pub fn password_reset_lifetime() -> Duration {
+    Duration::minutes(
+        std::env::var("PASSWORD_RESET_TTL_MINUTES")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(DEFAULT_PASSWORD_RESET_TTL_MINUTES),
+    )
+}
+
+/// Builds the text sent to the user. When `PASSWORD_RESET_URL` is set the
+/// token is appended to it as a `token` query parameter, otherwise the bare
+/// token is sent.
+fn reset_message(token: &str) -> String {
+    let link = match std::env::var("PASSWORD_RESET_URL") {
+        Ok(url) if !url.is_empty() => {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            format!("{}{}token={}", url, separator, token)
+        }
+        _ => token.to_string(),
+    };
+    format!(
+        "A password reset was requested for your account. Use this to choose a new password within {} minutes:\n\n{}\n\nIf you didn't ask for this you can ignore this message.",
+        password_reset_lifetime().whole_minutes(),
+        link
+    )
+}
+
+/// Issues a new reset token for a user and sends it through the notifier.
+/// Any earlier tokens that were not used yet stop working.
+pub async fn request_password_reset(
+    user: &User,
+    notifier: &dyn Notifier,
+) -> Result<(), PasswordResetError> {
+    let user_id = user.id.clone().unwrap_or_default();
+    let pool = get_connection().await;
+    if let Err(err) = sqlx::query(
+        "UPDATE password_resets SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND archived_at IS NULL",
+    )
+    .bind(&user_id)
+    .execute(&pool)
+    .await
+    {
+        println!("Error retiring password resets: {:?}", err);
+        return Err(PasswordResetError::ResetFailed);
+    }
+
+    let token = generate_token();
+    let expires_at = OffsetDateTime::now_utc() + password_reset_lifetime();
+    let reset_params = vec![
+        ("user_id", DatabaseValue::String(user_id)),
+        ("token", DatabaseValue::String(hash_token(&token))),
+        (
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ),
+    ];
+    if let Err(err) = insert_resource!(PasswordReset, reset_params).await {
+        println!("Error creating password reset: {:?}", err);
+        return Err(PasswordResetError::ResetFailed);
+    }
+
+    let notification = Notification {
+        username: user.username.clone().unwrap_or_default(),
+        email: user.email.clone(),
+        subject: "Reset your password".to_string(),
+        body: reset_message(&token),
+    };
+    notifier.send(&notification).await.map_err(|err| {
+        println!("Error sending password reset: {:?}", err);
+        PasswordResetError::ResetFailed
+    })
+}
+
+/// Uses up a reset token and returns the id of the user it was issued for.
+/// The update only matches unarchived rows, so a token can't be used twice
+/// even by concurrent requests.
+pub async fn consume_password_reset(token: &str) -> Result<String, PasswordResetError> {
+    let reset_params = [("token", DatabaseValue::String(hash_token(token.trim())))];
+    let password_reset = match find_one_resource_where_fields!(PasswordReset, reset_params).await {
+        Ok(password_reset) => password_reset,
+        Err(err) => {
+            println!("Error finding password reset: {:?}", err);
+            return Err(PasswordResetError::TokenNotValid);
+        }
+    };
+    if password_reset.is_used() {
+        return Err(PasswordResetError::TokenAlreadyUsed);
+    }
+    if password_reset.is_expired() {
+        return Err(PasswordResetError::TokenExpired);
+    }
+
+    let pool = get_connection().await;
+    let query = "UPDATE password_resets SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND archived_at IS NULL";
+    match sqlx::query(query)
+        .bind(&password_reset.id)
+        .execute(&pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 1 => Ok(password_reset.user_id.unwrap_or_default()),
+        Ok(_) => Err(PasswordResetError::TokenAlreadyUsed),
+        Err(err) => {
+            println!("Error consuming password reset: {:?}", err);
+            Err(PasswordResetError::ResetFailed)
+        }
+    }
+}
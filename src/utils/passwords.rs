@@ -1,5 +1,191 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rocket::tokio::task::spawn_blocking;
 use sha2::{Digest, Sha512};
+use std::sync::OnceLock;
 
-pub fn hash_password(password: &str) -> String {
+const DEFAULT_MEMORY_COST_KIB: u32 = 19456;
+const DEFAULT_TIME_COST: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    ValidNeedsRehash,
+}
+
+impl PasswordVerification {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, PasswordVerification::Invalid)
+    }
+
+    pub fn needs_rehash(&self) -> bool {
+        matches!(self, PasswordVerification::ValidNeedsRehash)
+    }
+}
+
+fn env_u32(name: &str, default: u32) -> u32 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Argon2id cost parameters, configurable through `PASSWORD_HASH_MEMORY_COST`
+/// (KiB), `PASSWORD_HASH_TIME_COST` and `PASSWORD_HASH_PARALLELISM`.
+fn password_params() -> Params {
+    let memory_cost = env_u32("PASSWORD_HASH_MEMORY_COST", DEFAULT_MEMORY_COST_KIB);
+    let time_cost = env_u32("PASSWORD_HASH_TIME_COST", DEFAULT_TIME_COST);
+    let parallelism = env_u32("PASSWORD_HASH_PARALLELISM", DEFAULT_PARALLELISM);
+    match Params::new(memory_cost, time_cost, parallelism, None) {
+        Ok(params) => params,
+        Err(err) => {
+            println!(
+                "Invalid password hash parameters, using defaults: {:?}",
+                err
+            );
+            Params::new(
+                DEFAULT_MEMORY_COST_KIB,
+                DEFAULT_TIME_COST,
+                DEFAULT_PARALLELISM,
+                None,
+            )
+            .unwrap()
+        }
+    }
+}
+
+fn hasher() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, password_params())
+}
+
+fn legacy_hash_password(password: &str) -> String {
     format!("{:x}", Sha512::digest(password.as_bytes()))
 }
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+fn hash_password_sync(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    hasher()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Error hashing password")
+        .to_string()
+}
+
+fn verify_password_sync(password: &str, password_hash: &str) -> PasswordVerification {
+    let parsed_hash = match PasswordHash::new(password_hash) {
+        Ok(parsed_hash) => parsed_hash,
+        Err(_) => {
+            let legacy_hash = legacy_hash_password(password);
+            if constant_time_eq(legacy_hash.as_bytes(), password_hash.as_bytes()) {
+                return PasswordVerification::ValidNeedsRehash;
+            }
+            return PasswordVerification::Invalid;
+        }
+    };
+
+    if Argon2::default()
+        .verify_password(password.as_bytes(), &parsed_hash)
+        .is_err()
+    {
+        return PasswordVerification::Invalid;
+    }
+
+    let current_params = password_params();
+    let outdated = parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+        || match Params::try_from(&parsed_hash) {
+            Ok(params) => {
+                params.m_cost() != current_params.m_cost()
+                    || params.t_cost() != current_params.t_cost()
+                    || params.p_cost() != current_params.p_cost()
+            }
+            Err(_) => true,
+        };
+
+    if outdated {
+        PasswordVerification::ValidNeedsRehash
+    } else {
+        PasswordVerification::Valid
+    }
+}
+
+/// Hashes a password with Argon2id off the async executor.
+pub async fn hash_password(password: &str) -> String {
+    let password = password.to_string();
+    spawn_blocking(move || hash_password_sync(&password))
+        .await
+        .expect("Error hashing password")
+}
+
+/// Checks a password against a stored hash off the async executor. Unsalted
+/// SHA-512 hashes from before the switch to Argon2id still verify, but are
+/// flagged for rehashing, as are Argon2 hashes made with different cost
+/// parameters.
+pub async fn verify_password(password: &str, password_hash: &str) -> PasswordVerification {
+    let password = password.to_string();
+    let password_hash = password_hash.to_string();
+    match spawn_blocking(move || verify_password_sync(&password, &password_hash)).await {
+        Ok(verification) => verification,
+        Err(err) => {
+            println!("Error verifying password: {:?}", err);
+            PasswordVerification::Invalid
+        }
+    }
+}
+
+/// Does the same work as [`verify_password`] against a throwaway hash, so a
+/// login for an unknown user takes as long as one with a wrong password.
+pub async fn verify_dummy_password(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let password = password.to_string();
+    let _ = spawn_blocking(move || {
+        let dummy_hash = DUMMY_HASH.get_or_init(|| hash_password_sync("dummy password"));
+        verify_password_sync(&password, dummy_hash)
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify_password() {
+        let hash = hash_password_sync("correct horse");
+        assert!(hash.starts_with("$argon2id$"));
+        assert_ne!(hash, hash_password_sync("correct horse"));
+        assert_eq!(
+            verify_password_sync("correct horse", &hash),
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            verify_password_sync("battery staple", &hash),
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[test]
+    fn test_verify_legacy_password() {
+        let legacy_hash = legacy_hash_password("correct horse");
+        assert_eq!(
+            verify_password_sync("correct horse", &legacy_hash),
+            PasswordVerification::ValidNeedsRehash
+        );
+        assert_eq!(
+            verify_password_sync("battery staple", &legacy_hash),
+            PasswordVerification::Invalid
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use time::format_description::well_known::Iso8601;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::database::connection::get_connection;
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::{Authentication, AuthenticationError};
+use crate::models::refresh_token::RefreshToken;
+use crate::utils::denylist::deny_sessions;
+use crate::utils::token_hash::hash_token;
+use crate::{delete_resource_where_fields, find_all_resources_where_fields, insert_resource};
+
+const DEFAULT_ACCESS_TOKEN_TTL_MINUTES: i64 = 15;
+const DEFAULT_REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+const DEFAULT_SESSION_MAX_LIFETIME_DAYS: i64 = 90;
+const DEFAULT_SECOND_FACTOR_TTL_MINUTES: i64 = 5;
+
+fn env_i64(name: &str, default: i64) -> i64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+/// Lifetime of an access token, from `ACCESS_TOKEN_TTL_MINUTES`.
+pub fn access_token_lifetime() -> Duration {
+    Duration::minutes(env_i64(
+        "ACCESS_TOKEN_TTL_MINUTES",
+        DEFAULT_ACCESS_TOKEN_TTL_MINUTES,
+    ))
+}
+
+/// Lifetime of a refresh token, from `REFRESH_TOKEN_TTL_DAYS`. Every refresh
+/// issues a new one, so an active session keeps sliding forward.
+pub fn refresh_token_lifetime() -> Duration {
+    Duration::days(env_i64(
+        "REFRESH_TOKEN_TTL_DAYS",
+        DEFAULT_REFRESH_TOKEN_TTL_DAYS,
+    ))
+}
+
+/// How long a login can wait on its second factor, from
+/// `SECOND_FACTOR_TTL_MINUTES`.
+pub fn second_factor_lifetime() -> Duration {
+    Duration::minutes(env_i64(
+        "SECOND_FACTOR_TTL_MINUTES",
+        DEFAULT_SECOND_FACTOR_TTL_MINUTES,
+    ))
+}
+
+/// Hard cap on how long a session can be kept alive by refreshing, from
+/// `SESSION_MAX_LIFETIME_DAYS`.
+pub fn session_max_lifetime() -> Duration {
+    Duration::days(env_i64(
+        "SESSION_MAX_LIFETIME_DAYS",
+        DEFAULT_SESSION_MAX_LIFETIME_DAYS,
+    ))
+}
+
+/// Returns `expires_at`, pulled back if needed so it never passes the end of
+/// the session's maximum lifetime.
+pub fn capped_expiry(
+    expires_at: OffsetDateTime,
+    session_created_at: OffsetDateTime,
+    max_lifetime: Duration,
+) -> OffsetDateTime {
+    expires_at.min(session_created_at + max_lifetime)
+}
+
+pub fn generate_token() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Creates a refresh token for a session. The returned value carries the
+/// plaintext token; only its hash is stored.
+pub async fn issue_refresh_token(
+    authentication_id: &str,
+    session_created_at: OffsetDateTime,
+) -> Result<RefreshToken, AuthenticationError> {
+    let expires_at = capped_expiry(
+        OffsetDateTime::now_utc() + refresh_token_lifetime(),
+        session_created_at,
+        session_max_lifetime(),
+    );
+    let token = generate_token();
+    let refresh_params = vec![
+        (
+            "authentication_id",
+            DatabaseValue::String(authentication_id.to_string()),
+        ),
+        ("token", DatabaseValue::String(hash_token(&token))),
+        (
+            "expires_at",
+            DatabaseValue::DateTime(expires_at.format(&Iso8601::DEFAULT).unwrap()),
+        ),
+    ];
+    match insert_resource!(RefreshToken, refresh_params).await {
+        Ok(mut refresh_token) => {
+            refresh_token.token = Some(token);
+            Ok(refresh_token)
+        }
+        Err(err) => {
+            println!("Error creating refresh token: {:?}", err);
+            Err(AuthenticationError::SessionCreationFailed)
+        }
+    }
+}
+
+/// Marks a refresh token as used. Only one caller can win this, so a second
+/// attempt with the same token is reported as reuse.
+pub async fn consume_refresh_token(refresh_token_id: &str) -> Result<(), AuthenticationError> {
+    let pool = get_connection().await;
+    let query = "UPDATE refresh_tokens SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND archived_at IS NULL";
+    match sqlx::query(query)
+        .bind(refresh_token_id)
+        .execute(&pool)
+        .await
+    {
+        Ok(result) if result.rows_affected() == 1 => Ok(()),
+        Ok(_) => Err(AuthenticationError::RefreshTokenReused),
+        Err(err) => {
+            println!("Error consuming refresh token: {:?}", err);
+            Err(AuthenticationError::SessionUpdateFailed)
+        }
+    }
+}
+
+/// Ends a session and, through the foreign key cascade, every refresh token
+/// issued for it. The session id is denylisted so access tokens already
+/// handed out stop working before they expire.
+pub async fn revoke_session(authentication_id: &str) -> Result<(), AuthenticationError> {
+    deny_sessions(
+        &[authentication_id.to_string()],
+        OffsetDateTime::now_utc() + access_token_lifetime(),
+    )
+    .await;
+    let session_params = vec![("id", authentication_id.to_string())];
+    match delete_resource_where_fields!(Authentication, session_params).await {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            println!("Error revoking session: {:?}", err);
+            Err(AuthenticationError::SessionDeletionFailed)
+        }
+    }
+}
+
+/// Revokes every session belonging to a user, optionally keeping one, and
+/// returns how many were revoked.
+pub async fn revoke_user_sessions(
+    user_id: &str,
+    except_authentication_id: Option<&str>,
+) -> Result<usize, AuthenticationError> {
+    let session_params = [("user_id", DatabaseValue::String(user_id.to_string()))];
+    let session_ids = match find_all_resources_where_fields!(Authentication, session_params).await {
+        Ok(authentications) => authentications
+            .into_iter()
+            .map(|authentication| authentication.id)
+            .filter(|id| Some(id.as_str()) != except_authentication_id)
+            .collect::<Vec<String>>(),
+        Err(err) => {
+            println!("Error finding sessions: {:?}", err);
+            return Err(AuthenticationError::SessionNotFound);
+        }
+    };
+    deny_sessions(
+        &session_ids,
+        OffsetDateTime::now_utc() + access_token_lifetime(),
+    )
+    .await;
+
+    let pool = get_connection().await;
+    match sqlx::query("DELETE FROM authentications WHERE id = ANY($1)")
+        .bind(&session_ids)
+        .execute(&pool)
+        .await
+    {
+        Ok(_) => Ok(session_ids.len()),
+        Err(err) => {
+            println!("Error revoking sessions: {:?}", err);
+            Err(AuthenticationError::SessionDeletionFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capped_expiry() {
+        let created_at = OffsetDateTime::now_utc();
+        let max_lifetime = Duration::days(90);
+        let early = created_at + Duration::days(30);
+        assert_eq!(capped_expiry(early, created_at, max_lifetime), early);
+        assert_eq!(
+            capped_expiry(created_at + Duration::days(120), created_at, max_lifetime),
+            created_at + max_lifetime
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use sqlx::{Postgres, Row, Transaction};
+use time::{Duration, OffsetDateTime};
+
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::team::{Team, TeamError};
+use crate::utils::audit::{record_audit_event, AuditAction, AuditEntry};
+use crate::utils::jobs::{interval_from_env, spawn_periodic};
+
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+const DEFAULT_PURGE_INTERVAL_MINUTES: u64 = 60;
+
+/// How long an archived team can still be restored, from
+/// `TEAM_RETENTION_DAYS`.
+pub fn team_retention_period() -> Duration {
+    Duration::days(
+        std::env::var("TEAM_RETENTION_DAYS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|days: &i64| *days >= 0)
+            .unwrap_or(DEFAULT_RETENTION_DAYS),
+    )
+}
+
+/// Whether the team exists and is archived.
+pub async fn is_team_archived(team_id: &str) -> bool {
+    let pool = get_connection().await;
+    let query = "SELECT EXISTS (SELECT 1 FROM teams WHERE id = $1 AND archived_at IS NOT NULL)";
+    match sqlx::query_scalar::<_, bool>(query)
+        .bind(team_id)
+        .fetch_one(&pool)
+        .await
+    {
+        Ok(is_archived) => is_archived,
+        Err(err) => {
+            println!("Error checking team archive: {:?}", err);
+            false
+        }
+    }
+}
+
+/// Archives the team: it turns read-only, drops out of every list and is
+/// purged once the retention period is over. A pending ownership offer is
+/// withdrawn.
+pub async fn archive_team(team_id: &str) -> Result<Team, TeamError> {
+    let purge_after = OffsetDateTime::now_utc() + team_retention_period();
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE teams SET archived_at = CURRENT_TIMESTAMP, purge_after = $2, pending_owner_id = NULL, ownership_offered_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND archived_at IS NULL RETURNING *",
+    )
+    .bind(team_id)
+    .bind(purge_after)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => Team::from_row(&row).map_err(|_| TeamError::TeamDeletionFailed),
+        Ok(None) => Err(TeamError::TeamArchived),
+        Err(err) => {
+            println!("Error archiving team: {:?}", err);
+            Err(TeamError::TeamDeletionFailed)
+        }
+    }
+}
+
+/// Brings an archived team back before it is purged.
+pub async fn restore_team(team_id: &str) -> Result<Team, TeamError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE teams SET archived_at = NULL, purge_after = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND archived_at IS NOT NULL RETURNING *",
+    )
+    .bind(team_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => Team::from_row(&row).map_err(|_| TeamError::TeamUpdateFailed),
+        Ok(None) => Err(TeamError::TeamNotArchived),
+        Err(err) => {
+            println!("Error restoring team: {:?}", err);
+            Err(TeamError::TeamUpdateFailed)
+        }
+    }
+}
+
+async fn execute(
+    tx: &mut Transaction<'_, Postgres>,
+    statement: &str,
+    team_id: &str,
+) -> Result<(), TeamError> {
+    match sqlx::query(statement)
+        .bind(team_id)
+        .execute(&mut **tx)
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            println!("Error running `{}`: {:?}", statement, err);
+            Err(TeamError::TeamDeletionFailed)
+        }
+    }
+}
+
+/// Permanently removes one archived team along with its activities,
+/// invitations, memberships, join links and join requests. Sub-teams are
+/// left in place at the top level.
+async fn purge_team(team_id: &str) -> Result<(), TeamError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting team purge: {:?}", err);
+        TeamError::TeamDeletionFailed
+    })?;
+    let statements = [
+        "DELETE FROM activities WHERE team_id = $1",
+        "DELETE FROM invitations WHERE team_id = $1",
+        "DELETE FROM join_requests WHERE team_id = $1",
+        "DELETE FROM teams_users WHERE team_id = $1",
+        "DELETE FROM join_links WHERE team_id = $1",
+        "DELETE FROM teams WHERE id = $1 AND archived_at IS NOT NULL",
+    ];
+    for statement in statements {
+        execute(&mut tx, statement, team_id).await?;
+    }
+    tx.commit().await.map_err(|err| {
+        println!("Error committing team purge: {:?}", err);
+        TeamError::TeamDeletionFailed
+    })
+}
+
+/// Purges every archived team whose retention period has passed.
+pub async fn purge_expired_teams() {
+    let pool = get_connection().await;
+    let team_ids = match sqlx::query(
+        "SELECT id FROM teams WHERE archived_at IS NOT NULL AND purge_after <= CURRENT_TIMESTAMP",
+    )
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(rows) => rows
+            .iter()
+            .map(|row| row.get::<String, _>("id"))
+            .collect::<Vec<String>>(),
+        Err(err) => {
+            println!("Error finding teams to purge: {:?}", err);
+            return;
+        }
+    };
+    for team_id in team_ids {
+        match purge_team(&team_id).await {
+            Ok(_) => {
+                record_audit_event(
+                    AuditEntry::new(AuditAction::TeamDeleted)
+                        .team(&team_id)
+                        .target("team", &team_id),
+                )
+                .await
+            }
+            Err(err) => println!("Error purging team {}: {:?}", team_id, err),
+        }
+    }
+}
+
+/// Runs [`purge_expired_teams`] in the background every
+/// `TEAM_PURGE_INTERVAL_MINUTES`.
+pub fn spawn_team_purge() {
+    spawn_periodic(
+        interval_from_env(
+            "TEAM_PURGE_INTERVAL_MINUTES",
+            DEFAULT_PURGE_INTERVAL_MINUTES,
+        ),
+        purge_expired_teams,
+    );
+}
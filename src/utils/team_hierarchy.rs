@@ -0,0 +1,163 @@
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::activity::Activity;
+use crate::models::permission::Permission;
+use crate::models::team::{Team, TeamError};
+use crate::models::team_role::TeamRole;
+use crate::models::user::User;
+
+/// The team and the chain of active parents whose roles it inherits.
+/// `UNION` drops repeated rows, so the walk ends even on a malformed loop.
+const INHERITED_TEAMS: &str = "WITH RECURSIVE inherited AS (SELECT id, parent_team_id, inherits_permissions FROM teams WHERE id = $1 UNION SELECT teams.id, teams.parent_team_id, teams.inherits_permissions FROM teams JOIN inherited ON teams.id = inherited.parent_team_id WHERE inherited.inherits_permissions AND teams.archived_at IS NULL)";
+
+/// The team and every active team below it. The team itself may be
+/// archived, since archived teams can still be read.
+const DESCENDANT_TEAMS: &str = "WITH RECURSIVE descendants AS (SELECT id FROM teams WHERE id = $1 UNION SELECT teams.id FROM teams JOIN descendants ON teams.parent_team_id = descendants.id WHERE teams.archived_at IS NULL)";
+
+/// The strongest role the user holds on the team, counting roles on parent
+/// teams it inherits from. Owning such a parent counts as being its admin.
+pub async fn effective_team_role(team_id: &str, user_id: &str) -> Option<TeamRole> {
+    let pool = get_connection().await;
+    let query = format!(
+        "{} SELECT role FROM (SELECT teams_users.team_role AS role FROM teams_users JOIN users ON users.id = teams_users.user_id WHERE teams_users.team_id IN (SELECT id FROM inherited) AND teams_users.user_id = $2 AND teams_users.archived_at IS NULL AND users.archived_at IS NULL UNION ALL SELECT 'admin' FROM teams WHERE id IN (SELECT id FROM inherited) AND id <> $1 AND owner_id = $2) AS roles ORDER BY CASE role WHEN 'admin' THEN 0 WHEN 'manager' THEN 1 ELSE 2 END LIMIT 1",
+        INHERITED_TEAMS
+    );
+    match sqlx::query_scalar::<_, String>(&query)
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+    {
+        Ok(role) => role.and_then(|role| role.parse().ok()),
+        Err(err) => {
+            println!("Error finding effective team role: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Whether the user owns the team or holds a role on it, directly or
+/// inherited, that grants `permission`.
+pub async fn has_team_permission(team_id: &str, user_id: &str, permission: Permission) -> bool {
+    let pool = get_connection().await;
+    let query = "SELECT EXISTS (SELECT 1 FROM teams WHERE id = $1 AND owner_id = $2 AND archived_at IS NULL)";
+    let is_owner = sqlx::query_scalar::<_, bool>(query)
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap_or(false);
+    is_owner
+        || effective_team_role(team_id, user_id)
+            .await
+            .is_some_and(|role| permission.allows(&role))
+}
+
+/// Ids of the team and all of its active sub-teams, however deep.
+pub async fn descendant_team_ids(team_id: &str) -> Result<Vec<String>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = format!("{} SELECT id FROM descendants", DESCENDANT_TEAMS);
+    sqlx::query_scalar::<_, String>(&query)
+        .bind(team_id)
+        .fetch_all(&pool)
+        .await
+}
+
+/// Active teams directly under the team.
+pub async fn child_teams(team_id: &str) -> Result<Vec<Team>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM teams WHERE parent_team_id = $1 AND archived_at IS NULL ORDER BY team_name ASC";
+    let rows = sqlx::query(query).bind(team_id).fetch_all(&pool).await?;
+    rows.iter().map(Team::from_row).collect()
+}
+
+/// Owners and active members of any of the teams, each listed once.
+pub async fn members_of_teams(team_ids: &[String]) -> Result<Vec<User>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM users WHERE archived_at IS NULL AND (id IN (SELECT owner_id FROM teams WHERE id = ANY($1)) OR id IN (SELECT user_id FROM teams_users WHERE team_id = ANY($1) AND archived_at IS NULL)) ORDER BY created_at ASC";
+    let rows = sqlx::query(query).bind(team_ids).fetch_all(&pool).await?;
+    rows.iter().map(User::from_row).collect()
+}
+
+/// Active activities of any of the teams.
+pub async fn activities_of_teams(team_ids: &[String]) -> Result<Vec<Activity>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM activities WHERE team_id = ANY($1) AND archived_at IS NULL ORDER BY created_at ASC";
+    let rows = sqlx::query(query).bind(team_ids).fetch_all(&pool).await?;
+    rows.iter().map(Activity::from_row).collect()
+}
+
+/// Moves the team under `parent_team_id`, or to the top level with `None`.
+/// The parent has to be an active team of the same organization and can't
+/// be the team itself or one of its sub-teams. Hierarchy changes in an
+/// organization are serialized so two moves can't close a loop together.
+pub async fn set_parent_team(
+    team: &Team,
+    parent_team_id: Option<&str>,
+    inherits_permissions: bool,
+) -> Result<Team, TeamError> {
+    let team_id = team.id.clone().unwrap_or_default();
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting team move: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    if let Err(err) = sqlx::query("SELECT id FROM organizations WHERE id = $1 FOR UPDATE")
+        .bind(&team.organization_id)
+        .fetch_optional(&mut *tx)
+        .await
+    {
+        println!("Error locking organization: {:?}", err);
+        return Err(TeamError::TeamUpdateFailed);
+    }
+
+    if let Some(parent_team_id) = parent_team_id {
+        let parent_exists = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM teams WHERE id = $1 AND organization_id = $2 AND archived_at IS NULL)",
+        )
+        .bind(parent_team_id)
+        .bind(&team.organization_id)
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap_or(false);
+        if !parent_exists {
+            return Err(TeamError::ParentTeamNotFound);
+        }
+
+        // Walk up from the new parent through archived teams too, since
+        // restoring one would otherwise close the loop.
+        let query = "WITH RECURSIVE ancestors AS (SELECT id, parent_team_id FROM teams WHERE id = $2 UNION SELECT teams.id, teams.parent_team_id FROM teams JOIN ancestors ON teams.id = ancestors.parent_team_id) SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $1)";
+        match sqlx::query_scalar::<_, bool>(query)
+            .bind(&team_id)
+            .bind(parent_team_id)
+            .fetch_one(&mut *tx)
+            .await
+        {
+            Ok(false) => {}
+            Ok(true) => return Err(TeamError::TeamHierarchyCycle),
+            Err(err) => {
+                println!("Error checking team hierarchy: {:?}", err);
+                return Err(TeamError::TeamUpdateFailed);
+            }
+        }
+    }
+
+    let team = sqlx::query(
+        "UPDATE teams SET parent_team_id = $2, inherits_permissions = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(&team_id)
+    .bind(parent_team_id)
+    .bind(parent_team_id.is_some() && inherits_permissions)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| Team::from_row(&row))
+    .map_err(|err| {
+        println!("Error moving team: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing team move: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    Ok(team)
+}
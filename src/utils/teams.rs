@@ -0,0 +1,417 @@
+use crate::database::connection::get_connection;
+use crate::database::traits::DatabaseResource;
+use crate::models::team::{Team, TeamError};
+use crate::models::team_role::TeamRole;
+use crate::models::team_user::{TeamUser, TeamUserError};
+use crate::models::user::User;
+use crate::utils::admin::search_pattern;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+/// The user's role on the team, if they are an active member of it.
+pub async fn team_role(team_id: &str, user_id: &str) -> Option<TeamRole> {
+    let pool = get_connection().await;
+    let query = "SELECT teams_users.team_role FROM teams_users JOIN users ON users.id = teams_users.user_id WHERE teams_users.team_id = $1 AND teams_users.user_id = $2 AND teams_users.archived_at IS NULL AND users.archived_at IS NULL";
+    match sqlx::query_scalar::<_, String>(query)
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+    {
+        Ok(role) => role.and_then(|role| role.parse().ok()),
+        Err(err) => {
+            println!("Error finding team role: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Whether the user owns the team or is an active member of it.
+pub async fn is_team_member(team_id: &str, user_id: &str) -> bool {
+    let pool = get_connection().await;
+    let query = "SELECT EXISTS (SELECT 1 FROM teams WHERE id = $1 AND owner_id = $2 AND archived_at IS NULL)";
+    let is_owner = sqlx::query_scalar::<_, bool>(query)
+        .bind(team_id)
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap_or(false);
+    is_owner || team_role(team_id, user_id).await.is_some()
+}
+
+/// Active teams the user owns or is a member of.
+pub async fn user_teams(user_id: &str) -> Result<Vec<Team>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM teams WHERE archived_at IS NULL AND (owner_id = $1 OR id IN (SELECT team_id FROM teams_users WHERE user_id = $1 AND archived_at IS NULL)) ORDER BY created_at ASC";
+    let rows = sqlx::query(query).bind(user_id).fetch_all(&pool).await?;
+    rows.iter().map(Team::from_row).collect()
+}
+
+/// Active discoverable teams in the user's organizations, optionally
+/// narrowed to those whose name or description contains `search`.
+pub async fn discoverable_teams(
+    user_id: &str,
+    search: Option<&str>,
+) -> Result<Vec<Team>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM teams WHERE is_discoverable AND archived_at IS NULL AND organization_id IN (SELECT organization_id FROM organizations_users WHERE user_id = $1 AND archived_at IS NULL) AND ($2::TEXT IS NULL OR team_name ILIKE '%' || $2 || '%' OR team_description ILIKE '%' || $2 || '%') ORDER BY team_name ASC";
+    let search = search
+        .filter(|search| !search.trim().is_empty())
+        .map(search_pattern);
+    let rows = sqlx::query(query)
+        .bind(user_id)
+        .bind(search)
+        .fetch_all(&pool)
+        .await?;
+    rows.iter().map(Team::from_row).collect()
+}
+
+/// The team's owner and active members.
+pub async fn team_members(team_id: &str) -> Result<Vec<User>, sqlx::Error> {
+    let pool = get_connection().await;
+    let query = "SELECT * FROM users WHERE archived_at IS NULL AND (id IN (SELECT owner_id FROM teams WHERE id = $1) OR id IN (SELECT user_id FROM teams_users WHERE team_id = $1 AND archived_at IS NULL)) ORDER BY created_at ASC";
+    let rows = sqlx::query(query).bind(team_id).fetch_all(&pool).await?;
+    rows.iter().map(User::from_row).collect()
+}
+
+/// Moves the team to `new_owner_id` and clears any pending offer. The new
+/// owner and the previous one both end up as admins of the team.
+async fn hand_over_team(
+    tx: &mut Transaction<'_, Postgres>,
+    team_id: &str,
+    owner_id: &str,
+    new_owner_id: &str,
+) -> Result<Team, TeamError> {
+    let team = match sqlx::query(
+        "UPDATE teams SET owner_id = $3, pending_owner_id = NULL, ownership_offered_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND owner_id = $2 AND archived_at IS NULL RETURNING *",
+    )
+    .bind(team_id)
+    .bind(owner_id)
+    .bind(new_owner_id)
+    .fetch_optional(&mut **tx)
+    .await
+    {
+        Ok(Some(row)) => Team::from_row(&row).map_err(|_| TeamError::TeamUpdateFailed)?,
+        Ok(None) => return Err(TeamError::TeamNotFound),
+        Err(err) => {
+            println!("Error transferring team: {:?}", err);
+            return Err(TeamError::TeamUpdateFailed);
+        }
+    };
+    for user_id in [new_owner_id, owner_id] {
+        if let Err(err) = sqlx::query(
+            "INSERT INTO teams_users (id, team_id, user_id, team_role) VALUES ($1, $2, $3, $4) ON CONFLICT (team_id, user_id) DO UPDATE SET team_role = EXCLUDED.team_role, archived_at = NULL, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(team_id)
+        .bind(user_id)
+        .bind(TeamRole::Admin.to_string())
+        .execute(&mut **tx)
+        .await
+        {
+            println!("Error updating team roles: {:?}", err);
+            return Err(TeamError::TeamUpdateFailed);
+        }
+    }
+    Ok(team)
+}
+
+/// Hands a team over to one of its members, who becomes an admin of it.
+/// Only the current owner's teams that are still active can be transferred.
+pub async fn transfer_team_ownership(
+    team_id: &str,
+    owner_id: &str,
+    new_owner_id: &str,
+) -> Result<Team, TeamError> {
+    if owner_id == new_owner_id {
+        return Err(TeamError::InvalidOwner);
+    }
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting ownership transfer: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    let is_member = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM teams_users JOIN users ON users.id = teams_users.user_id WHERE teams_users.team_id = $1 AND teams_users.user_id = $2 AND teams_users.archived_at IS NULL AND users.archived_at IS NULL)",
+    )
+    .bind(team_id)
+    .bind(new_owner_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or(false);
+    if !is_member {
+        return Err(TeamError::InvalidOwner);
+    }
+    let team = hand_over_team(&mut tx, team_id, owner_id, new_owner_id).await?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing ownership transfer: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    Ok(team)
+}
+
+/// First step of a transfer: the owner offers the team to one of its
+/// admins. A new offer replaces any earlier one.
+pub async fn offer_team_ownership(
+    team_id: &str,
+    owner_id: &str,
+    nominee_id: &str,
+) -> Result<Team, TeamError> {
+    if owner_id == nominee_id || team_role(team_id, nominee_id).await != Some(TeamRole::Admin) {
+        return Err(TeamError::NomineeNotAdmin);
+    }
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE teams SET pending_owner_id = $3, ownership_offered_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND owner_id = $2 AND archived_at IS NULL RETURNING *",
+    )
+    .bind(team_id)
+    .bind(owner_id)
+    .bind(nominee_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => Team::from_row(&row).map_err(|_| TeamError::TeamUpdateFailed),
+        Ok(None) => Err(TeamError::TeamNotFound),
+        Err(err) => {
+            println!("Error offering team ownership: {:?}", err);
+            Err(TeamError::TeamUpdateFailed)
+        }
+    }
+}
+
+/// Withdraws or declines the pending offer. `user_id` must be the owner or
+/// the nominee.
+pub async fn cancel_team_ownership_offer(team_id: &str, user_id: &str) -> Result<Team, TeamError> {
+    let pool = get_connection().await;
+    match sqlx::query(
+        "UPDATE teams SET pending_owner_id = NULL, ownership_offered_at = NULL, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND pending_owner_id IS NOT NULL AND (owner_id = $2 OR pending_owner_id = $2) AND archived_at IS NULL RETURNING *",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_optional(&pool)
+    .await
+    {
+        Ok(Some(row)) => Team::from_row(&row).map_err(|_| TeamError::TeamUpdateFailed),
+        Ok(None) => Err(TeamError::NoOwnershipOffer),
+        Err(err) => {
+            println!("Error cancelling ownership offer: {:?}", err);
+            Err(TeamError::TeamUpdateFailed)
+        }
+    }
+}
+
+/// Second step of a transfer: the nominee takes the team over. They must
+/// still be an admin. Returns the team and the previous owner's id.
+pub async fn accept_team_ownership(
+    team_id: &str,
+    user_id: &str,
+) -> Result<(Team, String), TeamError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting ownership transfer: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    let owner_id = match sqlx::query_scalar::<_, String>(
+        "SELECT owner_id FROM teams WHERE id = $1 AND pending_owner_id = $2 AND archived_at IS NULL FOR UPDATE",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(owner_id)) => owner_id,
+        Ok(None) => return Err(TeamError::NoOwnershipOffer),
+        Err(err) => {
+            println!("Error finding ownership offer: {:?}", err);
+            return Err(TeamError::TeamUpdateFailed);
+        }
+    };
+    let is_admin = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM teams_users JOIN users ON users.id = teams_users.user_id WHERE teams_users.team_id = $1 AND teams_users.user_id = $2 AND teams_users.team_role = $3 AND teams_users.archived_at IS NULL AND users.archived_at IS NULL)",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(TeamRole::Admin.to_string())
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap_or(false);
+    if !is_admin {
+        return Err(TeamError::NomineeNotAdmin);
+    }
+    let team = hand_over_team(&mut tx, team_id, &owner_id, user_id).await?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing ownership transfer: {:?}", err);
+        TeamError::TeamUpdateFailed
+    })?;
+    Ok((team, owner_id))
+}
+
+/// Locks the team's admins and says whether any would remain without
+/// `user_id`. An active owner counts as an admin.
+async fn has_other_admin(
+    tx: &mut Transaction<'_, Postgres>,
+    team_id: &str,
+    user_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let admins = sqlx::query_scalar::<_, String>(
+        "SELECT teams_users.user_id FROM teams_users JOIN users ON users.id = teams_users.user_id WHERE teams_users.team_id = $1 AND teams_users.team_role = $2 AND teams_users.archived_at IS NULL AND users.archived_at IS NULL FOR UPDATE OF teams_users",
+    )
+    .bind(team_id)
+    .bind(TeamRole::Admin.to_string())
+    .fetch_all(&mut **tx)
+    .await?;
+    if admins.iter().any(|admin| admin != user_id) {
+        return Ok(true);
+    }
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM teams JOIN users ON users.id = teams.owner_id WHERE teams.id = $1 AND teams.owner_id <> $2 AND users.archived_at IS NULL)",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await
+}
+
+/// Locks and returns the member's active membership. The owner's standing
+/// comes from owning the team, so it can't be changed here.
+async fn lock_membership(
+    tx: &mut Transaction<'_, Postgres>,
+    team_id: &str,
+    user_id: &str,
+) -> Result<TeamUser, TeamUserError> {
+    let is_owner = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM teams WHERE id = $1 AND owner_id = $2)",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await
+    .unwrap_or(false);
+    if is_owner {
+        return Err(TeamUserError::TeamOwnerMembership);
+    }
+    match sqlx::query(
+        "SELECT * FROM teams_users WHERE team_id = $1 AND user_id = $2 AND archived_at IS NULL FOR UPDATE",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .fetch_optional(&mut **tx)
+    .await
+    {
+        Ok(Some(row)) => TeamUser::from_row(&row).map_err(|_| TeamUserError::TeamUserNotFound),
+        Ok(None) => Err(TeamUserError::TeamUserNotFound),
+        Err(err) => {
+            println!("Error finding team member: {:?}", err);
+            Err(TeamUserError::TeamUserNotFound)
+        }
+    }
+}
+
+/// Changes a member's role. Demoting the last admin is refused.
+pub async fn change_team_role(
+    team_id: &str,
+    user_id: &str,
+    team_role: TeamRole,
+) -> Result<TeamUser, TeamUserError> {
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting role change: {:?}", err);
+        TeamUserError::TeamUserUpdateFailed
+    })?;
+    let membership = lock_membership(&mut tx, team_id, user_id).await?;
+    if membership.team_role == Some(TeamRole::Admin) && team_role != TeamRole::Admin {
+        let has_other_admin = has_other_admin(&mut tx, team_id, user_id)
+            .await
+            .map_err(|err| {
+                println!("Error counting team admins: {:?}", err);
+                TeamUserError::TeamUserUpdateFailed
+            })?;
+        if !has_other_admin {
+            return Err(TeamUserError::LastTeamAdmin);
+        }
+    }
+    let membership = sqlx::query(
+        "UPDATE teams_users SET team_role = $3, updated_at = CURRENT_TIMESTAMP WHERE team_id = $1 AND user_id = $2 AND archived_at IS NULL RETURNING *",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(team_role.to_string())
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| TeamUser::from_row(&row))
+    .map_err(|err| {
+        println!("Error changing team role: {:?}", err);
+        TeamUserError::TeamUserUpdateFailed
+    })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing role change: {:?}", err);
+        TeamUserError::TeamUserUpdateFailed
+    })?;
+    Ok(membership)
+}
+
+/// Takes a member off the team, whether they leave or are removed. Their
+/// open activities on the team go to `hand_over_to`, another member, or are
+/// unassigned. Returns the ended membership and how many activities moved.
+pub async fn remove_team_member(
+    team_id: &str,
+    user_id: &str,
+    hand_over_to: Option<&str>,
+) -> Result<(TeamUser, u64), TeamUserError> {
+    if hand_over_to == Some(user_id) {
+        return Err(TeamUserError::InvalidAssignee);
+    }
+    if let Some(assignee) = hand_over_to {
+        if !is_team_member(team_id, assignee).await {
+            return Err(TeamUserError::InvalidAssignee);
+        }
+    }
+    let pool = get_connection().await;
+    let mut tx = pool.begin().await.map_err(|err| {
+        println!("Error starting member removal: {:?}", err);
+        TeamUserError::TeamUserDeletionFailed
+    })?;
+    let membership = lock_membership(&mut tx, team_id, user_id).await?;
+    if membership.team_role == Some(TeamRole::Admin) {
+        let has_other_admin = has_other_admin(&mut tx, team_id, user_id)
+            .await
+            .map_err(|err| {
+                println!("Error counting team admins: {:?}", err);
+                TeamUserError::TeamUserDeletionFailed
+            })?;
+        if !has_other_admin {
+            return Err(TeamUserError::LastTeamAdmin);
+        }
+    }
+    let handed_over = match sqlx::query(
+        "UPDATE activities SET assigned_to = $3, updated_at = CURRENT_TIMESTAMP WHERE team_id = $1 AND assigned_to = $2 AND ended_at IS NULL AND archived_at IS NULL",
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(hand_over_to)
+    .execute(&mut *tx)
+    .await
+    {
+        Ok(result) => result.rows_affected(),
+        Err(err) => {
+            println!("Error handing over activities: {:?}", err);
+            return Err(TeamUserError::TeamUserDeletionFailed);
+        }
+    };
+    let membership = sqlx::query(
+        "UPDATE teams_users SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING *",
+    )
+    .bind(&membership.id)
+    .fetch_one(&mut *tx)
+    .await
+    .and_then(|row| TeamUser::from_row(&row))
+    .map_err(|err| {
+        println!("Error removing team member: {:?}", err);
+        TeamUserError::TeamUserDeletionFailed
+    })?;
+    tx.commit().await.map_err(|err| {
+        println!("Error committing member removal: {:?}", err);
+        TeamUserError::TeamUserDeletionFailed
+    })?;
+    Ok((membership, handed_over))
+}
@@ -0,0 +1,279 @@
+use sqlx::Row;
+use time::{Duration, OffsetDateTime};
+
+use crate::api::client::ClientInfo;
+use crate::database::connection::get_connection;
+use crate::models::authentication::AuthenticationError;
+
+const DEFAULT_BACKOFF_BASE_SECONDS: i64 = 1;
+const DEFAULT_BACKOFF_MAX_SECONDS: i64 = 300;
+const DEFAULT_USERNAME_LOCKOUT_THRESHOLD: i64 = 10;
+const DEFAULT_IP_LOCKOUT_THRESHOLD: i64 = 50;
+const DEFAULT_SECOND_FACTOR_LOCKOUT_THRESHOLD: i64 = 5;
+const DEFAULT_LOCKOUT_MINUTES: i64 = 15;
+const DEFAULT_WINDOW_MINUTES: i64 = 15;
+
+fn env_i64(name: &str, default: i64) -> i64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+/// Throttling settings, read from `LOGIN_BACKOFF_BASE_SECONDS`,
+/// `LOGIN_BACKOFF_MAX_SECONDS`, `LOGIN_USERNAME_LOCKOUT_THRESHOLD`,
+/// `LOGIN_IP_LOCKOUT_THRESHOLD`, `SECOND_FACTOR_LOCKOUT_THRESHOLD`,
+/// `LOGIN_LOCKOUT_MINUTES` and
+/// `LOGIN_ATTEMPT_WINDOW_MINUTES`. Failures older than the window are
+/// forgotten.
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleSettings {
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+    pub lockout_threshold: i64,
+    pub lockout: Duration,
+    pub window: Duration,
+}
+
+impl ThrottleSettings {
+    fn from_env(threshold_name: &str, default_threshold: i64) -> Self {
+        Self {
+            backoff_base: Duration::seconds(env_i64(
+                "LOGIN_BACKOFF_BASE_SECONDS",
+                DEFAULT_BACKOFF_BASE_SECONDS,
+            )),
+            backoff_max: Duration::seconds(env_i64(
+                "LOGIN_BACKOFF_MAX_SECONDS",
+                DEFAULT_BACKOFF_MAX_SECONDS,
+            )),
+            lockout_threshold: env_i64(threshold_name, default_threshold),
+            lockout: Duration::minutes(env_i64("LOGIN_LOCKOUT_MINUTES", DEFAULT_LOCKOUT_MINUTES)),
+            window: Duration::minutes(env_i64(
+                "LOGIN_ATTEMPT_WINDOW_MINUTES",
+                DEFAULT_WINDOW_MINUTES,
+            )),
+        }
+    }
+
+    /// How long to block further attempts after `failures` consecutive
+    /// failures: doubling from the base delay up to the maximum, then a full
+    /// lockout once the threshold is reached.
+    pub fn block_for(&self, failures: i64) -> Duration {
+        if failures <= 0 {
+            return Duration::ZERO;
+        }
+        if failures >= self.lockout_threshold {
+            return self.lockout;
+        }
+        let exponent = (failures - 1).min(30) as u32;
+        let delay = self
+            .backoff_base
+            .checked_mul(2i32.saturating_pow(exponent))
+            .unwrap_or(self.backoff_max);
+        delay.min(self.backoff_max)
+    }
+}
+
+/// A counter in `login_attempts`, keyed by username, by client IP or by the
+/// user finishing a login with a second factor.
+#[derive(Debug, Clone)]
+pub struct ThrottleKey {
+    key: String,
+    settings: ThrottleSettings,
+}
+
+impl ThrottleKey {
+    pub fn username(username: &str) -> Self {
+        Self {
+            key: format!("username:{}", username.trim().to_lowercase()),
+            settings: ThrottleSettings::from_env(
+                "LOGIN_USERNAME_LOCKOUT_THRESHOLD",
+                DEFAULT_USERNAME_LOCKOUT_THRESHOLD,
+            ),
+        }
+    }
+
+    pub fn ip_address(ip_address: &str) -> Self {
+        Self {
+            key: format!("ip:{}", ip_address),
+            settings: ThrottleSettings::from_env(
+                "LOGIN_IP_LOCKOUT_THRESHOLD",
+                DEFAULT_IP_LOCKOUT_THRESHOLD,
+            ),
+        }
+    }
+
+    pub fn second_factor(user_id: &str) -> Self {
+        Self {
+            key: format!("second_factor:{}", user_id),
+            settings: ThrottleSettings::from_env(
+                "SECOND_FACTOR_LOCKOUT_THRESHOLD",
+                DEFAULT_SECOND_FACTOR_LOCKOUT_THRESHOLD,
+            ),
+        }
+    }
+
+    /// Whether `failures` consecutive failures reach the lockout.
+    pub fn is_locked_out(&self, failures: i64) -> bool {
+        failures >= self.settings.lockout_threshold
+    }
+}
+
+/// The keys a login attempt is counted against.
+pub fn login_keys(username: &str, client: &ClientInfo) -> Vec<ThrottleKey> {
+    let mut keys = vec![ThrottleKey::username(username)];
+    if let Some(ip_address) = &client.ip_address {
+        keys.push(ThrottleKey::ip_address(ip_address));
+    }
+    keys
+}
+
+/// Rejected login attempt, with the number of seconds to send back in
+/// `Retry-After`.
+#[derive(Debug, Clone)]
+pub struct Throttled {
+    pub error: AuthenticationError,
+    pub retry_after: i64,
+}
+
+/// Fails if any of the keys is currently blocked. Blocks that reached the
+/// lockout threshold are reported as `AccountLocked`.
+pub async fn check_throttle(keys: &[ThrottleKey]) -> Result<(), Throttled> {
+    let pool = get_connection().await;
+    let names = keys
+        .iter()
+        .map(|key| key.key.clone())
+        .collect::<Vec<String>>();
+    let rows = match sqlx::query(
+        "SELECT key, failures, blocked_until FROM login_attempts WHERE key = ANY($1) AND blocked_until > CURRENT_TIMESTAMP",
+    )
+    .bind(&names)
+    .fetch_all(&pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("Error checking login attempts: {:?}", err);
+            return Ok(());
+        }
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let mut throttled: Option<Throttled> = None;
+    for row in rows {
+        let name: String = row.get("key");
+        let failures: i32 = row.get("failures");
+        let blocked_until: OffsetDateTime = row.get("blocked_until");
+        let locked = keys
+            .iter()
+            .find(|key| key.key == name)
+            .is_some_and(|key| i64::from(failures) >= key.settings.lockout_threshold);
+        let retry_after = (blocked_until - now).whole_seconds().max(1);
+        if throttled
+            .as_ref()
+            .is_none_or(|throttled| retry_after > throttled.retry_after)
+        {
+            throttled = Some(Throttled {
+                error: match locked {
+                    true => AuthenticationError::AccountLocked,
+                    false => AuthenticationError::TooManyAttempts,
+                },
+                retry_after,
+            });
+        }
+    }
+    match throttled {
+        Some(throttled) => Err(throttled),
+        None => Ok(()),
+    }
+}
+
+/// Counts a failed attempt against every key and blocks them for the
+/// resulting backoff. The counter is bumped in a single upsert so concurrent
+/// failures across server instances are all counted. Returns the highest
+/// count among the keys.
+pub async fn record_failure(keys: &[ThrottleKey]) -> i64 {
+    let pool = get_connection().await;
+    let mut highest = 0;
+    for key in keys {
+        let window_start = OffsetDateTime::now_utc() - key.settings.window;
+        let failures: i64 = match sqlx::query(
+            "INSERT INTO login_attempts (key, failures, last_failed_at) VALUES ($1, 1, CURRENT_TIMESTAMP)
+             ON CONFLICT (key) DO UPDATE SET
+                failures = CASE WHEN login_attempts.last_failed_at < $2 THEN 1 ELSE login_attempts.failures + 1 END,
+                last_failed_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+             RETURNING failures",
+        )
+        .bind(&key.key)
+        .bind(window_start)
+        .fetch_one(&pool)
+        .await
+        {
+            Ok(row) => i64::from(row.get::<i32, _>("failures")),
+            Err(err) => {
+                println!("Error recording login attempt: {:?}", err);
+                continue;
+            }
+        };
+
+        highest = highest.max(failures);
+        let blocked_until = OffsetDateTime::now_utc() + key.settings.block_for(failures);
+        if let Err(err) = sqlx::query("UPDATE login_attempts SET blocked_until = $2 WHERE key = $1")
+            .bind(&key.key)
+            .bind(blocked_until)
+            .execute(&pool)
+            .await
+        {
+            println!("Error blocking login attempts: {:?}", err);
+        }
+    }
+    highest
+}
+
+/// Clears the counter for a key after a successful login. Only the username
+/// or second factor key is cleared, so one valid account can't be used to
+/// reset an IP's counter.
+pub async fn clear_failures(key: &ThrottleKey) {
+    let pool = get_connection().await;
+    if let Err(err) = sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+        .bind(&key.key)
+        .execute(&pool)
+        .await
+    {
+        println!("Error clearing login attempts: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ThrottleSettings {
+        ThrottleSettings {
+            backoff_base: Duration::seconds(1),
+            backoff_max: Duration::seconds(30),
+            lockout_threshold: 10,
+            lockout: Duration::minutes(15),
+            window: Duration::minutes(15),
+        }
+    }
+
+    #[test]
+    fn test_block_for_backs_off_exponentially() {
+        let settings = settings();
+        assert_eq!(settings.block_for(0), Duration::ZERO);
+        assert_eq!(settings.block_for(1), Duration::seconds(1));
+        assert_eq!(settings.block_for(2), Duration::seconds(2));
+        assert_eq!(settings.block_for(4), Duration::seconds(8));
+        assert_eq!(settings.block_for(9), Duration::seconds(30));
+    }
+
+    #[test]
+    fn test_block_for_locks_out_at_threshold() {
+        let settings = settings();
+        assert_eq!(settings.block_for(10), Duration::minutes(15));
+        assert_eq!(settings.block_for(500), Duration::minutes(15));
+    }
+}
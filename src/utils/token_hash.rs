@@ -0,0 +1,58 @@
+use std::sync::OnceLock;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+const HASH_HEX_LENGTH: usize = 64;
+
+fn token_hash_secret() -> &'static [u8] {
+    static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+    SECRET.get_or_init(|| {
+        std::env::var("TOKEN_HASH_SECRET")
+            .expect("TOKEN_HASH_SECRET must be set")
+            .into_bytes()
+    })
+}
+
+/// Fails fast at startup instead of on the first login when the secret is
+/// missing.
+pub fn ensure_token_hash_secret() {
+    token_hash_secret();
+}
+
+fn hash_token_with(secret: &[u8], value: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(value.as_bytes());
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Keyed hash of a bearer secret (session token, refresh token, backup code)
+/// for storage and lookup. Without `TOKEN_HASH_SECRET` a database dump alone
+/// isn't enough to recover or brute-force the values.
+pub fn hash_token(value: &str) -> String {
+    hash_token_with(token_hash_secret(), value)
+}
+
+/// Whether a stored value already looks like one of our hashes, as opposed
+/// to a plaintext value written before hashing was introduced.
+pub fn is_token_hash(value: &str) -> bool {
+    value.len() == HASH_HEX_LENGTH && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_token_with() {
+        let hash = hash_token_with(b"secret", "a1b2c3d4e5f6a7");
+        assert_eq!(hash, hash_token_with(b"secret", "a1b2c3d4e5f6a7"));
+        assert_ne!(hash, hash_token_with(b"other", "a1b2c3d4e5f6a7"));
+        assert!(is_token_hash(&hash));
+        assert!(!is_token_hash("a1b2c3d4e5f6a7"));
+    }
+}
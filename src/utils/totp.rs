@@ -0,0 +1,153 @@
+use hmac::{Hmac, Mac};
+use rand::{rng, Rng};
+use sha1::Sha1;
+use time::OffsetDateTime;
+
+use crate::database::values::DatabaseValue;
+use crate::models::authentication::AuthenticationError;
+use crate::models::totp_secret::TotpSecret;
+use crate::utils::backup_codes::redeem_backup_code;
+use crate::{find_one_unarchived_resource_where_fields, update_resource};
+
+const STEP_SECONDS: i64 = 30;
+const CODE_DIGITS: u32 = 6;
+const SECRET_BYTES: usize = 20;
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    rng().fill(&mut secret[..]);
+    base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &secret)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Builds the `otpauth://` URI that authenticator apps scan. The issuer is
+/// taken from `TOTP_ISSUER` and defaults to "Capabilities".
+pub fn otpauth_uri(secret: &str, account_name: &str) -> String {
+    let issuer = std::env::var("TOTP_ISSUER").unwrap_or("Capabilities".to_string());
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(&issuer),
+        percent_encode(account_name),
+        secret,
+        percent_encode(&issuer),
+        CODE_DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn code_at(secret: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(CODE_DIGITS)
+}
+
+/// Checks a code against the current time step and one step either side.
+/// Returns the matched step so callers can store it and refuse to accept the
+/// same code twice.
+pub fn verify_code(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let secret = base32::decode(base32::Alphabet::Rfc4648 { padding: false }, secret)?;
+    let code = code.trim();
+    if code.len() != CODE_DIGITS as usize {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current_step = OffsetDateTime::now_utc().unix_timestamp() / STEP_SECONDS;
+    (current_step - ALLOWED_DRIFT_STEPS..=current_step + ALLOWED_DRIFT_STEPS)
+        .filter(|step| last_used_step.is_none_or(|last_used_step| *step > last_used_step))
+        .find(|step| code_at(&secret, *step) == code)
+}
+
+/// Verifies a second factor for a user, either a TOTP code from their
+/// confirmed secret or one of their unused backup codes.
+pub async fn verify_second_factor(
+    user_id: &str,
+    code: Option<&str>,
+    backup_code: Option<&str>,
+) -> Result<(), AuthenticationError> {
+    if let Some(backup_code) = backup_code.filter(|backup_code| !backup_code.is_empty()) {
+        return match redeem_backup_code(user_id, backup_code).await {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                println!("Error redeeming backup code: {:?}", err);
+                Err(AuthenticationError::InvalidSecondFactor)
+            }
+        };
+    }
+
+    let code = match code.filter(|code| !code.is_empty()) {
+        Some(code) => code,
+        None => return Err(AuthenticationError::SecondFactorRequired),
+    };
+
+    let secret_params = [("user_id", DatabaseValue::String(user_id.to_string()))];
+    let totp_secret =
+        match find_one_unarchived_resource_where_fields!(TotpSecret, secret_params).await {
+            Ok(totp_secret) if totp_secret.is_confirmed() => totp_secret,
+            Ok(_) => return Err(AuthenticationError::TwoFactorNotEnabled),
+            Err(err) => {
+                println!("Error finding TOTP secret: {:?}", err);
+                return Err(AuthenticationError::TwoFactorNotEnabled);
+            }
+        };
+
+    let secret = totp_secret.secret.clone().unwrap_or_default();
+    let step = match verify_code(&secret, code, totp_secret.last_used_step) {
+        Some(step) => step,
+        None => return Err(AuthenticationError::InvalidSecondFactor),
+    };
+
+    let totp_secret_id = totp_secret.id.unwrap();
+    let step_params = vec![("last_used_step", DatabaseValue::Int64(step.to_string()))];
+    match update_resource!(TotpSecret, totp_secret_id, step_params).await {
+        Ok(_) => Ok(()),
+        Err(err) => {
+            println!("Error updating TOTP secret: {:?}", err);
+            Err(AuthenticationError::InvalidSecondFactor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_at_matches_rfc_6238() {
+        let secret = b"12345678901234567890";
+        assert_eq!(code_at(secret, 59 / STEP_SECONDS), 287082);
+        assert_eq!(code_at(secret, 1111111109 / STEP_SECONDS), 81804);
+        assert_eq!(code_at(secret, 1234567890 / STEP_SECONDS), 5924);
+    }
+
+    #[test]
+    fn test_verify_code_rejects_reused_step() {
+        let secret = generate_secret();
+        let decoded =
+            base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &secret).unwrap();
+        let step = OffsetDateTime::now_utc().unix_timestamp() / STEP_SECONDS;
+        let code = format!("{:06}", code_at(&decoded, step));
+        let matched_step = verify_code(&secret, &code, None).unwrap();
+        assert_eq!(verify_code(&secret, &code, Some(matched_step)), None);
+        assert_eq!(verify_code(&secret, "abcdef", None), None);
+    }
+}